@@ -1,8 +1,10 @@
-use alloy_primitives::{Address, Bloom, BloomInput, U256};
+use std::collections::HashMap;
+
+use alloy_primitives::{Address, Bloom, BloomInput, U256, uint};
 use alloy_sol_types::{SolEvent, sol};
 use serde::Serialize;
 
-use crate::flashblocks::ReceiptLog;
+use crate::flashblocks::{ContextLog, EventContext, ReceiptLog};
 
 // AAVE V3 Pool events for user actions
 sol! {
@@ -53,6 +55,76 @@ sol! {
         address liquidator,
         bool receiveAToken
     );
+
+    /// Emitted whenever a reserve's rates and indexes are updated
+    event ReserveDataUpdated(
+        address indexed reserve,
+        uint256 liquidityRate,
+        uint256 stableBorrowRate,
+        uint256 variableBorrowRate,
+        uint256 liquidityIndex,
+        uint256 variableBorrowIndex
+    );
+
+    /// Emitted on a flash loan; `interestRateMode` is 0 unless the loan was
+    /// left open as debt
+    event FlashLoan(
+        address indexed target,
+        address initiator,
+        address indexed asset,
+        uint256 amount,
+        uint8 interestRateMode,
+        uint256 premium,
+        uint16 indexed referralCode
+    );
+
+    /// Emitted when a user switches efficiency mode category (0 = none)
+    event UserEModeSet(address indexed user, uint8 categoryId);
+
+    /// Emitted when a user starts using a reserve as collateral
+    event ReserveUsedAsCollateralEnabled(address indexed reserve, address indexed user);
+
+    /// Emitted when a user stops using a reserve as collateral
+    event ReserveUsedAsCollateralDisabled(address indexed reserve, address indexed user);
+
+    /// Emitted when the debt backed by an isolated collateral changes
+    event IsolationModeTotalDebtUpdated(address indexed asset, uint256 totalDebt);
+
+    /// Emitted when accrued reserve fees are minted to the treasury
+    event MintedToTreasury(address indexed reserve, uint256 amountMinted);
+
+    /// Emitted when unbacked (portal-bridged) aTokens are backed
+    event BackUnbacked(
+        address indexed reserve,
+        address indexed backer,
+        uint256 amount,
+        uint256 fee
+    );
+}
+
+/// 1e27, the fixed-point unit of AAVE rates and indexes
+pub const RAY: U256 = uint!(1_000_000_000_000_000_000_000_000_000_U256);
+
+const SECONDS_PER_YEAR: f64 = 31_536_000.0;
+
+/// Multiply two ray values, rounding half up like AAVE's `WadRayMath.rayMul`
+pub fn ray_mul(a: U256, b: U256) -> U256 {
+    a.saturating_mul(b).saturating_add(RAY / U256::from(2)) / RAY
+}
+
+/// Divide two ray values, rounding half up like AAVE's `WadRayMath.rayDiv`
+pub fn ray_div(a: U256, b: U256) -> U256 {
+    if b.is_zero() {
+        return U256::ZERO;
+    }
+    a.saturating_mul(RAY).saturating_add(b / U256::from(2)) / b
+}
+
+/// Convert a ray-denominated annual rate into an APY, compounding every
+/// second as the pool does
+pub fn rate_to_apy(rate: U256) -> f64 {
+    let apr = rate.saturating_to::<u128>() as f64 / 1e27;
+    ((apr / SECONDS_PER_YEAR).ln_1p() * SECONDS_PER_YEAR).exp_m1()
 }
 
 /// Detected AAVE events based on bloom filter
@@ -63,6 +135,13 @@ pub struct AaveEvents {
     pub may_have_borrow: bool,
     pub may_have_repay: bool,
     pub may_have_liquidation: bool,
+    pub may_have_reserve_data_updated: bool,
+    pub may_have_flash_loan: bool,
+    pub may_have_user_emode_set: bool,
+    pub may_have_collateral_toggle: bool,
+    pub may_have_isolation_mode_debt_updated: bool,
+    pub may_have_minted_to_treasury: bool,
+    pub may_have_back_unbacked: bool,
 }
 
 impl AaveEvents {
@@ -70,28 +149,59 @@ impl AaveEvents {
     /// Note: Bloom filters can have false positives but no false negatives.
     pub fn from_bloom(bloom: &Bloom) -> Self {
         Self {
-            may_have_supply: bloom.contains_input(BloomInput::Hash(Supply::SIGNATURE_HASH)),
-            may_have_withdraw: bloom.contains_input(BloomInput::Hash(Withdraw::SIGNATURE_HASH)),
-            may_have_borrow: bloom.contains_input(BloomInput::Hash(Borrow::SIGNATURE_HASH)),
-            may_have_repay: bloom.contains_input(BloomInput::Hash(Repay::SIGNATURE_HASH)),
+            may_have_supply: bloom
+                .contains_input(BloomInput::Raw(Supply::SIGNATURE_HASH.as_slice())),
+            may_have_withdraw: bloom
+                .contains_input(BloomInput::Raw(Withdraw::SIGNATURE_HASH.as_slice())),
+            may_have_borrow: bloom
+                .contains_input(BloomInput::Raw(Borrow::SIGNATURE_HASH.as_slice())),
+            may_have_repay: bloom.contains_input(BloomInput::Raw(Repay::SIGNATURE_HASH.as_slice())),
             may_have_liquidation: bloom
-                .contains_input(BloomInput::Hash(LiquidationCall::SIGNATURE_HASH)),
+                .contains_input(BloomInput::Raw(LiquidationCall::SIGNATURE_HASH.as_slice())),
+            may_have_reserve_data_updated: bloom.contains_input(BloomInput::Raw(
+                ReserveDataUpdated::SIGNATURE_HASH.as_slice(),
+            )),
+            may_have_flash_loan: bloom
+                .contains_input(BloomInput::Raw(FlashLoan::SIGNATURE_HASH.as_slice())),
+            may_have_user_emode_set: bloom
+                .contains_input(BloomInput::Raw(UserEModeSet::SIGNATURE_HASH.as_slice())),
+            may_have_collateral_toggle: bloom.contains_input(BloomInput::Raw(
+                ReserveUsedAsCollateralEnabled::SIGNATURE_HASH.as_slice(),
+            )) || bloom.contains_input(BloomInput::Raw(
+                ReserveUsedAsCollateralDisabled::SIGNATURE_HASH.as_slice(),
+            )),
+            may_have_isolation_mode_debt_updated: bloom.contains_input(BloomInput::Raw(
+                IsolationModeTotalDebtUpdated::SIGNATURE_HASH.as_slice(),
+            )),
+            may_have_minted_to_treasury: bloom
+                .contains_input(BloomInput::Raw(MintedToTreasury::SIGNATURE_HASH.as_slice())),
+            may_have_back_unbacked: bloom
+                .contains_input(BloomInput::Raw(BackUnbacked::SIGNATURE_HASH.as_slice())),
         }
     }
 
-    /// Returns true if any AAVE user event might be present
+    /// Returns true if any AAVE pool event might be present
     pub fn any(&self) -> bool {
         self.may_have_supply
             || self.may_have_withdraw
             || self.may_have_borrow
             || self.may_have_repay
             || self.may_have_liquidation
+            || self.may_have_reserve_data_updated
+            || self.may_have_flash_loan
+            || self.may_have_user_emode_set
+            || self.may_have_collateral_toggle
+            || self.may_have_isolation_mode_debt_updated
+            || self.may_have_minted_to_treasury
+            || self.may_have_back_unbacked
     }
 }
 
 /// Parsed AAVE Supply event
 #[derive(Debug, Clone, Serialize)]
 pub struct ParsedSupply {
+    /// Where the event was emitted
+    pub context: EventContext,
     /// Address of the AAVE pool contract
     pub pool: Address,
     /// The reserve (token) being supplied
@@ -108,7 +218,7 @@ pub struct ParsedSupply {
 
 impl ParsedSupply {
     /// Try to parse a Supply event from a log entry
-    pub fn try_from_log(log: &ReceiptLog) -> Option<Self> {
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
         if log.topics.len() != 4 {
             return None;
         }
@@ -120,6 +230,7 @@ impl ParsedSupply {
         let decoded = Supply::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
 
         Some(Self {
+            context: context.clone(),
             pool: log.address,
             reserve: decoded.reserve,
             user: decoded.user,
@@ -130,14 +241,18 @@ impl ParsedSupply {
     }
 
     /// Extract all Supply events from a slice of logs
-    pub fn extract_all(logs: &[ReceiptLog]) -> Vec<Self> {
-        logs.iter().filter_map(Self::try_from_log).collect()
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
     }
 }
 
 /// Parsed AAVE Withdraw event
 #[derive(Debug, Clone, Serialize)]
 pub struct ParsedWithdraw {
+    /// Where the event was emitted
+    pub context: EventContext,
     /// Address of the AAVE pool contract
     pub pool: Address,
     /// The reserve (token) being withdrawn
@@ -152,7 +267,7 @@ pub struct ParsedWithdraw {
 
 impl ParsedWithdraw {
     /// Try to parse a Withdraw event from a log entry
-    pub fn try_from_log(log: &ReceiptLog) -> Option<Self> {
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
         if log.topics.len() != 4 {
             return None;
         }
@@ -164,6 +279,7 @@ impl ParsedWithdraw {
         let decoded = Withdraw::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
 
         Some(Self {
+            context: context.clone(),
             pool: log.address,
             reserve: decoded.reserve,
             user: decoded.user,
@@ -173,14 +289,18 @@ impl ParsedWithdraw {
     }
 
     /// Extract all Withdraw events from a slice of logs
-    pub fn extract_all(logs: &[ReceiptLog]) -> Vec<Self> {
-        logs.iter().filter_map(Self::try_from_log).collect()
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
     }
 }
 
 /// Parsed AAVE Borrow event
 #[derive(Debug, Clone, Serialize)]
 pub struct ParsedBorrow {
+    /// Where the event was emitted
+    pub context: EventContext,
     /// Address of the AAVE pool contract
     pub pool: Address,
     /// The reserve (token) being borrowed
@@ -201,7 +321,7 @@ pub struct ParsedBorrow {
 
 impl ParsedBorrow {
     /// Try to parse a Borrow event from a log entry
-    pub fn try_from_log(log: &ReceiptLog) -> Option<Self> {
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
         if log.topics.len() != 4 {
             return None;
         }
@@ -213,6 +333,7 @@ impl ParsedBorrow {
         let decoded = Borrow::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
 
         Some(Self {
+            context: context.clone(),
             pool: log.address,
             reserve: decoded.reserve,
             user: decoded.user,
@@ -225,14 +346,18 @@ impl ParsedBorrow {
     }
 
     /// Extract all Borrow events from a slice of logs
-    pub fn extract_all(logs: &[ReceiptLog]) -> Vec<Self> {
-        logs.iter().filter_map(Self::try_from_log).collect()
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
     }
 }
 
 /// Parsed AAVE Repay event
 #[derive(Debug, Clone, Serialize)]
 pub struct ParsedRepay {
+    /// Where the event was emitted
+    pub context: EventContext,
     /// Address of the AAVE pool contract
     pub pool: Address,
     /// The reserve (token) being repaid
@@ -249,7 +374,7 @@ pub struct ParsedRepay {
 
 impl ParsedRepay {
     /// Try to parse a Repay event from a log entry
-    pub fn try_from_log(log: &ReceiptLog) -> Option<Self> {
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
         if log.topics.len() != 4 {
             return None;
         }
@@ -261,6 +386,7 @@ impl ParsedRepay {
         let decoded = Repay::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
 
         Some(Self {
+            context: context.clone(),
             pool: log.address,
             reserve: decoded.reserve,
             user: decoded.user,
@@ -271,14 +397,18 @@ impl ParsedRepay {
     }
 
     /// Extract all Repay events from a slice of logs
-    pub fn extract_all(logs: &[ReceiptLog]) -> Vec<Self> {
-        logs.iter().filter_map(Self::try_from_log).collect()
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
     }
 }
 
 /// Parsed AAVE LiquidationCall event
 #[derive(Debug, Clone, Serialize)]
 pub struct ParsedLiquidation {
+    /// Where the event was emitted
+    pub context: EventContext,
     /// Address of the AAVE pool contract
     pub pool: Address,
     /// The collateral asset being liquidated
@@ -299,7 +429,7 @@ pub struct ParsedLiquidation {
 
 impl ParsedLiquidation {
     /// Try to parse a LiquidationCall event from a log entry
-    pub fn try_from_log(log: &ReceiptLog) -> Option<Self> {
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
         if log.topics.len() != 4 {
             return None;
         }
@@ -312,6 +442,7 @@ impl ParsedLiquidation {
             LiquidationCall::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
 
         Some(Self {
+            context: context.clone(),
             pool: log.address,
             collateral_asset: decoded.collateralAsset,
             debt_asset: decoded.debtAsset,
@@ -324,12 +455,464 @@ impl ParsedLiquidation {
     }
 
     /// Extract all LiquidationCall events from a slice of logs
-    pub fn extract_all(logs: &[ReceiptLog]) -> Vec<Self> {
-        logs.iter().filter_map(Self::try_from_log).collect()
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// Parsed AAVE ReserveDataUpdated event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedReserveDataUpdated {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// Address of the AAVE pool contract
+    pub pool: Address,
+    /// The reserve (token) whose data changed
+    pub reserve: Address,
+    /// Supply rate, annual, in ray
+    pub liquidity_rate: U256,
+    /// Stable borrow rate, annual, in ray
+    pub stable_borrow_rate: U256,
+    /// Variable borrow rate, annual, in ray
+    pub variable_borrow_rate: U256,
+    /// Cumulative supply index, in ray
+    pub liquidity_index: U256,
+    /// Cumulative variable borrow index, in ray
+    pub variable_borrow_index: U256,
+}
+
+impl ParsedReserveDataUpdated {
+    /// Try to parse a ReserveDataUpdated event from a log entry
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 2 {
+            return None;
+        }
+
+        if log.topics[0] != ReserveDataUpdated::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded =
+            ReserveDataUpdated::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            pool: log.address,
+            reserve: decoded.reserve,
+            liquidity_rate: decoded.liquidityRate,
+            stable_borrow_rate: decoded.stableBorrowRate,
+            variable_borrow_rate: decoded.variableBorrowRate,
+            liquidity_index: decoded.liquidityIndex,
+            variable_borrow_index: decoded.variableBorrowIndex,
+        })
+    }
+
+    /// Extract all ReserveDataUpdated events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+
+    /// The reserve's state as of this event
+    pub fn state(&self) -> AaveReserveState {
+        AaveReserveState {
+            liquidity_rate: self.liquidity_rate,
+            stable_borrow_rate: self.stable_borrow_rate,
+            variable_borrow_rate: self.variable_borrow_rate,
+            liquidity_index: self.liquidity_index,
+            variable_borrow_index: self.variable_borrow_index,
+            block_number: self.context.block_number,
+        }
+    }
+}
+
+/// Parsed AAVE FlashLoan event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedFlashLoan {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// Address of the AAVE pool contract
+    pub pool: Address,
+    /// The contract that received the loan
+    pub target: Address,
+    /// The address that requested the loan
+    pub initiator: Address,
+    /// The reserve (token) borrowed
+    pub asset: Address,
+    /// Amount borrowed
+    pub amount: U256,
+    /// 0 if repaid in the same transaction, otherwise the debt's rate mode (2 = variable)
+    pub interest_rate_mode: u8,
+    /// Fee paid on the loan
+    pub premium: U256,
+    /// Referral code
+    pub referral_code: u16,
+}
+
+impl ParsedFlashLoan {
+    /// Try to parse a FlashLoan event from a log entry
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 4 {
+            return None;
+        }
+
+        if log.topics[0] != FlashLoan::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded =
+            FlashLoan::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            pool: log.address,
+            target: decoded.target,
+            initiator: decoded.initiator,
+            asset: decoded.asset,
+            amount: decoded.amount,
+            interest_rate_mode: decoded.interestRateMode,
+            premium: decoded.premium,
+            referral_code: decoded.referralCode,
+        })
+    }
+
+    /// Extract all FlashLoan events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// Parsed AAVE UserEModeSet event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedUserEModeSet {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// Address of the AAVE pool contract
+    pub pool: Address,
+    /// The user switching category
+    pub user: Address,
+    /// The new efficiency mode category (0 = none)
+    pub category_id: u8,
+}
+
+impl ParsedUserEModeSet {
+    /// Try to parse a UserEModeSet event from a log entry
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 2 {
+            return None;
+        }
+
+        if log.topics[0] != UserEModeSet::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded =
+            UserEModeSet::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            pool: log.address,
+            user: decoded.user,
+            category_id: decoded.categoryId,
+        })
+    }
+
+    /// Extract all UserEModeSet events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
     }
 }
 
-/// All AAVE user action events extracted from logs
+/// Parsed AAVE ReserveUsedAsCollateralEnabled or ReserveUsedAsCollateralDisabled event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedCollateralToggle {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// Address of the AAVE pool contract
+    pub pool: Address,
+    /// The reserve (token) whose collateral use changed
+    pub reserve: Address,
+    /// The user whose collateral changed
+    pub user: Address,
+    /// Whether the reserve now counts as the user's collateral
+    pub enabled: bool,
+}
+
+impl ParsedCollateralToggle {
+    /// Try to parse a ReserveUsedAsCollateralEnabled/Disabled event from a log entry
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 3 {
+            return None;
+        }
+
+        let topics = log.topics.iter().copied();
+        let (reserve, user, enabled) = if log.topics[0]
+            == ReserveUsedAsCollateralEnabled::SIGNATURE_HASH
+        {
+            let d = ReserveUsedAsCollateralEnabled::decode_raw_log(topics, &log.data, true).ok()?;
+            (d.reserve, d.user, true)
+        } else if log.topics[0] == ReserveUsedAsCollateralDisabled::SIGNATURE_HASH {
+            let d =
+                ReserveUsedAsCollateralDisabled::decode_raw_log(topics, &log.data, true).ok()?;
+            (d.reserve, d.user, false)
+        } else {
+            return None;
+        };
+
+        Some(Self {
+            context: context.clone(),
+            pool: log.address,
+            reserve,
+            user,
+            enabled,
+        })
+    }
+
+    /// Extract all collateral toggle events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// Parsed AAVE IsolationModeTotalDebtUpdated event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedIsolationModeDebtUpdated {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// Address of the AAVE pool contract
+    pub pool: Address,
+    /// The isolated collateral asset
+    pub asset: Address,
+    /// Total debt backed by the asset, in USD with 2 decimals
+    pub total_debt: U256,
+}
+
+impl ParsedIsolationModeDebtUpdated {
+    /// Try to parse an IsolationModeTotalDebtUpdated event from a log entry
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 2 {
+            return None;
+        }
+
+        if log.topics[0] != IsolationModeTotalDebtUpdated::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded = IsolationModeTotalDebtUpdated::decode_raw_log(
+            log.topics.iter().copied(),
+            &log.data,
+            true,
+        )
+        .ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            pool: log.address,
+            asset: decoded.asset,
+            total_debt: decoded.totalDebt,
+        })
+    }
+
+    /// Extract all IsolationModeTotalDebtUpdated events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// Parsed AAVE MintedToTreasury event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedMintedToTreasury {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// Address of the AAVE pool contract
+    pub pool: Address,
+    /// The reserve (token) whose fees were minted
+    pub reserve: Address,
+    /// Amount of aTokens minted to the treasury
+    pub amount_minted: U256,
+}
+
+impl ParsedMintedToTreasury {
+    /// Try to parse a MintedToTreasury event from a log entry
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 2 {
+            return None;
+        }
+
+        if log.topics[0] != MintedToTreasury::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded =
+            MintedToTreasury::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            pool: log.address,
+            reserve: decoded.reserve,
+            amount_minted: decoded.amountMinted,
+        })
+    }
+
+    /// Extract all MintedToTreasury events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// Parsed AAVE BackUnbacked event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedBackUnbacked {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// Address of the AAVE pool contract
+    pub pool: Address,
+    /// The reserve (token) being backed
+    pub reserve: Address,
+    /// The address supplying the backing
+    pub backer: Address,
+    /// Amount of unbacked aTokens backed
+    pub amount: U256,
+    /// Fee paid to suppliers
+    pub fee: U256,
+}
+
+impl ParsedBackUnbacked {
+    /// Try to parse a BackUnbacked event from a log entry
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 3 {
+            return None;
+        }
+
+        if log.topics[0] != BackUnbacked::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded =
+            BackUnbacked::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            pool: log.address,
+            reserve: decoded.reserve,
+            backer: decoded.backer,
+            amount: decoded.amount,
+            fee: decoded.fee,
+        })
+    }
+
+    /// Extract all BackUnbacked events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// A reserve's latest rates and indexes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct AaveReserveState {
+    pub liquidity_rate: U256,
+    pub stable_borrow_rate: U256,
+    pub variable_borrow_rate: U256,
+    pub liquidity_index: U256,
+    pub variable_borrow_index: U256,
+    /// Block of the update the state came from
+    pub block_number: u64,
+}
+
+impl AaveReserveState {
+    pub fn supply_apy(&self) -> f64 {
+        rate_to_apy(self.liquidity_rate)
+    }
+
+    pub fn variable_borrow_apy(&self) -> f64 {
+        rate_to_apy(self.variable_borrow_rate)
+    }
+
+    pub fn stable_borrow_apy(&self) -> f64 {
+        rate_to_apy(self.stable_borrow_rate)
+    }
+
+    /// aToken balance for a scaled supply balance, as of the last update
+    pub fn supply_balance(&self, scaled: U256) -> U256 {
+        ray_mul(scaled, self.liquidity_index)
+    }
+
+    /// Variable debt for a scaled debt balance, as of the last update
+    pub fn variable_debt(&self, scaled: U256) -> U256 {
+        ray_mul(scaled, self.variable_borrow_index)
+    }
+}
+
+/// A reserve update with its rates as APYs, streamed as `Aave_reserve_updated`
+#[derive(Debug, Clone, Serialize)]
+pub struct AaveReserveUpdated {
+    #[serde(flatten)]
+    pub update: ParsedReserveDataUpdated,
+    pub supply_apy: f64,
+    pub variable_borrow_apy: f64,
+    pub stable_borrow_apy: f64,
+}
+
+impl From<ParsedReserveDataUpdated> for AaveReserveUpdated {
+    fn from(update: ParsedReserveDataUpdated) -> Self {
+        let state = update.state();
+        Self {
+            update,
+            supply_apy: state.supply_apy(),
+            variable_borrow_apy: state.variable_borrow_apy(),
+            stable_borrow_apy: state.stable_borrow_apy(),
+        }
+    }
+}
+
+/// Latest state of every reserve seen, keyed by (pool, reserve)
+#[derive(Debug, Clone, Default)]
+pub struct AaveReserveCache {
+    reserves: HashMap<(Address, Address), AaveReserveState>,
+}
+
+impl AaveReserveCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of known reserves
+    pub fn len(&self) -> usize {
+        self.reserves.len()
+    }
+
+    /// Returns true if no reserves are known
+    pub fn is_empty(&self) -> bool {
+        self.reserves.is_empty()
+    }
+
+    pub fn get(&self, pool: &Address, reserve: &Address) -> Option<&AaveReserveState> {
+        self.reserves.get(&(*pool, *reserve))
+    }
+
+    /// Record a reserve update, returning the reserve's previous state
+    pub fn apply(&mut self, update: &ParsedReserveDataUpdated) -> Option<AaveReserveState> {
+        self.reserves
+            .insert((update.pool, update.reserve), update.state())
+    }
+}
+
+/// All AAVE pool events extracted from logs
 #[derive(Debug, Clone, Default, Serialize)]
 pub struct AaveUserUpdates {
     pub supplies: Vec<ParsedSupply>,
@@ -337,27 +920,48 @@ pub struct AaveUserUpdates {
     pub borrows: Vec<ParsedBorrow>,
     pub repays: Vec<ParsedRepay>,
     pub liquidations: Vec<ParsedLiquidation>,
+    pub reserve_data_updates: Vec<ParsedReserveDataUpdated>,
+    pub flash_loans: Vec<ParsedFlashLoan>,
+    pub emode_sets: Vec<ParsedUserEModeSet>,
+    pub collateral_toggles: Vec<ParsedCollateralToggle>,
+    pub isolation_mode_debt_updates: Vec<ParsedIsolationModeDebtUpdated>,
+    pub treasury_mints: Vec<ParsedMintedToTreasury>,
+    pub back_unbacked: Vec<ParsedBackUnbacked>,
 }
 
 impl AaveUserUpdates {
-    /// Extract all AAVE user events from a slice of logs
-    pub fn extract_all(logs: &[ReceiptLog]) -> Self {
+    /// Extract all AAVE pool events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Self {
         Self {
             supplies: ParsedSupply::extract_all(logs),
             withdraws: ParsedWithdraw::extract_all(logs),
             borrows: ParsedBorrow::extract_all(logs),
             repays: ParsedRepay::extract_all(logs),
             liquidations: ParsedLiquidation::extract_all(logs),
+            reserve_data_updates: ParsedReserveDataUpdated::extract_all(logs),
+            flash_loans: ParsedFlashLoan::extract_all(logs),
+            emode_sets: ParsedUserEModeSet::extract_all(logs),
+            collateral_toggles: ParsedCollateralToggle::extract_all(logs),
+            isolation_mode_debt_updates: ParsedIsolationModeDebtUpdated::extract_all(logs),
+            treasury_mints: ParsedMintedToTreasury::extract_all(logs),
+            back_unbacked: ParsedBackUnbacked::extract_all(logs),
         }
     }
 
-    /// Returns true if any AAVE user events were found
+    /// Returns true if no AAVE pool events were found
     pub fn is_empty(&self) -> bool {
         self.supplies.is_empty()
             && self.withdraws.is_empty()
             && self.borrows.is_empty()
             && self.repays.is_empty()
             && self.liquidations.is_empty()
+            && self.reserve_data_updates.is_empty()
+            && self.flash_loans.is_empty()
+            && self.emode_sets.is_empty()
+            && self.collateral_toggles.is_empty()
+            && self.isolation_mode_debt_updates.is_empty()
+            && self.treasury_mints.is_empty()
+            && self.back_unbacked.is_empty()
     }
 
     /// Total count of all events
@@ -367,12 +971,20 @@ impl AaveUserUpdates {
             + self.borrows.len()
             + self.repays.len()
             + self.liquidations.len()
+            + self.reserve_data_updates.len()
+            + self.flash_loans.len()
+            + self.emode_sets.len()
+            + self.collateral_toggles.len()
+            + self.isolation_mode_debt_updates.len()
+            + self.treasury_mints.len()
+            + self.back_unbacked.len()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::flashblocks::test_logs::{context_log, receipt_log};
 
     #[test]
     fn test_aave_events_default() {
@@ -416,4 +1028,158 @@ mod tests {
         );
         assert_eq!(LiquidationCall::SIGNATURE_HASH, expected_sig);
     }
+
+    #[test]
+    fn test_reserve_data_updated() {
+        let signature = alloy_primitives::keccak256(
+            b"ReserveDataUpdated(address,uint256,uint256,uint256,uint256,uint256)",
+        );
+        assert_eq!(ReserveDataUpdated::SIGNATURE_HASH, signature);
+
+        // 5% supply APR, 8% variable borrow APR, indexes 1.1 and 1.25
+        let ray_percent = |pct: u64| RAY * U256::from(pct) / U256::from(100);
+        let event = ReserveDataUpdated {
+            reserve: Address::repeat_byte(0x0e),
+            liquidityRate: ray_percent(5),
+            stableBorrowRate: U256::ZERO,
+            variableBorrowRate: ray_percent(8),
+            liquidityIndex: ray_percent(110),
+            variableBorrowIndex: ray_percent(125),
+        };
+        let data = event.encode_log_data();
+        let log = receipt_log(Address::repeat_byte(0xaa), data);
+        let parsed =
+            ParsedReserveDataUpdated::try_from_log(&log, &EventContext::default()).unwrap();
+        assert_eq!(parsed.pool, log.address);
+        assert_eq!(parsed.reserve, event.reserve);
+
+        let update = AaveReserveUpdated::from(parsed.clone());
+        assert!(
+            (update.supply_apy - 0.05127).abs() < 1e-5,
+            "{}",
+            update.supply_apy
+        );
+        assert!((update.variable_borrow_apy - 0.08328).abs() < 1e-5);
+        assert_eq!(update.stable_borrow_apy, 0.0);
+
+        let mut cache = AaveReserveCache::new();
+        assert_eq!(cache.apply(&parsed), None);
+        assert_eq!(cache.apply(&parsed), Some(parsed.state()));
+        let state = cache.get(&parsed.pool, &parsed.reserve).unwrap();
+        assert_eq!(state.variable_debt(U256::from(1_000)), U256::from(1_250));
+        assert_eq!(state.supply_balance(U256::from(1_000)), U256::from(1_100));
+    }
+
+    #[test]
+    fn test_pool_event_signatures() {
+        use alloy_primitives::keccak256;
+        let cases = [
+            (
+                FlashLoan::SIGNATURE_HASH,
+                &b"FlashLoan(address,address,address,uint256,uint8,uint256,uint16)"[..],
+            ),
+            (UserEModeSet::SIGNATURE_HASH, b"UserEModeSet(address,uint8)"),
+            (
+                ReserveUsedAsCollateralEnabled::SIGNATURE_HASH,
+                b"ReserveUsedAsCollateralEnabled(address,address)",
+            ),
+            (
+                ReserveUsedAsCollateralDisabled::SIGNATURE_HASH,
+                b"ReserveUsedAsCollateralDisabled(address,address)",
+            ),
+            (
+                IsolationModeTotalDebtUpdated::SIGNATURE_HASH,
+                b"IsolationModeTotalDebtUpdated(address,uint256)",
+            ),
+            (
+                MintedToTreasury::SIGNATURE_HASH,
+                b"MintedToTreasury(address,uint256)",
+            ),
+            (
+                BackUnbacked::SIGNATURE_HASH,
+                b"BackUnbacked(address,address,uint256,uint256)",
+            ),
+        ];
+        for (hash, signature) in cases {
+            assert_eq!(
+                hash,
+                keccak256(signature),
+                "{}",
+                String::from_utf8_lossy(signature)
+            );
+        }
+    }
+
+    #[test]
+    fn test_extract_pool_events() {
+        let pool = Address::repeat_byte(0xaa);
+        let user = Address::repeat_byte(0x01);
+        let reserve = Address::repeat_byte(0x0e);
+        let context_log = |data| context_log(pool, data);
+
+        let logs = vec![
+            context_log(
+                FlashLoan {
+                    target: user,
+                    initiator: user,
+                    asset: reserve,
+                    amount: U256::from(1_000_000),
+                    interestRateMode: 0,
+                    premium: U256::from(500),
+                    referralCode: 0,
+                }
+                .encode_log_data(),
+            ),
+            context_log(
+                UserEModeSet {
+                    user,
+                    categoryId: 1,
+                }
+                .encode_log_data(),
+            ),
+            context_log(ReserveUsedAsCollateralEnabled { reserve, user }.encode_log_data()),
+            context_log(ReserveUsedAsCollateralDisabled { reserve, user }.encode_log_data()),
+            context_log(
+                IsolationModeTotalDebtUpdated {
+                    asset: reserve,
+                    totalDebt: U256::from(12_345),
+                }
+                .encode_log_data(),
+            ),
+            context_log(
+                MintedToTreasury {
+                    reserve,
+                    amountMinted: U256::from(7),
+                }
+                .encode_log_data(),
+            ),
+            context_log(
+                BackUnbacked {
+                    reserve,
+                    backer: user,
+                    amount: U256::from(100),
+                    fee: U256::from(1),
+                }
+                .encode_log_data(),
+            ),
+        ];
+
+        let updates = AaveUserUpdates::extract_all(&logs);
+        assert_eq!(updates.total_count(), 7);
+        assert_eq!(updates.flash_loans[0].premium, U256::from(500));
+        assert_eq!(updates.flash_loans[0].pool, pool);
+        assert_eq!(updates.emode_sets[0].category_id, 1);
+        let toggles: Vec<_> = updates
+            .collateral_toggles
+            .iter()
+            .map(|t| (t.reserve, t.user, t.enabled))
+            .collect();
+        assert_eq!(toggles, vec![(reserve, user, true), (reserve, user, false)]);
+        assert_eq!(
+            updates.isolation_mode_debt_updates[0].total_debt,
+            U256::from(12_345)
+        );
+        assert_eq!(updates.treasury_mints[0].amount_minted, U256::from(7));
+        assert_eq!(updates.back_unbacked[0].fee, U256::from(1));
+    }
 }
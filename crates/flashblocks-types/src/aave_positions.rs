@@ -0,0 +1,817 @@
+//! AAVE position book and health factor estimation.
+//!
+//! The book keeps each user's scaled supply and debt per reserve, seeded from
+//! a snapshot and updated from the pool's user events. Scaled balances grow
+//! into real balances through the reserve indexes from `ReserveDataUpdated`,
+//! and are valued with Chainlink prices and the reserves' configured
+//! liquidation thresholds to estimate health factors like the pool's
+//! `getUserAccountData`. eMode categories and aToken transfers between users
+//! are not tracked, so estimates for those users are approximate.
+
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
+
+use alloy_primitives::{Address, U256};
+use serde::{Deserialize, Serialize};
+
+use crate::aave::{
+    AaveReserveCache, AaveReserveState, AaveUserUpdates, ParsedBorrow, ParsedCollateralToggle,
+    ParsedLiquidation, ParsedRepay, ParsedReserveDataUpdated, ParsedSupply, ParsedWithdraw, RAY,
+    ray_div, ray_mul,
+};
+use crate::chainlink::{FeedRegistry, ParsedAggregatorConfirmed, ParsedAnswerUpdated};
+use crate::flashblocks::EventContext;
+
+/// A reserve's risk parameters and price source
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct AaveReserveConfig {
+    /// Address of the AAVE pool contract
+    pub pool: Address,
+    /// The reserve (token)
+    pub asset: Address,
+    /// Token decimals
+    pub decimals: u8,
+    /// Liquidation threshold in basis points, e.g. 8300 for 83%
+    pub liquidation_threshold: u16,
+    /// Chainlink proxy pricing the asset in the base currency
+    pub price_feed: Address,
+    /// Price to use until the feed updates
+    #[serde(default)]
+    pub price: Option<f64>,
+    /// Supply index at snapshot time, in ray
+    #[serde(default)]
+    pub liquidity_index: Option<U256>,
+    /// Variable borrow index at snapshot time, in ray
+    #[serde(default)]
+    pub variable_borrow_index: Option<U256>,
+}
+
+/// A user's balances in one reserve
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub struct AavePosition {
+    /// aToken balance divided by the supply index (`scaledBalanceOf`)
+    #[serde(default)]
+    pub scaled_supply: U256,
+    /// Variable debt divided by the variable borrow index
+    #[serde(default)]
+    pub scaled_debt: U256,
+    /// Whether the supply counts as collateral
+    #[serde(default)]
+    pub collateral: bool,
+}
+
+/// A position as listed in the snapshot
+#[derive(Debug, Deserialize)]
+struct PositionEntry {
+    pool: Address,
+    user: Address,
+    reserve: Address,
+    #[serde(flatten)]
+    position: AavePosition,
+}
+
+/// On-disk layout of the position snapshot
+#[derive(Debug, Deserialize)]
+struct SnapshotFile {
+    #[serde(default)]
+    reserves: Vec<AaveReserveConfig>,
+    #[serde(default)]
+    positions: Vec<PositionEntry>,
+}
+
+/// A user's account totals in the base currency of the price feeds
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AaveHealth {
+    pub pool: Address,
+    pub user: Address,
+    /// Value of the user's collateral
+    pub total_collateral_base: f64,
+    /// Value of the user's debt
+    pub total_debt_base: f64,
+    /// Threshold-weighted collateral over debt; below 1 can be liquidated
+    pub health_factor: f64,
+}
+
+/// A user whose estimated health factor dropped below 1, streamed as `Aave_liquidatable`
+#[derive(Debug, Clone, Serialize)]
+pub struct AaveLiquidatable {
+    #[serde(flatten)]
+    pub health: AaveHealth,
+    pub block_number: u64,
+    pub flashblock_index: u64,
+    pub payload_id: String,
+}
+
+/// A flashblock event that moves the book's indexes, balances or prices
+#[derive(Debug, Clone, Copy)]
+pub enum AaveBookEvent<'a> {
+    ReserveUpdated(&'a ParsedReserveDataUpdated),
+    Supply(&'a ParsedSupply),
+    Withdraw(&'a ParsedWithdraw),
+    Borrow(&'a ParsedBorrow),
+    Repay(&'a ParsedRepay),
+    Liquidation(&'a ParsedLiquidation),
+    CollateralToggle(&'a ParsedCollateralToggle),
+    Answer(&'a ParsedAnswerUpdated),
+    AggregatorConfirmed(&'a ParsedAggregatorConfirmed),
+}
+
+impl<'a> AaveBookEvent<'a> {
+    /// Where the event was emitted
+    pub fn context(&self) -> &'a EventContext {
+        match *self {
+            Self::ReserveUpdated(e) => &e.context,
+            Self::Supply(e) => &e.context,
+            Self::Withdraw(e) => &e.context,
+            Self::Borrow(e) => &e.context,
+            Self::Repay(e) => &e.context,
+            Self::Liquidation(e) => &e.context,
+            Self::CollateralToggle(e) => &e.context,
+            Self::Answer(e) => &e.context,
+            Self::AggregatorConfirmed(e) => &e.context,
+        }
+    }
+
+    /// Merge a flashblock's book events into execution order
+    pub fn ordered(
+        updates: &'a AaveUserUpdates,
+        answers: &'a [ParsedAnswerUpdated],
+        confirmations: &'a [ParsedAggregatorConfirmed],
+    ) -> Vec<Self> {
+        let mut events: Vec<_> = updates
+            .reserve_data_updates
+            .iter()
+            .map(Self::ReserveUpdated)
+            .chain(updates.supplies.iter().map(Self::Supply))
+            .chain(updates.withdraws.iter().map(Self::Withdraw))
+            .chain(updates.borrows.iter().map(Self::Borrow))
+            .chain(updates.repays.iter().map(Self::Repay))
+            .chain(updates.liquidations.iter().map(Self::Liquidation))
+            .chain(
+                updates
+                    .collateral_toggles
+                    .iter()
+                    .map(Self::CollateralToggle),
+            )
+            .chain(answers.iter().map(Self::Answer))
+            .chain(confirmations.iter().map(Self::AggregatorConfirmed))
+            .collect();
+        events.sort_by_key(|e| e.context().execution_order());
+        events
+    }
+}
+
+/// Scaled AAVE positions keyed by (pool, user, reserve), with the reserve
+/// state and prices needed to value them.
+///
+/// Positions as of the last completed block are kept apart from the changes
+/// of the current payload, which are dropped if the payload is replaced.
+#[derive(Debug, Clone, Default)]
+pub struct AavePositionBook {
+    configs: HashMap<(Address, Address), AaveReserveConfig>,
+    reserves: AaveReserveCache,
+    /// Latest price of each configured feed proxy
+    prices: HashMap<Address, f64>,
+    payload_id: Option<String>,
+    block_number: Option<u64>,
+    /// Positions as of the last completed block
+    positions: HashMap<(Address, Address), HashMap<Address, AavePosition>>,
+    /// Positions of the users the current payload moved
+    pending: HashMap<(Address, Address), HashMap<Address, AavePosition>>,
+    /// Users below a health factor of 1 at the last check
+    liquidatable: HashSet<(Address, Address)>,
+}
+
+impl AavePositionBook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a snapshot from JSON of the form `{"reserves": [...], "positions": [...]}`
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let file: SnapshotFile = serde_json::from_str(json)?;
+        let mut book = Self::new();
+        for config in file.reserves {
+            if let Some(price) = config.price {
+                book.prices.entry(config.price_feed).or_insert(price);
+            }
+            book.configs.insert((config.pool, config.asset), config);
+        }
+        for entry in file.positions {
+            book.positions
+                .entry((entry.pool, entry.user))
+                .or_default()
+                .insert(entry.reserve, entry.position);
+        }
+        Ok(book)
+    }
+
+    /// Load a snapshot from a JSON file
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let json = std::fs::read_to_string(path)?;
+        Ok(Self::from_json(&json)?)
+    }
+
+    /// Number of configured reserves
+    pub fn reserve_count(&self) -> usize {
+        self.configs.len()
+    }
+
+    /// Number of users with a position
+    pub fn user_count(&self) -> usize {
+        self.users().count()
+    }
+
+    /// Latest rates and indexes of every reserve seen
+    pub fn reserves(&self) -> &AaveReserveCache {
+        &self.reserves
+    }
+
+    pub fn position(
+        &self,
+        pool: &Address,
+        user: &Address,
+        reserve: &Address,
+    ) -> Option<&AavePosition> {
+        self.user_positions(pool, user)?.get(reserve)
+    }
+
+    /// Start tracking a flashblock's payload. A new payload for the next block
+    /// commits the previous payload's changes; one replacing the current
+    /// block's payload drops them, as they never landed.
+    pub fn start_payload(&mut self, payload_id: &str, block_number: u64) {
+        if self.payload_id.as_deref() == Some(payload_id) {
+            return;
+        }
+        if self.block_number == Some(block_number) {
+            self.pending.clear();
+        } else {
+            self.positions.extend(self.pending.drain());
+        }
+        self.payload_id = Some(payload_id.to_string());
+        self.block_number = Some(block_number);
+    }
+
+    /// A user's positions, including the current payload's changes
+    fn user_positions(
+        &self,
+        pool: &Address,
+        user: &Address,
+    ) -> Option<&HashMap<Address, AavePosition>> {
+        let key = (*pool, *user);
+        self.pending.get(&key).or_else(|| self.positions.get(&key))
+    }
+
+    /// Every user's positions, including the current payload's changes
+    fn users(
+        &self,
+    ) -> impl Iterator<Item = (&(Address, Address), &HashMap<Address, AavePosition>)> {
+        self.pending.iter().chain(
+            self.positions
+                .iter()
+                .filter(|(key, _)| !self.pending.contains_key(*key)),
+        )
+    }
+
+    /// Current supply and variable borrow indexes of a reserve: from its last
+    /// update, else the snapshot, else 1 ray
+    fn indexes(&self, pool: &Address, reserve: &Address) -> (U256, U256) {
+        if let Some(state) = self.reserves.get(pool, reserve) {
+            return (state.liquidity_index, state.variable_borrow_index);
+        }
+        let config = self.configs.get(&(*pool, *reserve));
+        (
+            config.and_then(|c| c.liquidity_index).unwrap_or(RAY),
+            config.and_then(|c| c.variable_borrow_index).unwrap_or(RAY),
+        )
+    }
+
+    /// A user's position in the current payload, copied from the last
+    /// completed block on the payload's first change
+    fn position_mut(
+        &mut self,
+        pool: Address,
+        user: Address,
+        reserve: Address,
+    ) -> &mut AavePosition {
+        let committed = &self.positions;
+        self.pending
+            .entry((pool, user))
+            .or_insert_with(|| committed.get(&(pool, user)).cloned().unwrap_or_default())
+            .entry(reserve)
+            .or_default()
+    }
+
+    /// Record a reserve update, returning the reserve's previous state
+    pub fn apply_reserve_update(
+        &mut self,
+        update: &ParsedReserveDataUpdated,
+    ) -> Option<AaveReserveState> {
+        self.reserves.apply(update)
+    }
+
+    /// Apply a flashblock's reserve updates, user events, Chainlink answers
+    /// and aggregator rotations in execution order, re-estimating the health
+    /// of the users each transaction moved once it completes. A user that
+    /// dips below 1 and recovers within the flashblock is still reported.
+    /// Returns the users that dropped below 1, in the order they did.
+    ///
+    /// `feeds` is left untouched: rotations only apply to a copy used to
+    /// resolve the flashblock's later answers, as the Chainlink handler owns
+    /// the shared registry.
+    pub fn apply_flashblock(
+        &mut self,
+        feeds: &FeedRegistry,
+        updates: &AaveUserUpdates,
+        answers: &[ParsedAnswerUpdated],
+        confirmations: &[ParsedAggregatorConfirmed],
+    ) -> Vec<AaveHealth> {
+        let events = AaveBookEvent::ordered(updates, answers, confirmations);
+        let mut feeds = Cow::Borrowed(feeds);
+        let mut dropped = Vec::new();
+        let mut touched = BTreeSet::new();
+        for (i, event) in events.iter().enumerate() {
+            self.apply_event(&mut feeds, *event, &mut touched);
+
+            // Users are only checked between transactions, as a transaction's
+            // intermediate steps can't be liquidated
+            let tx_done = events
+                .get(i + 1)
+                .is_none_or(|next| next.context().tx_hash != event.context().tx_hash);
+            if tx_done {
+                dropped.extend(
+                    std::mem::take(&mut touched)
+                        .into_iter()
+                        .filter_map(|(pool, user)| self.check_user(pool, user)),
+                );
+            }
+        }
+        dropped
+    }
+
+    /// Apply one event, collecting the users whose health it can move.
+    /// Amounts are scaled by the reserves' current indexes.
+    fn apply_event(
+        &mut self,
+        feeds: &mut Cow<'_, FeedRegistry>,
+        event: AaveBookEvent<'_>,
+        touched: &mut BTreeSet<(Address, Address)>,
+    ) {
+        match event {
+            AaveBookEvent::ReserveUpdated(update) => {
+                self.apply_reserve_update(update);
+                touched.extend(
+                    self.holders(|pool, reserve| {
+                        *pool == update.pool && *reserve == update.reserve
+                    }),
+                );
+            }
+            AaveBookEvent::Supply(supply) => {
+                let (supply_index, _) = self.indexes(&supply.pool, &supply.reserve);
+                let position = self.position_mut(supply.pool, supply.on_behalf_of, supply.reserve);
+                position.scaled_supply += ray_div(supply.amount, supply_index);
+                touched.insert((supply.pool, supply.on_behalf_of));
+            }
+            AaveBookEvent::Withdraw(withdraw) => {
+                let (supply_index, _) = self.indexes(&withdraw.pool, &withdraw.reserve);
+                let position = self.position_mut(withdraw.pool, withdraw.user, withdraw.reserve);
+                position.scaled_supply = position
+                    .scaled_supply
+                    .saturating_sub(ray_div(withdraw.amount, supply_index));
+                touched.insert((withdraw.pool, withdraw.user));
+            }
+            AaveBookEvent::Borrow(borrow) => {
+                let (_, borrow_index) = self.indexes(&borrow.pool, &borrow.reserve);
+                let position = self.position_mut(borrow.pool, borrow.on_behalf_of, borrow.reserve);
+                position.scaled_debt += ray_div(borrow.amount, borrow_index);
+                touched.insert((borrow.pool, borrow.on_behalf_of));
+            }
+            AaveBookEvent::Repay(repay) => {
+                let (supply_index, borrow_index) = self.indexes(&repay.pool, &repay.reserve);
+                let position = self.position_mut(repay.pool, repay.user, repay.reserve);
+                position.scaled_debt = position
+                    .scaled_debt
+                    .saturating_sub(ray_div(repay.amount, borrow_index));
+                if repay.use_a_tokens {
+                    position.scaled_supply = position
+                        .scaled_supply
+                        .saturating_sub(ray_div(repay.amount, supply_index));
+                }
+                touched.insert((repay.pool, repay.user));
+            }
+            AaveBookEvent::Liquidation(liquidation) => {
+                let (_, borrow_index) = self.indexes(&liquidation.pool, &liquidation.debt_asset);
+                let debt =
+                    self.position_mut(liquidation.pool, liquidation.user, liquidation.debt_asset);
+                debt.scaled_debt = debt
+                    .scaled_debt
+                    .saturating_sub(ray_div(liquidation.debt_to_cover, borrow_index));
+
+                let (supply_index, _) =
+                    self.indexes(&liquidation.pool, &liquidation.collateral_asset);
+                let collateral = self.position_mut(
+                    liquidation.pool,
+                    liquidation.user,
+                    liquidation.collateral_asset,
+                );
+                collateral.scaled_supply = collateral.scaled_supply.saturating_sub(ray_div(
+                    liquidation.liquidated_collateral_amount,
+                    supply_index,
+                ));
+                touched.insert((liquidation.pool, liquidation.user));
+            }
+            AaveBookEvent::CollateralToggle(toggle) => {
+                self.position_mut(toggle.pool, toggle.user, toggle.reserve)
+                    .collateral = toggle.enabled;
+                touched.insert((toggle.pool, toggle.user));
+            }
+            AaveBookEvent::Answer(answer) => {
+                if self.apply_answer(feeds, answer)
+                    && let Some(feed) = feeds.feed_for_aggregator(&answer.feed)
+                {
+                    let proxy = feed.proxy;
+                    touched.extend(self.holders(|pool, reserve| {
+                        self.configs
+                            .get(&(*pool, *reserve))
+                            .is_some_and(|c| c.price_feed == proxy)
+                    }));
+                }
+            }
+            AaveBookEvent::AggregatorConfirmed(confirmed) => {
+                feeds.to_mut().rotate(&confirmed.proxy, confirmed.latest);
+            }
+        }
+    }
+
+    /// Users holding a position in any (pool, reserve) matching `filter`
+    fn holders(&self, filter: impl Fn(&Address, &Address) -> bool) -> Vec<(Address, Address)> {
+        self.users()
+            .filter(|((pool, _), reserves)| reserves.keys().any(|reserve| filter(pool, reserve)))
+            .map(|(key, _)| *key)
+            .collect()
+    }
+
+    /// Record a Chainlink answer for any configured feed it prices.
+    /// Returns false if the aggregator is unknown or prices no reserve.
+    pub fn apply_answer(&mut self, feeds: &FeedRegistry, answer: &ParsedAnswerUpdated) -> bool {
+        let Some(feed) = feeds.feed_for_aggregator(&answer.feed) else {
+            return false;
+        };
+        if !self.configs.values().any(|c| c.price_feed == feed.proxy) {
+            return false;
+        }
+        let Ok(raw) = i128::try_from(answer.answer) else {
+            return false;
+        };
+        if raw <= 0 {
+            return false;
+        }
+        let price = raw as f64 / 10f64.powi(feed.decimals.into());
+        self.prices.insert(feed.proxy, price);
+        true
+    }
+
+    /// Estimate a user's health, if they have debt and every reserve they hold
+    /// is configured and priced
+    pub fn health(&self, pool: &Address, user: &Address) -> Option<AaveHealth> {
+        let positions = self.user_positions(pool, user)?;
+
+        let mut total_collateral_base = 0.0;
+        let mut weighted_collateral = 0.0;
+        let mut total_debt_base = 0.0;
+        for (reserve, position) in positions {
+            let counts_as_collateral = position.collateral && !position.scaled_supply.is_zero();
+            if !counts_as_collateral && position.scaled_debt.is_zero() {
+                continue;
+            }
+
+            let config = self.configs.get(&(*pool, *reserve))?;
+            let price = *self.prices.get(&config.price_feed)?;
+            let (supply_index, borrow_index) = self.indexes(pool, reserve);
+            let unit = 10f64.powi(config.decimals.into());
+            let value = |amount: U256| amount.saturating_to::<u128>() as f64 / unit * price;
+
+            if counts_as_collateral {
+                let collateral = value(ray_mul(position.scaled_supply, supply_index));
+                total_collateral_base += collateral;
+                weighted_collateral +=
+                    collateral * f64::from(config.liquidation_threshold) / 10_000.0;
+            }
+            total_debt_base += value(ray_mul(position.scaled_debt, borrow_index));
+        }
+
+        if total_debt_base <= 0.0 {
+            return None;
+        }
+        Some(AaveHealth {
+            pool: *pool,
+            user: *user,
+            total_collateral_base,
+            total_debt_base,
+            health_factor: weighted_collateral / total_debt_base,
+        })
+    }
+
+    /// Re-estimate every user's health and return those that dropped below 1
+    /// since the last check, sorted by (pool, user). Users are reported again
+    /// only after recovering to 1 or above.
+    pub fn check_health(&mut self) -> Vec<AaveHealth> {
+        let mut users: Vec<_> = self.users().map(|(key, _)| *key).collect();
+        users.sort();
+        users
+            .into_iter()
+            .filter_map(|(pool, user)| self.check_user(pool, user))
+            .collect()
+    }
+
+    /// Re-estimate one user's health, returning it if they just dropped below 1
+    fn check_user(&mut self, pool: Address, user: Address) -> Option<AaveHealth> {
+        match self
+            .health(&pool, &user)
+            .filter(|health| health.health_factor < 1.0)
+        {
+            Some(health) => self.liquidatable.insert((pool, user)).then_some(health),
+            None => {
+                self.liquidatable.remove(&(pool, user));
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::aave::{ParsedBorrow, ParsedCollateralToggle, ParsedSupply};
+    use crate::chainlink::FeedConfig;
+    use alloy_primitives::{B256, I256};
+
+    const POOL: Address = Address::repeat_byte(0xaa);
+    const WETH: Address = Address::repeat_byte(0xe7);
+    const USDC: Address = Address::repeat_byte(0x5d);
+    const ETH_USD: Address = Address::repeat_byte(0xf1);
+    const ETH_USD_AGGREGATOR: Address = Address::repeat_byte(0xa1);
+    const USDC_USD: Address = Address::repeat_byte(0xf2);
+
+    fn snapshot() -> AavePositionBook {
+        AavePositionBook::from_json(&format!(
+            r#"{{
+                "reserves": [
+                    {{"pool": "{POOL}", "asset": "{WETH}", "decimals": 18,
+                      "liquidation_threshold": 8000, "price_feed": "{ETH_USD}", "price": 2000.0,
+                      "liquidity_index": "1000000000000000000000000000"}},
+                    {{"pool": "{POOL}", "asset": "{USDC}", "decimals": 6,
+                      "liquidation_threshold": 7800, "price_feed": "{USDC_USD}", "price": 1.0}}
+                ],
+                "positions": [
+                    {{"pool": "{POOL}", "user": "{alice}", "reserve": "{WETH}",
+                      "scaled_supply": "1000000000000000000", "collateral": true}},
+                    {{"pool": "{POOL}", "user": "{alice}", "reserve": "{USDC}",
+                      "scaled_debt": "1500000000"}}
+                ]
+            }}"#,
+            alice = Address::repeat_byte(0x01),
+        ))
+        .unwrap()
+    }
+
+    fn feeds() -> FeedRegistry {
+        FeedRegistry::new(vec![FeedConfig {
+            proxy: ETH_USD,
+            description: "ETH / USD".to_string(),
+            base: "ETH".to_string(),
+            quote: "USD".to_string(),
+            decimals: 8,
+            aggregators: vec![ETH_USD_AGGREGATOR],
+        }])
+    }
+
+    /// Context of the `log`th log of the flashblock's `tx`th transaction
+    fn at(tx: u64, log: u64) -> EventContext {
+        EventContext {
+            tx_hash: B256::with_last_byte(tx as u8),
+            tx_index: Some(tx),
+            receipt_log_index: log,
+            ..Default::default()
+        }
+    }
+
+    fn eth_price(usd: i64) -> ParsedAnswerUpdated {
+        ParsedAnswerUpdated {
+            context: EventContext::default(),
+            feed: ETH_USD_AGGREGATOR,
+            answer: I256::try_from(usd * 100_000_000).unwrap(),
+            round_id: U256::from(1),
+            updated_at: U256::ZERO,
+            feed_info: None,
+            price: None,
+        }
+    }
+
+    #[test]
+    fn test_price_drop_reports_once() {
+        let alice = Address::repeat_byte(0x01);
+        let mut book = snapshot();
+        assert_eq!((book.reserve_count(), book.user_count()), (2, 1));
+
+        // 1 WETH at $2000 with an 80% threshold against 1500 USDC
+        let health = book.health(&POOL, &alice).unwrap();
+        assert_eq!(health.total_collateral_base, 2000.0);
+        assert_eq!(health.total_debt_base, 1500.0);
+        assert!((health.health_factor - 1600.0 / 1500.0).abs() < 1e-12);
+        assert!(book.check_health().is_empty());
+
+        assert!(book.apply_answer(&feeds(), &eth_price(1800)));
+        let dropped = book.check_health();
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].user, alice);
+        assert!((dropped[0].health_factor - 0.96).abs() < 1e-12);
+
+        // Still underwater: not reported again until it recovers
+        assert!(book.apply_answer(&feeds(), &eth_price(1700)));
+        assert!(book.check_health().is_empty());
+        assert!(book.apply_answer(&feeds(), &eth_price(2000)));
+        assert!(book.check_health().is_empty());
+        assert!(book.apply_answer(&feeds(), &eth_price(1800)));
+        assert_eq!(book.check_health().len(), 1);
+    }
+
+    #[test]
+    fn test_user_events_scale_by_index() {
+        let bob = Address::repeat_byte(0x02);
+        let mut book = snapshot();
+        let ray_percent = |pct: u64| RAY * U256::from(pct) / U256::from(100);
+
+        // Indexes of 1.25 for WETH supply and 1.2 for USDC debt, updated by
+        // each reserve's first event
+        let updates = AaveUserUpdates {
+            reserve_data_updates: vec![
+                ParsedReserveDataUpdated {
+                    context: at(0, 0),
+                    pool: POOL,
+                    reserve: WETH,
+                    liquidity_rate: U256::ZERO,
+                    stable_borrow_rate: U256::ZERO,
+                    variable_borrow_rate: U256::ZERO,
+                    liquidity_index: ray_percent(125),
+                    variable_borrow_index: RAY,
+                },
+                ParsedReserveDataUpdated {
+                    context: at(1, 0),
+                    pool: POOL,
+                    reserve: USDC,
+                    liquidity_rate: U256::ZERO,
+                    stable_borrow_rate: U256::ZERO,
+                    variable_borrow_rate: U256::ZERO,
+                    liquidity_index: RAY,
+                    variable_borrow_index: ray_percent(120),
+                },
+            ],
+            supplies: vec![ParsedSupply {
+                context: at(0, 2),
+                pool: POOL,
+                reserve: WETH,
+                user: bob,
+                on_behalf_of: bob,
+                amount: U256::from(10u64.pow(18)),
+                referral_code: 0,
+            }],
+            borrows: vec![ParsedBorrow {
+                context: at(1, 1),
+                pool: POOL,
+                reserve: USDC,
+                user: bob,
+                on_behalf_of: bob,
+                amount: U256::from(1_200_000_000),
+                interest_rate_mode: 2,
+                borrow_rate: U256::ZERO,
+                referral_code: 0,
+            }],
+            collateral_toggles: vec![ParsedCollateralToggle {
+                context: at(0, 1),
+                pool: POOL,
+                reserve: WETH,
+                user: bob,
+                enabled: true,
+            }],
+            ..Default::default()
+        };
+        assert!(
+            book.apply_flashblock(&feeds(), &updates, &[], &[])
+                .is_empty()
+        );
+
+        let weth = book.position(&POOL, &bob, &WETH).unwrap();
+        assert_eq!(weth.scaled_supply, U256::from(8 * 10u64.pow(17)));
+        assert!(weth.collateral);
+        let usdc = book.position(&POOL, &bob, &USDC).unwrap();
+        assert_eq!(usdc.scaled_debt, U256::from(1_000_000_000));
+
+        let health = book.health(&POOL, &bob).unwrap();
+        assert!((health.total_collateral_base - 2000.0).abs() < 1e-9);
+        assert!((health.total_debt_base - 1200.0).abs() < 1e-9);
+
+        // Alice's snapshot balances accrue too: 1.25 WETH against 1800 USDC
+        let alice = book.health(&POOL, &Address::repeat_byte(0x01)).unwrap();
+        assert!((alice.total_collateral_base - 2500.0).abs() < 1e-9);
+        assert!((alice.total_debt_base - 1800.0).abs() < 1e-9);
+        assert!(book.check_health().is_empty());
+    }
+
+    #[test]
+    fn test_replaced_payload_is_dropped() {
+        let bob = Address::repeat_byte(0x02);
+        let mut book = snapshot();
+        let supply = AaveUserUpdates {
+            supplies: vec![ParsedSupply {
+                context: at(0, 0),
+                pool: POOL,
+                reserve: WETH,
+                user: bob,
+                on_behalf_of: bob,
+                amount: U256::from(10u64.pow(18)),
+                referral_code: 0,
+            }],
+            ..Default::default()
+        };
+
+        book.start_payload("0x01", 100);
+        book.apply_flashblock(&feeds(), &supply, &[], &[]);
+        assert_eq!(book.user_count(), 2);
+
+        // The block is rebuilt under a new payload: the supply is undone
+        // until the new payload includes it again
+        book.start_payload("0x02", 100);
+        assert_eq!(book.position(&POOL, &bob, &WETH), None);
+        assert_eq!(book.user_count(), 1);
+        book.apply_flashblock(&feeds(), &supply, &[], &[]);
+
+        // The next block keeps it, counted once
+        book.start_payload("0x03", 101);
+        let weth = book.position(&POOL, &bob, &WETH).unwrap();
+        assert_eq!(weth.scaled_supply, U256::from(10u64.pow(18)));
+        book.start_payload("0x04", 101);
+        assert_eq!(
+            book.position(&POOL, &bob, &WETH).unwrap().scaled_supply,
+            U256::from(10u64.pow(18))
+        );
+    }
+
+    #[test]
+    fn test_dip_within_flashblock_is_reported() {
+        let alice = Address::repeat_byte(0x01);
+        let mut book = snapshot();
+        let feeds = feeds();
+
+        // ETH drops to $1800 in one transaction and recovers in the next
+        let answers = [
+            ParsedAnswerUpdated {
+                context: at(3, 0),
+                ..eth_price(2000)
+            },
+            ParsedAnswerUpdated {
+                context: at(1, 0),
+                ..eth_price(1800)
+            },
+        ];
+        let dropped = book.apply_flashblock(&feeds, &AaveUserUpdates::default(), &answers, &[]);
+        assert_eq!(dropped.len(), 1);
+        assert_eq!(dropped[0].user, alice);
+        assert!((dropped[0].health_factor - 0.96).abs() < 1e-12);
+
+        // Recovered by the end of the flashblock, so the next dip reports again
+        assert!(
+            (book.health(&POOL, &alice).unwrap().health_factor - 1600.0 / 1500.0).abs() < 1e-12
+        );
+        assert!(book.apply_answer(&feeds, &eth_price(1800)));
+        assert_eq!(book.check_health().len(), 1);
+    }
+
+    #[test]
+    fn test_rotation_prices_answers_from_the_new_aggregator() {
+        let mut book = snapshot();
+        let feeds = feeds();
+        let new_aggregator = Address::repeat_byte(0xa2);
+
+        // The new aggregator's answer only resolves after the rotation
+        let confirmed = ParsedAggregatorConfirmed {
+            context: at(0, 0),
+            proxy: ETH_USD,
+            previous: ETH_USD_AGGREGATOR,
+            latest: new_aggregator,
+            feed_info: None,
+        };
+        let answer = ParsedAnswerUpdated {
+            context: at(1, 0),
+            feed: new_aggregator,
+            ..eth_price(1800)
+        };
+        let dropped =
+            book.apply_flashblock(&feeds, &AaveUserUpdates::default(), &[answer], &[confirmed]);
+        assert_eq!(dropped.len(), 1);
+
+        // The shared registry is only rotated by the Chainlink handler
+        assert!(feeds.feed_for_aggregator(&new_aggregator).is_none());
+    }
+}
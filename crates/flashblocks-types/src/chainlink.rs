@@ -1,8 +1,13 @@
-use alloy_primitives::{Address, Bloom, BloomInput, I256, U256};
+use std::collections::HashMap;
+use std::path::Path;
+
+use alloy_primitives::aliases::I192;
+use alloy_primitives::utils::format_units;
+use alloy_primitives::{Address, B256, Bloom, BloomInput, I256, U256};
 use alloy_sol_types::{SolEvent, sol};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use crate::flashblocks::ReceiptLog;
+use crate::flashblocks::{ContextLog, EventContext, ReceiptLog};
 
 // Chainlink Aggregator events
 sol! {
@@ -19,6 +24,25 @@ sol! {
         address indexed startedBy,
         uint256 startedAt
     );
+
+    /// Emitted by OCR2 aggregators for every report transmitted on-chain
+    event NewTransmission(
+        uint32 indexed aggregatorRoundId,
+        int192 answer,
+        address transmitter,
+        uint32 observationsTimestamp,
+        int192[] observations,
+        bytes observers,
+        int192 juelsPerFeeCoin,
+        bytes32 configDigest,
+        uint40 epochAndRound
+    );
+
+    /// Emitted by a feed proxy when it switches to a new aggregator
+    event AggregatorConfirmed(
+        address indexed previous,
+        address indexed latest
+    );
 }
 
 /// Detected Chainlink oracle events based on bloom filter
@@ -26,6 +50,8 @@ sol! {
 pub struct ChainlinkEvents {
     pub may_have_answer_updated: bool,
     pub may_have_new_round: bool,
+    pub may_have_new_transmission: bool,
+    pub may_have_aggregator_confirmed: bool,
 }
 
 impl ChainlinkEvents {
@@ -34,20 +60,31 @@ impl ChainlinkEvents {
     pub fn from_bloom(bloom: &Bloom) -> Self {
         Self {
             may_have_answer_updated: bloom
-                .contains_input(BloomInput::Hash(AnswerUpdated::SIGNATURE_HASH)),
-            may_have_new_round: bloom.contains_input(BloomInput::Hash(NewRound::SIGNATURE_HASH)),
+                .contains_input(BloomInput::Raw(AnswerUpdated::SIGNATURE_HASH.as_slice())),
+            may_have_new_round: bloom
+                .contains_input(BloomInput::Raw(NewRound::SIGNATURE_HASH.as_slice())),
+            may_have_new_transmission: bloom
+                .contains_input(BloomInput::Raw(NewTransmission::SIGNATURE_HASH.as_slice())),
+            may_have_aggregator_confirmed: bloom.contains_input(BloomInput::Raw(
+                AggregatorConfirmed::SIGNATURE_HASH.as_slice(),
+            )),
         }
     }
 
     /// Returns true if any Chainlink event might be present
     pub fn any(&self) -> bool {
-        self.may_have_answer_updated || self.may_have_new_round
+        self.may_have_answer_updated
+            || self.may_have_new_round
+            || self.may_have_new_transmission
+            || self.may_have_aggregator_confirmed
     }
 }
 
 /// Parsed Chainlink AnswerUpdated event with pool address
 #[derive(Debug, Clone, Serialize)]
 pub struct ParsedAnswerUpdated {
+    /// Where the event was emitted
+    pub context: EventContext,
     /// Address of the Chainlink aggregator/feed
     pub feed: Address,
     /// The new answer value (price with decimals)
@@ -56,11 +93,17 @@ pub struct ParsedAnswerUpdated {
     pub round_id: U256,
     /// Timestamp when the answer was updated
     pub updated_at: U256,
+    /// Registry details of the feed, if the aggregator is known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feed_info: Option<FeedInfo>,
+    /// `answer` scaled by the feed's decimals, if the aggregator is known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<String>,
 }
 
 impl ParsedAnswerUpdated {
     /// Try to parse an AnswerUpdated event from a log entry
-    pub fn try_from_log(log: &ReceiptLog) -> Option<Self> {
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
         // AnswerUpdated has 2 indexed parameters (current, roundId) + 1 non-indexed (updatedAt)
         if log.topics.len() != 3 {
             return None;
@@ -76,22 +119,29 @@ impl ParsedAnswerUpdated {
             AnswerUpdated::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
 
         Some(Self {
+            context: context.clone(),
             feed: log.address,
             answer: decoded.current,
             round_id: decoded.roundId,
             updated_at: decoded.updatedAt,
+            feed_info: None,
+            price: None,
         })
     }
 
     /// Extract all AnswerUpdated events from a slice of logs
-    pub fn extract_all(logs: &[ReceiptLog]) -> Vec<Self> {
-        logs.iter().filter_map(Self::try_from_log).collect()
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
     }
 }
 
 /// Parsed Chainlink NewRound event
 #[derive(Debug, Clone, Serialize)]
 pub struct ParsedNewRound {
+    /// Where the event was emitted
+    pub context: EventContext,
     /// Address of the Chainlink aggregator/feed
     pub feed: Address,
     /// The round ID that was started
@@ -100,11 +150,14 @@ pub struct ParsedNewRound {
     pub started_by: Address,
     /// Timestamp when the round was started
     pub started_at: U256,
+    /// Registry details of the feed, if the aggregator is known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feed_info: Option<FeedInfo>,
 }
 
 impl ParsedNewRound {
     /// Try to parse a NewRound event from a log entry
-    pub fn try_from_log(log: &ReceiptLog) -> Option<Self> {
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
         // NewRound has 2 indexed parameters (roundId, startedBy) + 1 non-indexed (startedAt)
         if log.topics.len() != 3 {
             return None;
@@ -119,22 +172,377 @@ impl ParsedNewRound {
         let decoded = NewRound::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
 
         Some(Self {
+            context: context.clone(),
             feed: log.address,
             round_id: decoded.roundId,
             started_by: decoded.startedBy,
             started_at: decoded.startedAt,
+            feed_info: None,
         })
     }
 
     /// Extract all NewRound events from a slice of logs
-    pub fn extract_all(logs: &[ReceiptLog]) -> Vec<Self> {
-        logs.iter().filter_map(Self::try_from_log).collect()
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// Parsed Chainlink proxy AggregatorConfirmed event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedAggregatorConfirmed {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// Address of the feed proxy
+    pub proxy: Address,
+    /// The aggregator the proxy pointed at before
+    pub previous: Address,
+    /// The aggregator the proxy points at now
+    pub latest: Address,
+    /// Registry details of the feed, if the proxy is known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feed_info: Option<FeedInfo>,
+}
+
+impl ParsedAggregatorConfirmed {
+    /// Try to parse an AggregatorConfirmed event from a log entry
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        // AggregatorConfirmed has 2 indexed parameters (previous, latest) and no data
+        if log.topics.len() != 3 {
+            return None;
+        }
+
+        if log.topics[0] != AggregatorConfirmed::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded =
+            AggregatorConfirmed::decode_raw_log(log.topics.iter().copied(), &log.data, true)
+                .ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            proxy: log.address,
+            previous: decoded.previous,
+            latest: decoded.latest,
+            feed_info: None,
+        })
+    }
+
+    /// Extract all AggregatorConfirmed events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// Sign-extend an OCR2 `int192` to `I256`
+fn i192_to_i256(value: I192) -> I256 {
+    let raw = U256::from(value.into_raw());
+    if value.is_negative() {
+        I256::from_raw(raw | (U256::MAX << 192))
+    } else {
+        I256::from_raw(raw)
+    }
+}
+
+/// Parsed Chainlink OCR2 NewTransmission event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedNewTransmission {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// Address of the OCR2 aggregator
+    pub feed: Address,
+    /// The aggregator's round ID for this report
+    pub aggregator_round_id: u32,
+    /// The median answer (price with decimals)
+    pub answer: I256,
+    /// The node that transmitted the report
+    pub transmitter: Address,
+    /// Timestamp of the observations in the report
+    pub observations_timestamp: u32,
+    /// Sorted observations the answer was taken from
+    pub observations: Vec<I256>,
+    /// Oracle index of each observation's observer, in observation order
+    pub observers: Vec<u8>,
+    /// LINK (in juels) per unit of the chain's fee coin, used for reimbursement
+    pub juels_per_fee_coin: I256,
+    /// Digest of the OCR config the report was produced under
+    pub config_digest: B256,
+    /// OCR epoch (upper 32 bits) and round (lower 8 bits)
+    pub epoch_and_round: u64,
+    /// Registry details of the feed, if the aggregator is known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feed_info: Option<FeedInfo>,
+    /// `answer` scaled by the feed's decimals, if the aggregator is known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<String>,
+}
+
+impl ParsedNewTransmission {
+    /// Try to parse a NewTransmission event from a log entry
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        // NewTransmission has 1 indexed parameter (aggregatorRoundId)
+        if log.topics.len() != 2 {
+            return None;
+        }
+
+        // Check event signature
+        if log.topics[0] != NewTransmission::SIGNATURE_HASH {
+            return None;
+        }
+
+        // Decode the event
+        let decoded =
+            NewTransmission::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            feed: log.address,
+            aggregator_round_id: decoded.aggregatorRoundId,
+            answer: i192_to_i256(decoded.answer),
+            transmitter: decoded.transmitter,
+            observations_timestamp: decoded.observationsTimestamp,
+            observations: decoded.observations.into_iter().map(i192_to_i256).collect(),
+            observers: decoded.observers.to_vec(),
+            juels_per_fee_coin: i192_to_i256(decoded.juelsPerFeeCoin),
+            config_digest: decoded.configDigest,
+            epoch_and_round: decoded.epochAndRound.to(),
+            feed_info: None,
+            price: None,
+        })
+    }
+
+    /// Extract all NewTransmission events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// A Chainlink price feed as declared in the feed registry config
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct FeedConfig {
+    /// The proxy consumers read from; stable across aggregator rotations
+    pub proxy: Address,
+    /// Feed description as reported by the proxy (e.g. "ETH / USD")
+    pub description: String,
+    /// Base asset symbol
+    pub base: String,
+    /// Quote asset symbol
+    pub quote: String,
+    /// Decimals of the answer
+    pub decimals: u8,
+    /// Aggregators the proxy has pointed to, oldest first. The last one is current.
+    pub aggregators: Vec<Address>,
+}
+
+impl FeedConfig {
+    /// Pair name, e.g. "ETH/USD"
+    pub fn pair(&self) -> String {
+        format!("{}/{}", self.base, self.quote)
+    }
+
+    /// Scale a raw answer by the feed's decimals
+    pub fn normalize(&self, answer: I256) -> Option<String> {
+        format_units(answer, self.decimals).ok()
+    }
+
+    /// Registry details for an event emitted by `aggregator`
+    pub fn info(&self, aggregator: &Address) -> FeedInfo {
+        FeedInfo {
+            proxy: self.proxy,
+            pair: self.pair(),
+            description: self.description.clone(),
+            decimals: self.decimals,
+            current_aggregator: self.aggregators.last() == Some(aggregator),
+        }
+    }
+}
+
+/// Registry details attached to Chainlink stream events
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FeedInfo {
+    /// The feed's proxy address
+    pub proxy: Address,
+    /// Pair name, e.g. "ETH/USD"
+    pub pair: String,
+    /// Feed description
+    pub description: String,
+    /// Decimals of the answer
+    pub decimals: u8,
+    /// False if the event came from an aggregator the proxy has since rotated away from
+    pub current_aggregator: bool,
+}
+
+/// On-disk layout of the feed registry config
+#[derive(Debug, Deserialize)]
+struct FeedRegistryFile {
+    feeds: Vec<FeedConfig>,
+}
+
+/// Maps aggregator addresses to the Chainlink feeds they serve
+#[derive(Debug, Clone, Default)]
+pub struct FeedRegistry {
+    feeds: Vec<FeedConfig>,
+    /// Index into `feeds` for every known aggregator, current or retired
+    by_aggregator: HashMap<Address, usize>,
+}
+
+impl FeedRegistry {
+    pub fn new(feeds: Vec<FeedConfig>) -> Self {
+        let by_aggregator = feeds
+            .iter()
+            .enumerate()
+            .flat_map(|(i, feed)| feed.aggregators.iter().map(move |a| (*a, i)))
+            .collect();
+        Self {
+            feeds,
+            by_aggregator,
+        }
+    }
+
+    /// Parse a registry from JSON of the form `{"feeds": [FeedConfig, ...]}`
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let file: FeedRegistryFile = serde_json::from_str(json)?;
+        Ok(Self::new(file.feeds))
     }
+
+    /// Load a registry from a JSON file
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let json = std::fs::read_to_string(path)?;
+        Ok(Self::from_json(&json)?)
+    }
+
+    /// Number of feeds in the registry
+    pub fn len(&self) -> usize {
+        self.feeds.len()
+    }
+
+    /// Returns true if the registry has no feeds
+    pub fn is_empty(&self) -> bool {
+        self.feeds.is_empty()
+    }
+
+    /// Look up the feed an aggregator serves or used to serve
+    pub fn feed_for_aggregator(&self, aggregator: &Address) -> Option<&FeedConfig> {
+        self.by_aggregator.get(aggregator).map(|&i| &self.feeds[i])
+    }
+
+    /// Record that `proxy` now points at `aggregator`. Rotating to the current
+    /// aggregator again is a no-op. Returns false if the proxy is not in the
+    /// registry.
+    pub fn rotate(&mut self, proxy: &Address, aggregator: Address) -> bool {
+        let Some(i) = self.feeds.iter().position(|f| f.proxy == *proxy) else {
+            return false;
+        };
+        let feed = &mut self.feeds[i];
+        feed.aggregators.retain(|a| *a != aggregator);
+        feed.aggregators.push(aggregator);
+        self.by_aggregator.insert(aggregator, i);
+        true
+    }
+
+    /// Rotate the proxy of an AggregatorConfirmed event to its new aggregator
+    /// and attach the feed details. Returns false if the proxy is unknown.
+    pub fn apply_confirmed(&mut self, confirmed: &mut ParsedAggregatorConfirmed) -> bool {
+        if !self.rotate(&confirmed.proxy, confirmed.latest) {
+            return false;
+        }
+        confirmed.feed_info = self
+            .feed_for_aggregator(&confirmed.latest)
+            .map(|feed| feed.info(&confirmed.latest));
+        true
+    }
+
+    /// Apply a flashblock's rotations and annotate its events in execution
+    /// order, so each event is flagged against the aggregator its proxy
+    /// pointed at when it was emitted. Confirmations that rotated a known
+    /// proxy come back with their `feed_info` set.
+    pub fn apply(
+        &mut self,
+        confirmations: &mut [ParsedAggregatorConfirmed],
+        answers: &mut [ParsedAnswerUpdated],
+        rounds: &mut [ParsedNewRound],
+        transmissions: &mut [ParsedNewTransmission],
+    ) {
+        let mut events: Vec<_> = confirmations
+            .iter()
+            .enumerate()
+            .map(|(i, e)| (e.context.execution_order(), RegistryEvent::Confirmed(i)))
+            .chain(
+                answers
+                    .iter()
+                    .enumerate()
+                    .map(|(i, e)| (e.context.execution_order(), RegistryEvent::Answer(i))),
+            )
+            .chain(
+                rounds
+                    .iter()
+                    .enumerate()
+                    .map(|(i, e)| (e.context.execution_order(), RegistryEvent::Round(i))),
+            )
+            .chain(
+                transmissions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, e)| (e.context.execution_order(), RegistryEvent::Transmission(i))),
+            )
+            .collect();
+        events.sort_by_key(|(order, _)| *order);
+
+        for (_, event) in events {
+            match event {
+                RegistryEvent::Confirmed(i) => {
+                    self.apply_confirmed(&mut confirmations[i]);
+                }
+                RegistryEvent::Answer(i) => self.annotate_answer(&mut answers[i]),
+                RegistryEvent::Round(i) => self.annotate_round(&mut rounds[i]),
+                RegistryEvent::Transmission(i) => self.annotate_transmission(&mut transmissions[i]),
+            }
+        }
+    }
+
+    /// Attach feed details and the normalized price to an AnswerUpdated event
+    pub fn annotate_answer(&self, update: &mut ParsedAnswerUpdated) {
+        if let Some(feed) = self.feed_for_aggregator(&update.feed) {
+            update.feed_info = Some(feed.info(&update.feed));
+            update.price = feed.normalize(update.answer);
+        }
+    }
+
+    /// Attach feed details to a NewRound event
+    pub fn annotate_round(&self, round: &mut ParsedNewRound) {
+        if let Some(feed) = self.feed_for_aggregator(&round.feed) {
+            round.feed_info = Some(feed.info(&round.feed));
+        }
+    }
+
+    /// Attach feed details and the normalized price to a NewTransmission event
+    pub fn annotate_transmission(&self, transmission: &mut ParsedNewTransmission) {
+        if let Some(feed) = self.feed_for_aggregator(&transmission.feed) {
+            transmission.feed_info = Some(feed.info(&transmission.feed));
+            transmission.price = feed.normalize(transmission.answer);
+        }
+    }
+}
+
+/// A Chainlink event the registry acts on, by its index in `FeedRegistry::apply`'s slices
+#[derive(Debug, Clone, Copy)]
+enum RegistryEvent {
+    Confirmed(usize),
+    Answer(usize),
+    Round(usize),
+    Transmission(usize),
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::flashblocks::test_logs::receipt_log;
 
     #[test]
     fn test_chainlink_events_default() {
@@ -157,4 +565,190 @@ mod tests {
         let expected_sig = alloy_primitives::keccak256(b"NewRound(uint256,address,uint256)");
         assert_eq!(NewRound::SIGNATURE_HASH, expected_sig);
     }
+
+    #[test]
+    fn test_aggregator_confirmed_signature() {
+        let expected_sig = alloy_primitives::keccak256(b"AggregatorConfirmed(address,address)");
+        assert_eq!(AggregatorConfirmed::SIGNATURE_HASH, expected_sig);
+    }
+
+    #[test]
+    fn test_new_transmission_signature() {
+        let expected_sig = alloy_primitives::keccak256(
+            b"NewTransmission(uint32,int192,address,uint32,int192[],bytes,int192,bytes32,uint40)",
+        );
+        assert_eq!(NewTransmission::SIGNATURE_HASH, expected_sig);
+    }
+
+    #[test]
+    fn test_parse_new_transmission() {
+        let i192 = |v: i64| I192::try_from(v).unwrap();
+        let event = NewTransmission {
+            aggregatorRoundId: 42,
+            answer: i192(250_000_000_000),
+            transmitter: Address::repeat_byte(0x07),
+            observationsTimestamp: 1_700_000_000,
+            observations: vec![i192(-1), i192(249_000_000_000), i192(251_000_000_000)],
+            observers: vec![3, 0, 9].into(),
+            juelsPerFeeCoin: i192(1_234),
+            configDigest: B256::repeat_byte(0xcd),
+            epochAndRound: alloy_primitives::Uint::from((17 << 8) | 2),
+        };
+        let data = event.encode_log_data();
+        let log = receipt_log(Address::repeat_byte(0xfe), data);
+
+        let parsed = ParsedNewTransmission::try_from_log(&log, &EventContext::default()).unwrap();
+        assert_eq!(parsed.feed, log.address);
+        assert_eq!(parsed.aggregator_round_id, 42);
+        assert_eq!(parsed.answer, I256::try_from(250_000_000_000i64).unwrap());
+        assert_eq!(
+            parsed.observations,
+            vec![
+                I256::MINUS_ONE,
+                I256::try_from(249_000_000_000i64).unwrap(),
+                I256::try_from(251_000_000_000i64).unwrap(),
+            ]
+        );
+        assert_eq!(parsed.observers, vec![3, 0, 9]);
+        assert_eq!(parsed.juels_per_fee_coin, I256::try_from(1_234).unwrap());
+        assert_eq!(parsed.epoch_and_round >> 8, 17);
+    }
+
+    const FEEDS: &str = r#"{
+        "feeds": [
+            {
+                "proxy": "0x0000000000000000000000000000000000000e70",
+                "description": "ETH / USD",
+                "base": "ETH",
+                "quote": "USD",
+                "decimals": 8,
+                "aggregators": [
+                    "0x00000000000000000000000000000000000000a1",
+                    "0x00000000000000000000000000000000000000a2"
+                ]
+            }
+        ]
+    }"#;
+
+    fn answer_updated(feed: Address, answer: i64) -> ParsedAnswerUpdated {
+        ParsedAnswerUpdated {
+            context: EventContext::default(),
+            feed,
+            answer: I256::try_from(answer).unwrap(),
+            round_id: U256::from(1),
+            updated_at: U256::from(1_700_000_000u64),
+            feed_info: None,
+            price: None,
+        }
+    }
+
+    #[test]
+    fn test_feed_registry_annotates_answers() {
+        let mut registry = FeedRegistry::from_json(FEEDS).unwrap();
+        assert_eq!(registry.len(), 1);
+
+        let current = Address::with_last_byte(0xa2);
+        let mut update = answer_updated(current, 250_012_345_678);
+        registry.annotate_answer(&mut update);
+        let info = update.feed_info.as_ref().unwrap();
+        assert_eq!(info.pair, "ETH/USD");
+        assert_eq!(info.decimals, 8);
+        assert!(info.current_aggregator);
+        assert_eq!(update.price.as_deref(), Some("2500.12345678"));
+
+        // Updates from a retired aggregator still resolve, flagged as not current
+        let retired = Address::with_last_byte(0xa1);
+        let mut update = answer_updated(retired, -100_000_000);
+        registry.annotate_answer(&mut update);
+        assert!(!update.feed_info.as_ref().unwrap().current_aggregator);
+        assert_eq!(update.price.as_deref(), Some("-1.00000000"));
+
+        // Unknown aggregators are left untouched
+        let mut update = answer_updated(Address::with_last_byte(0xff), 1);
+        registry.annotate_answer(&mut update);
+        assert!(update.feed_info.is_none());
+        assert!(
+            !serde_json::to_value(&update)
+                .unwrap()
+                .as_object()
+                .unwrap()
+                .contains_key("price")
+        );
+
+        // The proxy's AggregatorConfirmed makes the new aggregator current
+        let proxy: Address = "0x0000000000000000000000000000000000000e70"
+            .parse()
+            .unwrap();
+        let next = Address::with_last_byte(0xa3);
+        let data = AggregatorConfirmed {
+            previous: current,
+            latest: next,
+        }
+        .encode_log_data();
+        let log = receipt_log(proxy, data);
+        let mut confirmed =
+            ParsedAggregatorConfirmed::try_from_log(&log, &EventContext::default()).unwrap();
+        assert_eq!((confirmed.previous, confirmed.latest), (current, next));
+        assert!(registry.apply_confirmed(&mut confirmed));
+        assert!(confirmed.feed_info.unwrap().current_aggregator);
+
+        let mut update = answer_updated(current, 1);
+        registry.annotate_answer(&mut update);
+        assert!(!update.feed_info.unwrap().current_aggregator);
+        let mut update = answer_updated(next, 1);
+        registry.annotate_answer(&mut update);
+        assert!(update.feed_info.unwrap().current_aggregator);
+
+        // Unknown proxies are not rotated
+        let mut confirmed = ParsedAggregatorConfirmed {
+            proxy: Address::with_last_byte(0xee),
+            ..ParsedAggregatorConfirmed::try_from_log(&log, &EventContext::default()).unwrap()
+        };
+        assert!(!registry.apply_confirmed(&mut confirmed));
+        assert!(confirmed.feed_info.is_none());
+    }
+
+    #[test]
+    fn test_apply_in_execution_order() {
+        let mut registry = FeedRegistry::from_json(FEEDS).unwrap();
+        let current = Address::with_last_byte(0xa2);
+        let next = Address::with_last_byte(0xa3);
+        let at = |tx: u64| EventContext {
+            tx_index: Some(tx),
+            ..Default::default()
+        };
+
+        let mut confirmations = vec![ParsedAggregatorConfirmed {
+            context: at(1),
+            proxy: registry.feeds[0].proxy,
+            previous: current,
+            latest: next,
+            feed_info: None,
+        }];
+        // The retiring aggregator answers before the rotation, its successor after
+        let mut answers = vec![
+            ParsedAnswerUpdated {
+                context: at(2),
+                ..answer_updated(next, 1)
+            },
+            ParsedAnswerUpdated {
+                context: at(0),
+                ..answer_updated(current, 1)
+            },
+        ];
+        let mut rounds = vec![ParsedNewRound {
+            context: at(3),
+            feed: current,
+            round_id: U256::from(2),
+            started_by: Address::ZERO,
+            started_at: U256::ZERO,
+            feed_info: None,
+        }];
+
+        registry.apply(&mut confirmations, &mut answers, &mut rounds, &mut []);
+        assert!(confirmations[0].feed_info.is_some());
+        assert!(answers[0].feed_info.as_ref().unwrap().current_aggregator);
+        assert!(answers[1].feed_info.as_ref().unwrap().current_aggregator);
+        assert!(!rounds[0].feed_info.as_ref().unwrap().current_aggregator);
+    }
 }
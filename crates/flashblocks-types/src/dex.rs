@@ -0,0 +1,356 @@
+//! Factory-based DEX attribution for V3-style pools.
+//!
+//! Uniswap V3 and its forks emit the same pool events, so the pool address
+//! alone doesn't say which DEX a swap happened on. The registry maps factory
+//! addresses to a DEX name and learns each pool's DEX from the factory's
+//! `PoolCreated` event. Pools created before startup are only attributed if
+//! they are listed directly, or if their factory declares its pool
+//! `init_code_hash` and both tokens are in the token registry, in which case
+//! the pool address is derived the way the factory's CREATE2 deploys it.
+//! Swaps on any other pool carry no `dex`.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use alloy_primitives::{Address, B256, keccak256};
+use serde::Deserialize;
+
+use crate::univ3::{ParsedPoolCreated, ParsedSwap, UniV3Updates};
+
+pub const UNISWAP_V3: &str = "uniswap_v3";
+pub const AERODROME_SLIPSTREAM: &str = "aerodrome_slipstream";
+pub const PANCAKESWAP_V3: &str = "pancakeswap_v3";
+pub const SUSHISWAP_V3: &str = "sushiswap_v3";
+
+/// Factory list bundled with the crate
+const BUNDLED_DEXES: &str = include_str!("../dexes/base.json");
+
+/// A pool factory and the DEX it belongs to
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DexFactory {
+    pub address: Address,
+    pub dex: String,
+    /// Keccak256 of the pool creation code, for factories that deploy pools
+    /// with CREATE2 salted by `(token0, token1, fee)` like Uniswap V3
+    #[serde(default)]
+    pub init_code_hash: Option<B256>,
+    /// The contract that deploys the pools, when it isn't the factory
+    #[serde(default)]
+    pub deployer: Option<Address>,
+    /// Fee tiers, in hundredths of a bip, to derive pool addresses for
+    #[serde(default)]
+    pub fees: Vec<u32>,
+}
+
+impl DexFactory {
+    /// Where this factory deploys the `(token0, token1, fee)` pool, if its
+    /// `init_code_hash` is known. `token0` must sort below `token1`.
+    pub fn pool_address(&self, token0: Address, token1: Address, fee: u32) -> Option<Address> {
+        let init_code_hash = self.init_code_hash?;
+        let mut salt = [0u8; 96];
+        salt[12..32].copy_from_slice(token0.as_slice());
+        salt[44..64].copy_from_slice(token1.as_slice());
+        salt[92..96].copy_from_slice(&fee.to_be_bytes());
+        let deployer = self.deployer.unwrap_or(self.address);
+        Some(deployer.create2(keccak256(salt), init_code_hash))
+    }
+}
+
+/// A pool attributed to a DEX
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct DexPool {
+    pub address: Address,
+    pub dex: String,
+    /// The pool's tokens, when learned from its PoolCreated event
+    #[serde(default)]
+    pub tokens: Option<(Address, Address)>,
+}
+
+/// On-disk layout of the DEX registry config
+#[derive(Debug, Deserialize)]
+struct DexRegistryFile {
+    #[serde(default)]
+    factories: Vec<DexFactory>,
+    #[serde(default)]
+    pools: Vec<DexPool>,
+}
+
+/// Maps factories and pools to the DEX they belong to
+#[derive(Debug, Clone, Default)]
+pub struct DexRegistry {
+    factories: HashMap<Address, DexFactory>,
+    pools: HashMap<Address, DexPool>,
+}
+
+impl DexRegistry {
+    pub fn new(factories: Vec<DexFactory>, pools: Vec<DexPool>) -> Self {
+        Self {
+            factories: factories.into_iter().map(|f| (f.address, f)).collect(),
+            pools: pools.into_iter().map(|p| (p.address, p)).collect(),
+        }
+    }
+
+    /// The registry bundled with the crate: V3-style factories on Base and Base Sepolia
+    pub fn bundled() -> Self {
+        Self::from_json(BUNDLED_DEXES).expect("bundled DEX list is valid")
+    }
+
+    /// Parse a registry from JSON of the form `{"factories": [...], "pools": [...]}`
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let file: DexRegistryFile = serde_json::from_str(json)?;
+        Ok(Self::new(file.factories, file.pools))
+    }
+
+    /// Load a registry from a JSON file
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let json = std::fs::read_to_string(path)?;
+        Ok(Self::from_json(&json)?)
+    }
+
+    /// Add the entries of `other`, replacing any existing entries for the same address
+    pub fn extend(&mut self, other: DexRegistry) {
+        self.factories.extend(other.factories);
+        self.pools.extend(other.pools);
+    }
+
+    /// Number of known factories
+    pub fn factory_count(&self) -> usize {
+        self.factories.len()
+    }
+
+    /// Number of attributed pools
+    pub fn pool_count(&self) -> usize {
+        self.pools.len()
+    }
+
+    pub fn dex_for_factory(&self, factory: &Address) -> Option<&str> {
+        self.factories.get(factory).map(|f| f.dex.as_str())
+    }
+
+    pub fn pool(&self, pool: &Address) -> Option<&DexPool> {
+        self.pools.get(pool)
+    }
+
+    pub fn dex_for_pool(&self, pool: &Address) -> Option<&str> {
+        self.pool(pool).map(|p| p.dex.as_str())
+    }
+
+    /// Attribute the pool every factory with an `init_code_hash` would deploy
+    /// for each pair of `tokens` at each of its fee tiers, so pools created
+    /// before startup resolve without being listed. Pools already in the
+    /// registry are kept. Returns the number of pools added.
+    pub fn derive_pools(&mut self, tokens: impl IntoIterator<Item = Address>) -> usize {
+        let mut tokens: Vec<_> = tokens.into_iter().filter(|t| !t.is_zero()).collect();
+        tokens.sort();
+        tokens.dedup();
+
+        let before = self.pools.len();
+        for factory in self.factories.values() {
+            for (i, &token0) in tokens.iter().enumerate() {
+                for &token1 in &tokens[i + 1..] {
+                    for &fee in &factory.fees {
+                        let Some(pool) = factory.pool_address(token0, token1, fee) else {
+                            continue;
+                        };
+                        self.pools.entry(pool).or_insert_with(|| DexPool {
+                            address: pool,
+                            dex: factory.dex.clone(),
+                            tokens: Some((token0, token1)),
+                        });
+                    }
+                }
+            }
+        }
+        self.pools.len() - before
+    }
+
+    /// Attribute a newly created pool to its factory's DEX.
+    /// Returns false if the factory is unknown.
+    pub fn record_pool_created(&mut self, created: &mut ParsedPoolCreated) -> bool {
+        let Some(dex) = self.dex_for_factory(&created.factory) else {
+            return false;
+        };
+        let dex = dex.to_string();
+        created.dex = Some(dex.clone());
+        self.pools.insert(
+            created.pool,
+            DexPool {
+                address: created.pool,
+                dex,
+                tokens: Some((created.token0, created.token1)),
+            },
+        );
+        true
+    }
+
+    /// Set a swap's `dex` from its pool, unless its decoder already did
+    pub fn annotate_swap(&self, swap: &mut ParsedSwap) {
+        if swap.dex.is_none() {
+            swap.dex = self.dex_for_pool(&swap.pool).map(str::to_string);
+        }
+    }
+
+    /// Record the pools created in `updates`, then attribute its swaps
+    pub fn annotate(&mut self, updates: &mut UniV3Updates) {
+        for created in &mut updates.pools_created {
+            self.record_pool_created(created);
+        }
+        for swap in &mut updates.swaps {
+            self.annotate_swap(swap);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flashblocks::test_logs::context_log;
+    use crate::univ3::{PancakeV3Pool, PoolCreated, SlipstreamFactory, Swap};
+    use alloy_primitives::aliases::{I24, U24};
+    use alloy_primitives::{I256, U160, address};
+    use alloy_sol_types::SolEvent;
+
+    const SLIPSTREAM_FACTORY: Address = address!("5e7BB104d84c7CB9B682AaC2F3d509f5F406809A");
+    const PANCAKE_FACTORY: Address = address!("0BFbCF9fa4f9C56B0F40a671Ad40E0805A091865");
+
+    fn swap_data() -> Swap {
+        Swap {
+            sender: Address::repeat_byte(0x01),
+            recipient: Address::repeat_byte(0x02),
+            amount0: I256::try_from(-5).unwrap(),
+            amount1: I256::try_from(7).unwrap(),
+            sqrtPriceX96: U160::from(1) << 96,
+            liquidity: 1,
+            tick: I24::ZERO,
+        }
+    }
+
+    #[test]
+    fn test_pool_created_attributes_later_swaps() {
+        let slipstream_pool = Address::repeat_byte(0x51);
+        let sushi_pool = Address::repeat_byte(0x55);
+        let unknown_pool = Address::repeat_byte(0x99);
+
+        let slipstream_created = SlipstreamFactory::PoolCreated {
+            token0: Address::repeat_byte(0x0a),
+            token1: Address::repeat_byte(0x0b),
+            tickSpacing: I24::try_from(100).unwrap(),
+            pool: slipstream_pool,
+        };
+        let unknown_created = PoolCreated {
+            token0: Address::repeat_byte(0x0a),
+            token1: Address::repeat_byte(0x0b),
+            fee: U24::from(500),
+            tickSpacing: I24::try_from(10).unwrap(),
+            pool: unknown_pool,
+        };
+        let logs = vec![
+            context_log(SLIPSTREAM_FACTORY, slipstream_created.encode_log_data()),
+            context_log(
+                Address::repeat_byte(0xfa),
+                unknown_created.encode_log_data(),
+            ),
+            context_log(slipstream_pool, swap_data().encode_log_data()),
+            context_log(sushi_pool, swap_data().encode_log_data()),
+            context_log(unknown_pool, swap_data().encode_log_data()),
+        ];
+
+        let mut registry = DexRegistry::bundled();
+        assert_eq!(
+            registry.dex_for_factory(&PANCAKE_FACTORY),
+            Some(PANCAKESWAP_V3)
+        );
+        registry.extend(
+            DexRegistry::from_json(&format!(
+                r#"{{"pools": [{{"address": "{sushi_pool}", "dex": "{SUSHISWAP_V3}"}}]}}"#
+            ))
+            .unwrap(),
+        );
+
+        let mut updates = UniV3Updates::extract_all(&logs);
+        registry.annotate(&mut updates);
+
+        let created = &updates.pools_created;
+        assert_eq!(created.len(), 2);
+        assert_eq!(created[0].fee, None);
+        assert_eq!(created[0].tick_spacing, 100);
+        assert_eq!(created[0].dex.as_deref(), Some(AERODROME_SLIPSTREAM));
+        assert_eq!(created[1].fee, Some(500));
+        assert_eq!(created[1].dex, None);
+
+        let dexes: Vec<_> = updates.swaps.iter().map(|s| s.dex.as_deref()).collect();
+        assert_eq!(
+            dexes,
+            vec![Some(AERODROME_SLIPSTREAM), Some(SUSHISWAP_V3), None]
+        );
+        assert_eq!(
+            registry.pool(&slipstream_pool).unwrap().tokens,
+            Some((Address::repeat_byte(0x0a), Address::repeat_byte(0x0b)))
+        );
+    }
+
+    #[test]
+    fn test_derived_pools() {
+        let weth = address!("4200000000000000000000000000000000000006");
+        let usdc = address!("833589fCD6eDb6E08f4c7C32D4f71b54bdA02913");
+        // The Uniswap V3 WETH/USDC 0.05% pool on Base
+        let weth_usdc_500 = address!("d0b53D9277642d899DF5C87A3966A349A798F224");
+
+        let mut registry = DexRegistry::bundled();
+        let uniswap = &registry.factories[&address!("33128a8fC17869897dcE68Ed026d694621f6FDfD")];
+        assert_eq!(uniswap.pool_address(weth, usdc, 500), Some(weth_usdc_500));
+
+        let mut without_listed =
+            DexRegistry::new(registry.factories.values().cloned().collect(), vec![]);
+        assert!(without_listed.derive_pools([usdc, weth, weth, Address::ZERO]) > 0);
+        let pool = without_listed.pool(&weth_usdc_500).unwrap();
+        assert_eq!(pool.dex, UNISWAP_V3);
+        assert_eq!(pool.tokens, Some((weth, usdc)));
+
+        // Listed pools win over derived ones
+        registry.pools.get_mut(&weth_usdc_500).unwrap().dex = SUSHISWAP_V3.to_string();
+        registry.derive_pools([weth, usdc]);
+        assert_eq!(registry.dex_for_pool(&weth_usdc_500), Some(SUSHISWAP_V3));
+
+        // Factories without an init code hash derive nothing
+        let slipstream = &registry.factories[&SLIPSTREAM_FACTORY];
+        assert_eq!(slipstream.pool_address(weth, usdc, 500), None);
+    }
+
+    #[test]
+    fn test_pancake_swap_decoder() {
+        let swap = swap_data();
+        let pancake = PancakeV3Pool::Swap {
+            sender: swap.sender,
+            recipient: swap.recipient,
+            amount0: swap.amount0,
+            amount1: swap.amount1,
+            sqrtPriceX96: swap.sqrtPriceX96,
+            liquidity: swap.liquidity,
+            tick: swap.tick,
+            protocolFeesToken0: 3,
+            protocolFeesToken1: 0,
+        };
+        assert_ne!(PancakeV3Pool::Swap::SIGNATURE_HASH, Swap::SIGNATURE_HASH);
+
+        let log = context_log(Address::repeat_byte(0xca), pancake.encode_log_data());
+        let parsed = ParsedSwap::from_log(&log.log, &log.context).unwrap();
+        assert_eq!(parsed.dex.as_deref(), Some(PANCAKESWAP_V3));
+        assert_eq!(parsed.amount0, swap.amount0);
+        assert_eq!(parsed.protocol_fees_token0, Some(3));
+        assert_eq!(parsed.protocol_fees_token1, Some(0));
+
+        // The decoder's attribution wins over the registry
+        let mut parsed = parsed;
+        DexRegistry::new(
+            vec![],
+            vec![DexPool {
+                address: parsed.pool,
+                dex: UNISWAP_V3.to_string(),
+                tokens: None,
+            }],
+        )
+        .annotate_swap(&mut parsed);
+        assert_eq!(parsed.dex.as_deref(), Some(PANCAKESWAP_V3));
+    }
+}
@@ -0,0 +1,246 @@
+use alloy_primitives::{Address, Bloom, BloomInput, U256};
+use alloy_sol_types::{SolEvent, sol};
+use serde::Serialize;
+
+use crate::flashblocks::{ContextLog, EventContext, ReceiptLog};
+
+// ERC-20 token events. ERC-721 shares these signatures but indexes the
+// token id, so its logs have one more topic and are skipped.
+sol! {
+    /// Emitted when tokens move between accounts, including mints and burns
+    event Transfer(address indexed from, address indexed to, uint256 value);
+
+    /// Emitted when an owner sets a spender's allowance
+    event Approval(address indexed owner, address indexed spender, uint256 value);
+}
+
+/// Detected ERC-20 events based on bloom filter
+#[derive(Debug, Default)]
+pub struct Erc20Events {
+    pub may_have_transfer: bool,
+    pub may_have_approval: bool,
+}
+
+impl Erc20Events {
+    /// Check the bloom filter for potential ERC-20 events.
+    /// Note: Bloom filters can have false positives but no false negatives.
+    pub fn from_bloom(bloom: &Bloom) -> Self {
+        Self {
+            may_have_transfer: bloom
+                .contains_input(BloomInput::Raw(Transfer::SIGNATURE_HASH.as_slice())),
+            may_have_approval: bloom
+                .contains_input(BloomInput::Raw(Approval::SIGNATURE_HASH.as_slice())),
+        }
+    }
+
+    /// Returns true if any ERC-20 event might be present
+    pub fn any(&self) -> bool {
+        self.may_have_transfer || self.may_have_approval
+    }
+}
+
+/// A decoded ERC-20 Transfer event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedTransfer {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// The token contract that emitted the event
+    pub token: Address,
+    /// The sender; the zero address for mints
+    pub from: Address,
+    /// The recipient; the zero address for burns
+    pub to: Address,
+    /// Amount transferred, in the token's smallest unit
+    pub value: U256,
+}
+
+impl ParsedTransfer {
+    /// Try to parse an ERC-20 Transfer event from a log
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 3 {
+            return None;
+        }
+
+        if log.topics[0] != Transfer::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded = Transfer::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            token: log.address,
+            from: decoded.from,
+            to: decoded.to,
+            value: decoded.value,
+        })
+    }
+
+    /// Extract all ERC-20 Transfer events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+
+    /// Returns true if the token, sender or recipient is `address`
+    pub fn touches(&self, address: &Address) -> bool {
+        self.token == *address || self.from == *address || self.to == *address
+    }
+}
+
+/// A decoded ERC-20 Approval event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedApproval {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// The token contract that emitted the event
+    pub token: Address,
+    /// The account granting the allowance
+    pub owner: Address,
+    /// The account allowed to spend
+    pub spender: Address,
+    /// The new allowance, in the token's smallest unit
+    pub value: U256,
+}
+
+impl ParsedApproval {
+    /// Try to parse an ERC-20 Approval event from a log
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 3 {
+            return None;
+        }
+
+        if log.topics[0] != Approval::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded = Approval::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            token: log.address,
+            owner: decoded.owner,
+            spender: decoded.spender,
+            value: decoded.value,
+        })
+    }
+
+    /// Extract all ERC-20 Approval events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+
+    /// Returns true if the token, owner or spender is `address`
+    pub fn touches(&self, address: &Address) -> bool {
+        self.token == *address || self.owner == *address || self.spender == *address
+    }
+}
+
+/// A transfer at or above its token's alert threshold
+#[derive(Debug, Clone, Serialize)]
+pub struct LargeTransfer {
+    #[serde(flatten)]
+    pub transfer: ParsedTransfer,
+    /// The threshold it crossed, in the token's smallest unit
+    pub threshold: U256,
+}
+
+/// Collection of ERC-20 events
+#[derive(Debug, Default)]
+pub struct Erc20Updates {
+    pub transfers: Vec<ParsedTransfer>,
+    pub approvals: Vec<ParsedApproval>,
+}
+
+impl Erc20Updates {
+    /// Extract all ERC-20 events from logs
+    pub fn extract_all(logs: &[ContextLog]) -> Self {
+        Self {
+            transfers: ParsedTransfer::extract_all(logs),
+            approvals: ParsedApproval::extract_all(logs),
+        }
+    }
+
+    /// Returns true if no events were found
+    pub fn is_empty(&self) -> bool {
+        self.transfers.is_empty() && self.approvals.is_empty()
+    }
+
+    /// Total count of all events
+    pub fn total_count(&self) -> usize {
+        self.transfers.len() + self.approvals.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flashblocks::test_logs::receipt_log;
+    use alloy_primitives::{B256, keccak256};
+
+    const TOKEN: Address = Address::repeat_byte(0x70);
+
+    #[test]
+    fn test_erc20_signatures() {
+        assert_eq!(
+            Transfer::SIGNATURE_HASH,
+            keccak256(b"Transfer(address,address,uint256)")
+        );
+        assert_eq!(
+            Approval::SIGNATURE_HASH,
+            keccak256(b"Approval(address,address,uint256)")
+        );
+    }
+
+    #[test]
+    fn test_parse_transfer_and_skip_erc721() {
+        let context = EventContext::default();
+        let event = Transfer {
+            from: Address::repeat_byte(0x01),
+            to: Address::repeat_byte(0x02),
+            value: U256::from(1_000),
+        };
+        let log = receipt_log(TOKEN, event.encode_log_data());
+        let transfer = ParsedTransfer::try_from_log(&log, &context).unwrap();
+        assert_eq!(transfer.token, log.address);
+        assert_eq!(transfer.from, event.from);
+        assert_eq!(transfer.to, event.to);
+        assert_eq!(transfer.value, U256::from(1_000));
+        assert!(transfer.touches(&log.address));
+        assert!(!transfer.touches(&Address::repeat_byte(0x03)));
+        assert!(ParsedApproval::try_from_log(&log, &context).is_none());
+
+        // ERC-721 Transfer: same signature, token id as a fourth topic, no data
+        let erc721 = ReceiptLog {
+            address: Address::repeat_byte(0x72),
+            topics: vec![
+                Transfer::SIGNATURE_HASH,
+                event.from.into_word(),
+                event.to.into_word(),
+                B256::with_last_byte(7),
+            ],
+            data: Default::default(),
+        };
+        assert!(ParsedTransfer::try_from_log(&erc721, &context).is_none());
+    }
+
+    #[test]
+    fn test_parse_approval() {
+        let event = Approval {
+            owner: Address::repeat_byte(0x01),
+            spender: Address::repeat_byte(0x02),
+            value: U256::MAX,
+        };
+        let approval = ParsedApproval::try_from_log(
+            &receipt_log(TOKEN, event.encode_log_data()),
+            &Default::default(),
+        )
+        .unwrap();
+        assert_eq!(approval.owner, event.owner);
+        assert_eq!(approval.spender, event.spender);
+        assert_eq!(approval.value, U256::MAX);
+    }
+}
@@ -0,0 +1,514 @@
+//! ERC-4337 account abstraction: EntryPoint v0.6 and v0.7 events.
+//!
+//! Bundlers submit user operations through `handleOps`, so the transaction's
+//! sender is the bundler, not the smart account. The EntryPoint emits
+//! `BeforeExecution` once validation is done, then executes each op in turn and
+//! closes it with its `UserOperationEvent`. Every log between the two belongs
+//! to that op, which is how [`link_user_ops`] attributes other events in the
+//! transaction to the account that caused them.
+
+use alloy_primitives::{Address, B256, Bloom, BloomInput, Bytes, U256, address};
+use alloy_sol_types::{SolEvent, sol};
+use serde::Serialize;
+
+use crate::flashblocks::{ContextLog, EventContext, ReceiptLog};
+
+/// EntryPoint v0.6, at the same address on every chain
+pub const ENTRY_POINT_V06: Address = address!("5FF137D4b0FDCD49DcA30c7CF57E578a026d2789");
+/// EntryPoint v0.7, at the same address on every chain
+pub const ENTRY_POINT_V07: Address = address!("0000000071727De22E5E9d8BAf0edAc6f37da032");
+
+// EntryPoint events, identical in v0.6 and v0.7
+sol! {
+    /// Emitted after each successful or reverted user operation
+    event UserOperationEvent(
+        bytes32 indexed userOpHash,
+        address indexed sender,
+        address indexed paymaster,
+        uint256 nonce,
+        bool success,
+        uint256 actualGasCost,
+        uint256 actualGasUsed
+    );
+
+    /// Emitted when a user operation deploys its account through a factory
+    event AccountDeployed(
+        bytes32 indexed userOpHash,
+        address indexed sender,
+        address factory,
+        address paymaster
+    );
+
+    /// Emitted when a user operation's execution reverts
+    event UserOperationRevertReason(
+        bytes32 indexed userOpHash,
+        address indexed sender,
+        uint256 nonce,
+        bytes revertReason
+    );
+
+    /// Emitted once per bundle, after validation and before the first op executes
+    event BeforeExecution();
+}
+
+/// Which EntryPoint emitted an event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum EntryPointVersion {
+    #[serde(rename = "v0.6")]
+    V06,
+    #[serde(rename = "v0.7")]
+    V07,
+}
+
+impl EntryPointVersion {
+    /// The version of the EntryPoint at `address`, if it is one
+    pub fn of(address: &Address) -> Option<Self> {
+        match *address {
+            ENTRY_POINT_V06 => Some(Self::V06),
+            ENTRY_POINT_V07 => Some(Self::V07),
+            _ => None,
+        }
+    }
+}
+
+/// The user operation a log was emitted under
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct UserOpRef {
+    pub user_op_hash: B256,
+    /// The smart account that sent the op
+    pub sender: Address,
+}
+
+/// Detected ERC-4337 events based on bloom filter
+#[derive(Debug, Default)]
+pub struct Erc4337Events {
+    pub may_have_user_operation: bool,
+    pub may_have_account_deployed: bool,
+    pub may_have_revert_reason: bool,
+}
+
+impl Erc4337Events {
+    /// Check the bloom filter for potential ERC-4337 events.
+    /// Note: Bloom filters can have false positives but no false negatives.
+    pub fn from_bloom(bloom: &Bloom) -> Self {
+        let contains = |topic: B256| bloom.contains_input(BloomInput::Raw(topic.as_slice()));
+        Self {
+            may_have_user_operation: contains(UserOperationEvent::SIGNATURE_HASH),
+            may_have_account_deployed: contains(AccountDeployed::SIGNATURE_HASH),
+            may_have_revert_reason: contains(UserOperationRevertReason::SIGNATURE_HASH),
+        }
+    }
+
+    /// Returns true if any ERC-4337 event might be present
+    pub fn any(&self) -> bool {
+        self.may_have_user_operation
+            || self.may_have_account_deployed
+            || self.may_have_revert_reason
+    }
+}
+
+/// The paymaster, or none if the account paid for itself
+fn paymaster(address: Address) -> Option<Address> {
+    (address != Address::ZERO).then_some(address)
+}
+
+/// A decoded UserOperationEvent: the outcome of one user operation
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedUserOperation {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// The EntryPoint that executed the op
+    pub entry_point: Address,
+    pub version: EntryPointVersion,
+    pub user_op_hash: B256,
+    /// The smart account that sent the op
+    pub sender: Address,
+    /// The paymaster that sponsored the op, if any
+    pub paymaster: Option<Address>,
+    pub nonce: U256,
+    /// Whether the op's execution succeeded
+    pub success: bool,
+    /// Gas cost charged to the account or paymaster, in wei
+    pub actual_gas_cost: U256,
+    pub actual_gas_used: U256,
+    /// Revert data of a failed execution, from its UserOperationRevertReason
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revert_reason: Option<Bytes>,
+}
+
+impl ParsedUserOperation {
+    /// Try to parse a UserOperationEvent from a log of a known EntryPoint
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        let version = EntryPointVersion::of(&log.address)?;
+        if log.topics.len() != 4 {
+            return None;
+        }
+
+        if log.topics[0] != UserOperationEvent::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded =
+            UserOperationEvent::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            entry_point: log.address,
+            version,
+            user_op_hash: decoded.userOpHash,
+            sender: decoded.sender,
+            paymaster: paymaster(decoded.paymaster),
+            nonce: decoded.nonce,
+            success: decoded.success,
+            actual_gas_cost: decoded.actualGasCost,
+            actual_gas_used: decoded.actualGasUsed,
+            revert_reason: None,
+        })
+    }
+
+    /// Extract all UserOperationEvents from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// A decoded AccountDeployed event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedAccountDeployed {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// The EntryPoint that deployed the account
+    pub entry_point: Address,
+    pub version: EntryPointVersion,
+    /// The op whose initCode deployed the account
+    pub user_op_hash: B256,
+    /// The new smart account
+    pub sender: Address,
+    /// The factory that created the account
+    pub factory: Address,
+    /// The paymaster that sponsored the op, if any
+    pub paymaster: Option<Address>,
+}
+
+impl ParsedAccountDeployed {
+    /// Try to parse an AccountDeployed event from a log of a known EntryPoint
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        let version = EntryPointVersion::of(&log.address)?;
+        if log.topics.len() != 3 {
+            return None;
+        }
+
+        if log.topics[0] != AccountDeployed::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded =
+            AccountDeployed::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            entry_point: log.address,
+            version,
+            user_op_hash: decoded.userOpHash,
+            sender: decoded.sender,
+            factory: decoded.factory,
+            paymaster: paymaster(decoded.paymaster),
+        })
+    }
+
+    /// Extract all AccountDeployed events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// A decoded UserOperationRevertReason event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedUserOperationRevertReason {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// The EntryPoint that executed the op
+    pub entry_point: Address,
+    pub version: EntryPointVersion,
+    pub user_op_hash: B256,
+    /// The smart account that sent the op
+    pub sender: Address,
+    pub nonce: U256,
+    /// The revert data of the op's execution
+    pub revert_reason: Bytes,
+}
+
+impl ParsedUserOperationRevertReason {
+    /// Try to parse a UserOperationRevertReason event from a log of a known EntryPoint
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        let version = EntryPointVersion::of(&log.address)?;
+        if log.topics.len() != 3 {
+            return None;
+        }
+
+        if log.topics[0] != UserOperationRevertReason::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded =
+            UserOperationRevertReason::decode_raw_log(log.topics.iter().copied(), &log.data, true)
+                .ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            entry_point: log.address,
+            version,
+            user_op_hash: decoded.userOpHash,
+            sender: decoded.sender,
+            nonce: decoded.nonce,
+            revert_reason: decoded.revertReason,
+        })
+    }
+
+    /// Extract all UserOperationRevertReason events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// Attribute each log of a receipt to the user operation it was emitted under.
+///
+/// Logs after the EntryPoint's `BeforeExecution` (or the previous op's
+/// `UserOperationEvent`) up to and including an op's `UserOperationEvent`
+/// belong to that op. Validation-phase logs are unattributed, except each
+/// op's own `AccountDeployed`. Returns one entry per log.
+pub fn link_user_ops(logs: &[ReceiptLog]) -> Vec<Option<UserOpRef>> {
+    let mut links = vec![None; logs.len()];
+    let mut start = 0;
+    for (i, log) in logs.iter().enumerate() {
+        if EntryPointVersion::of(&log.address).is_none() {
+            continue;
+        }
+        if log.topics.first() == Some(&BeforeExecution::SIGNATURE_HASH) {
+            start = i + 1;
+            continue;
+        }
+        if log.topics.len() < 3 {
+            continue;
+        }
+
+        let user_op = UserOpRef {
+            user_op_hash: log.topics[1],
+            sender: Address::from_word(log.topics[2]),
+        };
+        if log.topics[0] == UserOperationEvent::SIGNATURE_HASH {
+            links[start..=i].fill(Some(user_op));
+            start = i + 1;
+        } else if log.topics[0] == AccountDeployed::SIGNATURE_HASH {
+            links[i] = Some(user_op);
+        }
+    }
+    links
+}
+
+/// Collection of ERC-4337 events
+#[derive(Debug, Default)]
+pub struct Erc4337Updates {
+    /// User operation outcomes, with the revert reason of failed ops
+    pub user_operations: Vec<ParsedUserOperation>,
+    pub accounts_deployed: Vec<ParsedAccountDeployed>,
+    pub revert_reasons: Vec<ParsedUserOperationRevertReason>,
+}
+
+impl Erc4337Updates {
+    /// Extract all ERC-4337 events from logs, joining each revert reason to its op
+    pub fn extract_all(logs: &[ContextLog]) -> Self {
+        let mut user_operations = ParsedUserOperation::extract_all(logs);
+        let revert_reasons = ParsedUserOperationRevertReason::extract_all(logs);
+        for op in &mut user_operations {
+            op.revert_reason = revert_reasons
+                .iter()
+                .find(|r| {
+                    r.user_op_hash == op.user_op_hash && r.context.tx_hash == op.context.tx_hash
+                })
+                .map(|r| r.revert_reason.clone());
+        }
+
+        Self {
+            user_operations,
+            accounts_deployed: ParsedAccountDeployed::extract_all(logs),
+            revert_reasons,
+        }
+    }
+
+    /// Returns true if no events were found
+    pub fn is_empty(&self) -> bool {
+        self.user_operations.is_empty()
+            && self.accounts_deployed.is_empty()
+            && self.revert_reasons.is_empty()
+    }
+
+    /// Total count of all events
+    pub fn total_count(&self) -> usize {
+        self.user_operations.len() + self.accounts_deployed.len() + self.revert_reasons.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flashblocks::test_logs::receipt_log;
+    use alloy_primitives::keccak256;
+
+    fn user_op_event(hash: B256, sender: Address, success: bool) -> UserOperationEvent {
+        UserOperationEvent {
+            userOpHash: hash,
+            sender,
+            paymaster: Address::ZERO,
+            nonce: U256::from(1),
+            success,
+            actualGasCost: U256::from(21_000_000_000u64),
+            actualGasUsed: U256::from(21_000),
+        }
+    }
+
+    #[test]
+    fn test_erc4337_signatures() {
+        assert_eq!(
+            UserOperationEvent::SIGNATURE_HASH,
+            keccak256(b"UserOperationEvent(bytes32,address,address,uint256,bool,uint256,uint256)")
+        );
+        assert_eq!(
+            AccountDeployed::SIGNATURE_HASH,
+            keccak256(b"AccountDeployed(bytes32,address,address,address)")
+        );
+        assert_eq!(
+            UserOperationRevertReason::SIGNATURE_HASH,
+            keccak256(b"UserOperationRevertReason(bytes32,address,uint256,bytes)")
+        );
+        assert_eq!(
+            BeforeExecution::SIGNATURE_HASH,
+            keccak256(b"BeforeExecution()")
+        );
+    }
+
+    #[test]
+    fn test_bundle_links_logs_to_user_ops() {
+        let (op1, alice) = (B256::repeat_byte(0x01), Address::repeat_byte(0xa1));
+        let (op2, bob) = (B256::repeat_byte(0x02), Address::repeat_byte(0xb0));
+        let paymaster = Address::repeat_byte(0x9a);
+        let token = Address::repeat_byte(0x70);
+        let token_log = ReceiptLog {
+            address: token,
+            topics: vec![B256::repeat_byte(0xee)],
+            data: Bytes::new(),
+        };
+
+        let logs = vec![
+            // Validation: bob's account is deployed, a paymaster deposit moves
+            receipt_log(
+                ENTRY_POINT_V07,
+                AccountDeployed {
+                    userOpHash: op2,
+                    sender: bob,
+                    factory: Address::repeat_byte(0xfa),
+                    paymaster,
+                }
+                .encode_log_data(),
+            ),
+            token_log.clone(),
+            receipt_log(ENTRY_POINT_V07, BeforeExecution {}.encode_log_data()),
+            // Alice's op succeeds after emitting two logs
+            token_log.clone(),
+            token_log.clone(),
+            receipt_log(
+                ENTRY_POINT_V07,
+                user_op_event(op1, alice, true).encode_log_data(),
+            ),
+            // Bob's op reverts
+            receipt_log(
+                ENTRY_POINT_V07,
+                UserOperationRevertReason {
+                    userOpHash: op2,
+                    sender: bob,
+                    nonce: U256::from(1),
+                    revertReason: Bytes::from_static(b"nope"),
+                }
+                .encode_log_data(),
+            ),
+            receipt_log(
+                ENTRY_POINT_V07,
+                UserOperationEvent {
+                    paymaster,
+                    ..user_op_event(op2, bob, false)
+                }
+                .encode_log_data(),
+            ),
+        ];
+
+        let alice_op = Some(UserOpRef {
+            user_op_hash: op1,
+            sender: alice,
+        });
+        let bob_op = Some(UserOpRef {
+            user_op_hash: op2,
+            sender: bob,
+        });
+        assert_eq!(
+            link_user_ops(&logs),
+            vec![
+                bob_op, None, None, alice_op, alice_op, alice_op, bob_op, bob_op
+            ]
+        );
+
+        let logs: Vec<_> = logs
+            .into_iter()
+            .enumerate()
+            .map(|(i, log)| ContextLog {
+                log,
+                context: EventContext {
+                    receipt_log_index: i as u64,
+                    ..Default::default()
+                },
+            })
+            .collect();
+        let updates = Erc4337Updates::extract_all(&logs);
+        assert_eq!(updates.total_count(), 4);
+
+        let ops = &updates.user_operations;
+        assert_eq!(ops.len(), 2);
+        assert_eq!(ops[0].version, EntryPointVersion::V07);
+        assert!(ops[0].success);
+        assert_eq!(ops[0].paymaster, None);
+        assert_eq!(ops[0].revert_reason, None);
+        assert_eq!(ops[1].sender, bob);
+        assert!(!ops[1].success);
+        assert_eq!(ops[1].paymaster, Some(paymaster));
+        assert_eq!(ops[1].revert_reason, Some(Bytes::from_static(b"nope")));
+
+        let deployed = &updates.accounts_deployed[0];
+        assert_eq!(
+            (deployed.sender, deployed.paymaster),
+            (bob, Some(paymaster))
+        );
+    }
+
+    #[test]
+    fn test_ignores_unknown_entry_points() {
+        let log = receipt_log(
+            Address::repeat_byte(0x43),
+            user_op_event(B256::ZERO, Address::ZERO, true).encode_log_data(),
+        );
+        assert!(ParsedUserOperation::try_from_log(&log, &EventContext::default()).is_none());
+        assert_eq!(link_user_ops(&[log]), vec![None]);
+
+        let log = receipt_log(
+            ENTRY_POINT_V06,
+            user_op_event(B256::ZERO, Address::ZERO, true).encode_log_data(),
+        );
+        let op = ParsedUserOperation::try_from_log(&log, &EventContext::default()).unwrap();
+        assert_eq!(op.version, EntryPointVersion::V06);
+        assert_eq!(serde_json::to_value(op.version).unwrap(), "v0.6");
+    }
+}
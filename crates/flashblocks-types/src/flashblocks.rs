@@ -1,13 +1,23 @@
-use alloy_primitives::{Address, B256, Bloom, BloomInput, Bytes, U256};
+use alloy_primitives::{Address, B256, Bloom, BloomInput, Bytes, U256, keccak256};
 use alloy_sol_types::SolEvent;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
 
 use crate::aave::{AaveEvents, AaveUserUpdates};
-use crate::chainlink::{AnswerUpdated, ParsedAnswerUpdated};
+use crate::chainlink::{
+    AggregatorConfirmed, AnswerUpdated, NewRound, NewTransmission, ParsedAggregatorConfirmed,
+    ParsedAnswerUpdated, ParsedNewRound, ParsedNewTransmission,
+};
+use crate::erc20::{Erc20Events, Erc20Updates};
+use crate::erc4337::{Erc4337Events, Erc4337Updates, UserOpRef, link_user_ops};
 use crate::morpho::{MorphoEvents, MorphoUpdates};
-use crate::univ3::{ParsedSwap, Swap};
+use crate::transactions::{DecodedTransaction, TransactionWithReceipt, TxDecodeError};
+use crate::univ2::{UniV2Events, UniV2Updates};
+use crate::univ3::{ParsedSwap, UniV3Events, UniV3Updates};
+use crate::univ4::{UniV4Events, UniV4Updates};
+use crate::weth::{WethEvents, WethUpdates};
 
 /// Log entry from receipt
 #[derive(Debug, Deserialize, Clone)]
@@ -17,6 +27,52 @@ pub struct ReceiptLog {
     pub topics: Vec<B256>,
 }
 
+/// Where a parsed event was emitted. Carried and serialized by every parsed event type.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct EventContext {
+    /// Hash of the transaction that emitted the log
+    pub tx_hash: B256,
+    /// Position of the transaction in this flashblock's `diff.transactions`,
+    /// if its receipt could be matched to one
+    pub tx_index: Option<u64>,
+    /// Sender of the transaction, recovered from its signature (or the `from`
+    /// of a deposit), if it was matched and decodes
+    pub from: Option<Address>,
+    /// Index of the log within its transaction receipt. This is not the
+    /// block-level `logIndex` of RPC logs: a flashblock only carries its own
+    /// receipts, so the log counts of earlier flashblocks are unknown.
+    pub receipt_log_index: u64,
+    /// Block number
+    pub block_number: u64,
+    /// Index of the flashblock within its payload
+    pub flashblock_index: u64,
+    /// Payload being built
+    pub payload_id: String,
+    /// The ERC-4337 user operation the log was emitted under, for bundle transactions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_op: Option<UserOpRef>,
+}
+
+impl EventContext {
+    /// Sort key matching the order `logs_with_context` yields logs in: by
+    /// transaction position, then by log within the receipt. Logs of receipts
+    /// with no matching transaction follow, by hash.
+    pub fn execution_order(&self) -> (u64, B256, u64) {
+        (
+            self.tx_index.unwrap_or(u64::MAX),
+            self.tx_hash,
+            self.receipt_log_index,
+        )
+    }
+}
+
+/// A receipt log together with its event context
+#[derive(Debug, Clone)]
+pub struct ContextLog {
+    pub log: ReceiptLog,
+    pub context: EventContext,
+}
+
 /// Inner receipt data (inside the transaction type wrapper)
 #[derive(Debug, Default, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -62,19 +118,108 @@ impl FlashblockReceipt {
         self.inner()
             .logs_bloom
             .as_ref()
-            .map(|bloom| bloom.contains_input(BloomInput::Hash(Swap::SIGNATURE_HASH)))
+            .map(|bloom| UniV3Events::from_bloom(bloom).may_have_swap)
             .unwrap_or(true) // If no bloom, assume it might have swaps
     }
 
+    /// Check if this receipt might contain UniV3 pool events using its bloom filter
+    pub fn may_have_univ3_events(&self) -> bool {
+        self.inner()
+            .logs_bloom
+            .as_ref()
+            .map(|bloom| UniV3Events::from_bloom(bloom).any())
+            .unwrap_or(true) // If no bloom, assume it might have events
+    }
+
+    /// Check if this receipt might contain UniV4 PoolManager events using its bloom filter
+    pub fn may_have_univ4_events(&self) -> bool {
+        self.inner()
+            .logs_bloom
+            .as_ref()
+            .map(|bloom| UniV4Events::from_bloom(bloom).any())
+            .unwrap_or(true) // If no bloom, assume it might have events
+    }
+
+    /// Check if this receipt might contain V2-style pool events using its bloom filter
+    pub fn may_have_univ2_events(&self) -> bool {
+        self.inner()
+            .logs_bloom
+            .as_ref()
+            .map(|bloom| UniV2Events::from_bloom(bloom).any())
+            .unwrap_or(true) // If no bloom, assume it might have events
+    }
+
+    /// Check if this receipt might contain ERC-20 events using its bloom filter
+    pub fn may_have_erc20_events(&self) -> bool {
+        self.inner()
+            .logs_bloom
+            .as_ref()
+            .map(|bloom| Erc20Events::from_bloom(bloom).any())
+            .unwrap_or(true) // If no bloom, assume it might have events
+    }
+
+    /// Check if this receipt might contain WETH Deposit or Withdrawal events using its bloom filter
+    pub fn may_have_weth_events(&self) -> bool {
+        self.inner()
+            .logs_bloom
+            .as_ref()
+            .map(|bloom| WethEvents::from_bloom(bloom).any())
+            .unwrap_or(true) // If no bloom, assume it might have events
+    }
+
+    /// Check if this receipt might contain ERC-4337 EntryPoint events using its bloom filter
+    pub fn may_have_erc4337_events(&self) -> bool {
+        self.inner()
+            .logs_bloom
+            .as_ref()
+            .map(|bloom| Erc4337Events::from_bloom(bloom).any())
+            .unwrap_or(true) // If no bloom, assume it might have events
+    }
+
     /// Check if this receipt might contain a Chainlink AnswerUpdated event using its bloom filter
     pub fn may_have_answer_updated(&self) -> bool {
         self.inner()
             .logs_bloom
             .as_ref()
-            .map(|bloom| bloom.contains_input(BloomInput::Hash(AnswerUpdated::SIGNATURE_HASH)))
+            .map(|bloom| {
+                bloom.contains_input(BloomInput::Raw(AnswerUpdated::SIGNATURE_HASH.as_slice()))
+            })
             .unwrap_or(true) // If no bloom, assume it might have updates
     }
 
+    /// Check if this receipt might contain a Chainlink NewRound event using its bloom filter
+    pub fn may_have_new_round(&self) -> bool {
+        self.inner()
+            .logs_bloom
+            .as_ref()
+            .map(|bloom| bloom.contains_input(BloomInput::Raw(NewRound::SIGNATURE_HASH.as_slice())))
+            .unwrap_or(true) // If no bloom, assume it might have rounds
+    }
+
+    /// Check if this receipt might contain a Chainlink OCR2 NewTransmission event using its bloom filter
+    pub fn may_have_new_transmission(&self) -> bool {
+        self.inner()
+            .logs_bloom
+            .as_ref()
+            .map(|bloom| {
+                bloom.contains_input(BloomInput::Raw(NewTransmission::SIGNATURE_HASH.as_slice()))
+            })
+            .unwrap_or(true) // If no bloom, assume it might have transmissions
+    }
+
+    /// Check if this receipt might contain a Chainlink proxy AggregatorConfirmed event using its bloom filter
+    pub fn may_have_aggregator_confirmed(&self) -> bool {
+        self.inner()
+            .logs_bloom
+            .as_ref()
+            .map(|bloom| {
+                bloom.contains_input(BloomInput::Raw(
+                    AggregatorConfirmed::SIGNATURE_HASH.as_slice(),
+                ))
+            })
+            .unwrap_or(true) // If no bloom, assume it might have rotations
+    }
+
     /// Check if this receipt might contain AAVE user events using its bloom filter
     pub fn may_have_aave_events(&self) -> bool {
         self.inner()
@@ -99,51 +244,63 @@ impl FlashblockReceipt {
 pub struct FlashblockMetadata {
     /// Transaction receipts keyed by tx hash
     pub receipts: HashMap<String, FlashblockReceipt>,
-    /// New account balances after this flashblock
-    pub new_account_balances: HashMap<String, String>,
+    /// New balances of the accounts touched by this flashblock
+    pub new_account_balances: HashMap<Address, U256>,
     /// Block number
     pub block_number: u64,
 }
 
+/// A receipt positioned in execution order
+#[derive(Debug, Clone, Copy)]
+pub struct OrderedReceipt<'a> {
+    /// Position of the transaction in `diff.transactions`, if present there
+    pub tx_index: Option<u64>,
+    pub tx_hash: B256,
+    /// Key of the receipt in `receipts`
+    pub key: &'a str,
+    pub receipt: &'a FlashblockReceipt,
+}
+
 impl FlashblockMetadata {
-    /// Extract all Swap events from receipts, using bloom filters to skip irrelevant receipts
-    pub fn extract_swaps(&self) -> Vec<ParsedSwap> {
-        self.receipts
-            .values()
-            .filter(|receipt| receipt.may_have_swap())
-            .flat_map(|receipt| ParsedSwap::extract_all(receipt.logs()))
-            .collect()
-    }
+    /// Receipts in execution order.
+    ///
+    /// `receipts` is keyed by hash and carries no order, so the order is rebuilt
+    /// from the flashblock's encoded transactions (a transaction's hash is the
+    /// keccak256 of its EIP-2718 encoding). Receipts with no matching transaction
+    /// follow, sorted by hash so the output is deterministic.
+    pub fn ordered_receipts(&self, transactions: &[Bytes]) -> Vec<OrderedReceipt<'_>> {
+        let mut ordered = Vec::with_capacity(self.receipts.len());
+        let mut matched = HashSet::with_capacity(transactions.len());
 
-    /// Extract all Chainlink AnswerUpdated events from receipts, using bloom filters to skip irrelevant receipts
-    pub fn extract_answer_updates(&self) -> Vec<ParsedAnswerUpdated> {
-        self.receipts
-            .values()
-            .filter(|receipt| receipt.may_have_answer_updated())
-            .flat_map(|receipt| ParsedAnswerUpdated::extract_all(receipt.logs()))
-            .collect()
-    }
+        for (tx_index, raw) in transactions.iter().enumerate() {
+            let tx_hash = keccak256(raw);
+            let key = tx_hash.to_string();
+            if let Some((stored_key, receipt)) = self.receipts.get_key_value(&key) {
+                ordered.push(OrderedReceipt {
+                    tx_index: Some(tx_index as u64),
+                    tx_hash,
+                    key: stored_key,
+                    receipt,
+                });
+                matched.insert(key);
+            }
+        }
 
-    /// Extract all AAVE user events from receipts, using bloom filters to skip irrelevant receipts
-    pub fn extract_aave_updates(&self) -> AaveUserUpdates {
-        let all_logs: Vec<_> = self
+        let mut unmatched: Vec<_> = self
             .receipts
-            .values()
-            .filter(|receipt| receipt.may_have_aave_events())
-            .flat_map(|receipt| receipt.logs().iter().cloned())
+            .iter()
+            .filter(|(key, _)| !matched.contains(*key))
+            .map(|(key, receipt)| OrderedReceipt {
+                tx_index: None,
+                tx_hash: key.parse().unwrap_or_default(),
+                key,
+                receipt,
+            })
             .collect();
-        AaveUserUpdates::extract_all(&all_logs)
-    }
+        unmatched.sort_by_key(|r| r.tx_hash);
+        ordered.extend(unmatched);
 
-    /// Extract all Morpho events from receipts, using bloom filters to skip irrelevant receipts
-    pub fn extract_morpho_updates(&self) -> MorphoUpdates {
-        let all_logs: Vec<_> = self
-            .receipts
-            .values()
-            .filter(|receipt| receipt.may_have_morpho_events())
-            .flat_map(|receipt| receipt.logs().iter().cloned())
-            .collect();
-        MorphoUpdates::extract_all(&all_logs)
+        ordered
     }
 }
 
@@ -171,6 +328,13 @@ pub struct ExecutionPayloadDiff {
     pub withdrawals_root: B256,
 }
 
+impl ExecutionPayloadDiff {
+    /// Decode the transactions in this diff, in block order
+    pub fn decode_transactions(&self) -> Vec<Result<DecodedTransaction, TxDecodeError>> {
+        DecodedTransaction::decode_all(&self.transactions)
+    }
+}
+
 impl std::fmt::Display for ExecutionPayloadDiff {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
@@ -189,50 +353,590 @@ impl std::fmt::Display for ExecutionPayloadDiff {
     }
 }
 
+/// Block-level fields fixed when a payload starts building.
+///
+/// Sent only with index 0 of each payload; every later flashblock of the
+/// same payload shares it.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ExecutionPayloadBase {
+    /// Parent beacon block root
+    pub parent_beacon_block_root: B256,
+    /// Parent block hash
+    pub parent_hash: B256,
+    /// Fee recipient (coinbase)
+    pub fee_recipient: Address,
+    /// Previous RANDAO value
+    pub prev_randao: B256,
+    /// Block number
+    #[serde(with = "alloy_serde::quantity")]
+    pub block_number: u64,
+    /// Block gas limit
+    #[serde(with = "alloy_serde::quantity")]
+    pub gas_limit: u64,
+    /// Block timestamp (seconds)
+    #[serde(with = "alloy_serde::quantity")]
+    pub timestamp: u64,
+    /// Extra data (holds the EIP-1559 parameters on OP Stack chains)
+    pub extra_data: Bytes,
+    /// Base fee per gas
+    pub base_fee_per_gas: U256,
+}
+
+/// A receipt's logs tagged with their event contexts
+#[derive(Debug, Clone)]
+struct ContextReceipt {
+    /// Key of the receipt in `FlashblockMetadata::receipts`
+    key: String,
+    logs: Vec<ContextLog>,
+}
+
 /// A minimal view of the Flashblock payload so we can print something structured.
-/// Most fields are left as `Value` so you can extend this as needed.
 #[derive(Debug, Deserialize)]
 pub struct Flashblock {
     pub payload_id: String,
     pub index: u64,
     #[serde(default)]
     pub metadata: Option<FlashblockMetadata>,
+    /// Payload base. Sent with index 0 only; the digestor carries it forward
+    /// to later flashblocks of the same payload.
     #[serde(default)]
-    pub base: Value,
+    pub base: Option<ExecutionPayloadBase>,
     #[serde(default)]
     pub diff: Option<ExecutionPayloadDiff>,
+    /// Receipts in execution order with their logs' contexts, built on first
+    /// use and shared by every extractor. Changes to `metadata` or `diff`
+    /// after that aren't seen.
+    #[serde(skip)]
+    context_receipts: OnceLock<Vec<ContextReceipt>>,
 }
 
 impl Flashblock {
-    /// Extract all Swap events from this flashblock's metadata
+    /// Block timestamp from the payload base, if known
+    pub fn timestamp(&self) -> Option<u64> {
+        self.base.as_ref().map(|b| b.timestamp)
+    }
+
+    /// Base fee per gas from the payload base, if known
+    pub fn base_fee_per_gas(&self) -> Option<U256> {
+        self.base.as_ref().map(|b| b.base_fee_per_gas)
+    }
+
+    /// Decode this flashblock's transactions in block order and join each with
+    /// its receipt by hash. Transactions that fail to decode are returned as errors.
+    pub fn transactions_with_receipts(
+        &self,
+    ) -> Vec<Result<TransactionWithReceipt<'_>, TxDecodeError>> {
+        let Some(diff) = &self.diff else {
+            return Vec::new();
+        };
+        diff.decode_transactions()
+            .into_iter()
+            .map(|decoded| {
+                decoded.map(|transaction| TransactionWithReceipt {
+                    receipt: self
+                        .metadata
+                        .as_ref()
+                        .and_then(|m| m.receipts.get(&transaction.hash.to_string())),
+                    transaction,
+                })
+            })
+            .collect()
+    }
+
+    /// Order the receipts and tag their logs with event contexts. Matching
+    /// receipts to transactions hashes every transaction and recovering
+    /// senders runs ecrecover for each one with logs, so this runs once per
+    /// flashblock.
+    fn context_receipts(&self) -> &[ContextReceipt] {
+        self.context_receipts.get_or_init(|| {
+            let Some(metadata) = &self.metadata else {
+                return Vec::new();
+            };
+            let transactions = self
+                .diff
+                .as_ref()
+                .map(|d| d.transactions.as_slice())
+                .unwrap_or_default();
+
+            metadata
+                .ordered_receipts(transactions)
+                .into_iter()
+                .map(|ordered| {
+                    let logs = ordered.receipt.logs();
+                    let from = ordered
+                        .tx_index
+                        .filter(|_| !logs.is_empty())
+                        .and_then(|i| DecodedTransaction::decode(&transactions[i as usize]).ok())
+                        .map(|tx| tx.from);
+                    let user_ops = if ordered.receipt.may_have_erc4337_events() {
+                        link_user_ops(logs)
+                    } else {
+                        Vec::new()
+                    };
+                    ContextReceipt {
+                        key: ordered.key.to_string(),
+                        logs: logs
+                            .iter()
+                            .enumerate()
+                            .map(|(log_index, log)| ContextLog {
+                                log: log.clone(),
+                                context: EventContext {
+                                    tx_hash: ordered.tx_hash,
+                                    tx_index: ordered.tx_index,
+                                    from,
+                                    receipt_log_index: log_index as u64,
+                                    block_number: metadata.block_number,
+                                    flashblock_index: self.index,
+                                    payload_id: self.payload_id.clone(),
+                                    user_op: user_ops.get(log_index).copied().flatten(),
+                                },
+                            })
+                            .collect(),
+                    }
+                })
+                .collect()
+        })
+    }
+
+    /// Collect the logs of receipts passing `filter` in (tx position, log index)
+    /// order, each tagged with its event context
+    pub fn logs_with_context(
+        &self,
+        filter: impl Fn(&FlashblockReceipt) -> bool,
+    ) -> Vec<ContextLog> {
+        let Some(metadata) = &self.metadata else {
+            return Vec::new();
+        };
+        self.context_receipts()
+            .iter()
+            .filter(|r| metadata.receipts.get(&r.key).is_some_and(&filter))
+            .flat_map(|r| r.logs.iter().cloned())
+            .collect()
+    }
+
+    /// Extract all Swap events, using bloom filters to skip irrelevant receipts
     pub fn extract_swaps(&self) -> Vec<ParsedSwap> {
-        self.metadata
-            .as_ref()
-            .map(|m| m.extract_swaps())
-            .unwrap_or_default()
+        ParsedSwap::extract_all(&self.logs_with_context(FlashblockReceipt::may_have_swap))
+    }
+
+    /// Extract all UniV3 pool events, using bloom filters to skip irrelevant receipts
+    pub fn extract_univ3_updates(&self) -> UniV3Updates {
+        UniV3Updates::extract_all(&self.logs_with_context(FlashblockReceipt::may_have_univ3_events))
+    }
+
+    /// Extract all UniV4 PoolManager events, using bloom filters to skip irrelevant receipts
+    pub fn extract_univ4_updates(&self) -> UniV4Updates {
+        UniV4Updates::extract_all(&self.logs_with_context(FlashblockReceipt::may_have_univ4_events))
+    }
+
+    /// Extract all V2-style (Uniswap V2 fork and Aerodrome) pool events, using bloom filters to skip irrelevant receipts
+    pub fn extract_univ2_updates(&self) -> UniV2Updates {
+        UniV2Updates::extract_all(&self.logs_with_context(FlashblockReceipt::may_have_univ2_events))
+    }
+
+    /// Extract all ERC-20 Transfer and Approval events, using bloom filters to skip irrelevant receipts
+    pub fn extract_erc20_updates(&self) -> Erc20Updates {
+        Erc20Updates::extract_all(&self.logs_with_context(FlashblockReceipt::may_have_erc20_events))
     }
 
-    /// Extract all Chainlink AnswerUpdated events from this flashblock's metadata
+    /// Extract all WETH Deposit and Withdrawal events, using bloom filters to skip irrelevant receipts
+    pub fn extract_weth_updates(&self) -> WethUpdates {
+        WethUpdates::extract_all(&self.logs_with_context(FlashblockReceipt::may_have_weth_events))
+    }
+
+    /// Extract all ERC-4337 EntryPoint events, using bloom filters to skip irrelevant receipts
+    pub fn extract_erc4337_updates(&self) -> Erc4337Updates {
+        Erc4337Updates::extract_all(
+            &self.logs_with_context(FlashblockReceipt::may_have_erc4337_events),
+        )
+    }
+
+    /// Extract all Chainlink AnswerUpdated events, using bloom filters to skip irrelevant receipts
     pub fn extract_answer_updates(&self) -> Vec<ParsedAnswerUpdated> {
-        self.metadata
-            .as_ref()
-            .map(|m| m.extract_answer_updates())
-            .unwrap_or_default()
+        ParsedAnswerUpdated::extract_all(
+            &self.logs_with_context(FlashblockReceipt::may_have_answer_updated),
+        )
+    }
+
+    /// Extract all Chainlink NewRound events, using bloom filters to skip irrelevant receipts
+    pub fn extract_new_rounds(&self) -> Vec<ParsedNewRound> {
+        ParsedNewRound::extract_all(&self.logs_with_context(FlashblockReceipt::may_have_new_round))
+    }
+
+    /// Extract all Chainlink OCR2 NewTransmission events, using bloom filters to skip irrelevant receipts
+    pub fn extract_new_transmissions(&self) -> Vec<ParsedNewTransmission> {
+        ParsedNewTransmission::extract_all(
+            &self.logs_with_context(FlashblockReceipt::may_have_new_transmission),
+        )
     }
 
-    /// Extract all AAVE user events from this flashblock's metadata
+    /// Extract all Chainlink proxy AggregatorConfirmed events, using bloom filters to skip irrelevant receipts
+    pub fn extract_aggregator_confirmations(&self) -> Vec<ParsedAggregatorConfirmed> {
+        ParsedAggregatorConfirmed::extract_all(
+            &self.logs_with_context(FlashblockReceipt::may_have_aggregator_confirmed),
+        )
+    }
+
+    /// Extract all AAVE user events, using bloom filters to skip irrelevant receipts
     pub fn extract_aave_updates(&self) -> AaveUserUpdates {
-        self.metadata
-            .as_ref()
-            .map(|m| m.extract_aave_updates())
-            .unwrap_or_default()
+        AaveUserUpdates::extract_all(
+            &self.logs_with_context(FlashblockReceipt::may_have_aave_events),
+        )
     }
 
-    /// Extract all Morpho events from this flashblock's metadata
+    /// Extract all Morpho events, using bloom filters to skip irrelevant receipts
     pub fn extract_morpho_updates(&self) -> MorphoUpdates {
-        self.metadata
-            .as_ref()
-            .map(|m| m.extract_morpho_updates())
-            .unwrap_or_default()
+        MorphoUpdates::extract_all(
+            &self.logs_with_context(FlashblockReceipt::may_have_morpho_events),
+        )
+    }
+}
+
+/// Log builders shared by the event parsers' tests
+#[cfg(test)]
+pub(crate) mod test_logs {
+    use alloy_primitives::{Address, LogData};
+
+    use super::{ContextLog, EventContext, ReceiptLog};
+
+    /// A receipt log of `data` emitted by `address`
+    pub(crate) fn receipt_log(address: Address, data: LogData) -> ReceiptLog {
+        ReceiptLog {
+            address,
+            topics: data.topics().to_vec(),
+            data: data.data,
+        }
+    }
+
+    /// A log of `data` emitted by `address`, with a default event context
+    pub(crate) fn context_log(address: Address, data: LogData) -> ContextLog {
+        ContextLog {
+            log: receipt_log(address, data),
+            context: EventContext::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{address, b256};
+
+    const FLASHBLOCK_INDEX_0: &str = include_str!("../testdata/flashblock_index0.json");
+    const FLASHBLOCK_INDEX_1: &str = include_str!("../testdata/flashblock_index1.json");
+
+    #[test]
+    fn test_base_round_trip() {
+        let raw: Value = serde_json::from_str(FLASHBLOCK_INDEX_0).unwrap();
+        let base: ExecutionPayloadBase = serde_json::from_value(raw["base"].clone()).unwrap();
+
+        assert_eq!(base.block_number, 30_000_000);
+        assert_eq!(base.gas_limit, 60_000_000);
+        assert_eq!(base.timestamp, 0x671f5a2c);
+        assert_eq!(base.base_fee_per_gas, U256::from(252));
+        assert_eq!(
+            base.fee_recipient,
+            "0x4200000000000000000000000000000000000011"
+                .parse::<Address>()
+                .unwrap()
+        );
+
+        assert_eq!(serde_json::to_value(&base).unwrap(), raw["base"]);
+    }
+
+    #[test]
+    fn test_flashblock_base_only_on_index_0() {
+        let fb0: Flashblock = serde_json::from_str(FLASHBLOCK_INDEX_0).unwrap();
+        assert_eq!(fb0.index, 0);
+        assert_eq!(fb0.timestamp(), Some(0x671f5a2c));
+        assert_eq!(fb0.base_fee_per_gas(), Some(U256::from(252)));
+        assert_eq!(
+            fb0.base.as_ref().map(|b| b.block_number),
+            fb0.metadata.as_ref().map(|m| m.block_number)
+        );
+
+        let fb1: Flashblock = serde_json::from_str(FLASHBLOCK_INDEX_1).unwrap();
+        assert_eq!(fb1.index, 1);
+        assert!(fb1.base.is_none());
+        assert_eq!(fb1.payload_id, fb0.payload_id);
+    }
+
+    #[test]
+    fn test_fixture_deposit_decoded() {
+        use crate::transactions::TxType;
+
+        let fb0: Flashblock = serde_json::from_str(FLASHBLOCK_INDEX_0).unwrap();
+        let joined = fb0.transactions_with_receipts();
+        assert_eq!(joined.len(), 1);
+
+        // Every block opens with the L1 attributes deposit to the L1Block predeploy
+        let first = joined[0].as_ref().unwrap();
+        assert_eq!(first.transaction.tx_type, TxType::Deposit);
+        assert_eq!(
+            first.transaction.from,
+            address!("deaddeaddeaddeaddeaddeaddeaddeaddead0001")
+        );
+        assert_eq!(
+            first.transaction.to,
+            Some(address!("4200000000000000000000000000000000000015"))
+        );
+        assert_eq!(first.transaction.input[..4], [0x44, 0x0a, 0x5e, 0x20]);
+        assert_eq!(
+            first.transaction.hash,
+            b256!("85a6de1d0e72bb35870d8bab41e50236dc71dec38a0b2c811ff69e13e972d096")
+        );
+        assert!(matches!(first.receipt, Some(FlashblockReceipt::Deposit(_))));
+    }
+
+    #[test]
+    fn test_fixture_transactions_match_receipts() {
+        use crate::transactions::TxType;
+
+        let fb: Flashblock = serde_json::from_str(FLASHBLOCK_INDEX_1).unwrap();
+        let joined: Vec<_> = fb
+            .transactions_with_receipts()
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        let expected = [
+            (
+                b256!("4af621289c2339f4a0955af6035940e6a3575746844b4e74780f4f5cdc239745"),
+                address!("4200000000000000000000000000000000000006"),
+                41,
+            ),
+            (
+                b256!("f67d39967f298485908364b1c4e3d888724dac82d0ec240192d6966355b04ac5"),
+                address!("833589fCD6eDb6E08f4c7C32D4f71b54bdA02913"),
+                42,
+            ),
+        ];
+        assert_eq!(joined.len(), expected.len());
+
+        let mut block_bloom = Bloom::default();
+        for (joined, (hash, to, nonce)) in joined.iter().zip(expected) {
+            let tx = &joined.transaction;
+            assert_eq!(tx.hash, hash);
+            assert_eq!(tx.tx_type, TxType::Eip1559);
+            assert_eq!(
+                tx.from,
+                address!("f39Fd6e51aad88F6F4ce6aB8827279cffFb92266")
+            );
+            assert_eq!(tx.to, Some(to));
+            assert_eq!(tx.nonce, Some(nonce));
+
+            // Each receipt's bloom covers its own logs
+            let inner = joined.receipt.unwrap().inner();
+            let bloom = inner.logs_bloom.unwrap();
+            assert_ne!(bloom, Bloom::default());
+            for log in &inner.logs {
+                assert!(bloom.contains_input(BloomInput::Raw(log.address.as_slice())));
+                for topic in &log.topics {
+                    assert!(bloom.contains_input(BloomInput::Raw(topic.as_slice())));
+                }
+            }
+            block_bloom.accrue_bloom(&bloom);
+        }
+        assert_eq!(fb.diff.as_ref().unwrap().logs_bloom, block_bloom);
+    }
+
+    #[test]
+    fn test_fixture_events_extracted() {
+        let sender = address!("f39Fd6e51aad88F6F4ce6aB8827279cffFb92266");
+        let fb: Flashblock = serde_json::from_str(FLASHBLOCK_INDEX_1).unwrap();
+
+        let weth = fb.extract_weth_updates();
+        assert_eq!(weth.deposits.len(), 1);
+        assert!(weth.withdrawals.is_empty());
+        assert_eq!(weth.deposits[0].account, sender);
+        assert_eq!(
+            weth.deposits[0].amount,
+            U256::from(10_000_000_000_000_000u64)
+        );
+        assert_eq!(weth.deposits[0].context.tx_index, Some(0));
+        assert_eq!(weth.deposits[0].context.from, Some(sender));
+
+        let erc20 = fb.extract_erc20_updates();
+        let transfer = erc20
+            .transfers
+            .iter()
+            .find(|t| t.token == address!("833589fCD6eDb6E08f4c7C32D4f71b54bdA02913"))
+            .unwrap();
+        assert_eq!(transfer.from, sender);
+        assert_eq!(
+            transfer.to,
+            address!("70997970C51812dc3A010C7d01b50e8d17dc79C8")
+        );
+        assert_eq!(transfer.value, U256::from(2_500_000));
+        assert_eq!(transfer.context.tx_index, Some(1));
+        assert_eq!(transfer.context.from, Some(sender));
+
+        let json = serde_json::to_value(transfer).unwrap();
+        assert_eq!(
+            json["context"]["from"],
+            serde_json::to_value(sender).unwrap()
+        );
+        assert_eq!(transfer.context.block_number, 30_000_000);
+    }
+
+    #[test]
+    fn test_transactions_joined_with_receipts() {
+        use crate::transactions::TxType;
+        use alloy_eips::eip2718::Encodable2718;
+        use alloy_primitives::TxKind;
+        use op_alloy_consensus::TxDeposit;
+
+        let deposit = TxDeposit {
+            source_hash: B256::repeat_byte(0x01),
+            from: Address::repeat_byte(0xde),
+            to: TxKind::Call(Address::repeat_byte(0x42)),
+            gas_limit: 1_000_000,
+            ..Default::default()
+        };
+        let orphan = TxDeposit {
+            source_hash: B256::repeat_byte(0x02),
+            ..deposit.clone()
+        };
+
+        let mut fb: Flashblock = serde_json::from_str(FLASHBLOCK_INDEX_1).unwrap();
+        let diff = fb.diff.as_mut().unwrap();
+        diff.transactions = vec![
+            deposit.encoded_2718().into(),
+            orphan.encoded_2718().into(),
+            Bytes::from_static(&[0x02, 0x01]),
+        ];
+        let receipt: FlashblockReceipt =
+            serde_json::from_str(r#"{"Deposit":{"logs":[],"status":"0x1"}}"#).unwrap();
+        fb.metadata
+            .as_mut()
+            .unwrap()
+            .receipts
+            .insert(deposit.tx_hash().to_string(), receipt);
+
+        let joined = fb.transactions_with_receipts();
+        assert_eq!(joined.len(), 3);
+
+        let first = joined[0].as_ref().unwrap();
+        assert_eq!(first.transaction.hash, deposit.tx_hash());
+        assert_eq!(first.transaction.tx_type, TxType::Deposit);
+        assert_eq!(first.transaction.from, deposit.from);
+        assert!(matches!(first.receipt, Some(FlashblockReceipt::Deposit(_))));
+
+        let second = joined[1].as_ref().unwrap();
+        assert_eq!(second.transaction.hash, orphan.tx_hash());
+        assert!(second.receipt.is_none());
+
+        assert!(joined[2].is_err());
+    }
+
+    fn answer_updated_log(feed: Address, round_id: u64) -> ReceiptLog {
+        ReceiptLog {
+            address: feed,
+            topics: vec![
+                AnswerUpdated::SIGNATURE_HASH,
+                B256::from(U256::from(3000)),
+                B256::from(U256::from(round_id)),
+            ],
+            data: U256::from(1_700_000_000u64).to_be_bytes::<32>().into(),
+        }
+    }
+
+    fn receipt(logs: Vec<ReceiptLog>) -> FlashblockReceipt {
+        FlashblockReceipt::Eip1559(ReceiptInner {
+            logs,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_extracted_events_carry_context() {
+        let tx_hash = B256::repeat_byte(0xab);
+        let feed = Address::repeat_byte(0xfe);
+        let unrelated = ReceiptLog {
+            address: Address::ZERO,
+            topics: vec![B256::ZERO],
+            data: Bytes::new(),
+        };
+
+        let mut fb: Flashblock = serde_json::from_str(FLASHBLOCK_INDEX_1).unwrap();
+        let metadata = fb.metadata.as_mut().unwrap();
+        let block_number = metadata.block_number;
+        metadata.receipts = HashMap::from([(
+            tx_hash.to_string(),
+            receipt(vec![unrelated, answer_updated_log(feed, 7)]),
+        )]);
+
+        let updates = fb.extract_answer_updates();
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].feed, feed);
+        assert_eq!(
+            updates[0].context,
+            EventContext {
+                tx_hash,
+                tx_index: None,
+                from: None,
+                receipt_log_index: 1,
+                block_number,
+                flashblock_index: 1,
+                payload_id: fb.payload_id.clone(),
+                user_op: None,
+            }
+        );
+
+        let json = serde_json::to_value(&updates[0]).unwrap();
+        assert_eq!(json["context"]["tx_hash"], tx_hash.to_string());
+        assert_eq!(json["context"]["receipt_log_index"], 1);
+        assert!(json["context"]["from"].is_null());
+        assert!(json["context"].get("user_op").is_none());
+    }
+
+    #[test]
+    fn test_extracted_events_in_execution_order() {
+        let feed = Address::repeat_byte(0xfe);
+        let transactions: Vec<Bytes> = (0u8..8).map(|i| Bytes::from(vec![0x02, i])).collect();
+
+        let mut fb: Flashblock = serde_json::from_str(FLASHBLOCK_INDEX_1).unwrap();
+        fb.diff.as_mut().unwrap().transactions = transactions.clone();
+        let receipts = &mut fb.metadata.as_mut().unwrap().receipts;
+        receipts.clear();
+        // Insert in reverse so hash map order can't line up by accident
+        for (i, raw) in transactions.iter().enumerate().rev() {
+            let round = 10 * i as u64;
+            receipts.insert(
+                keccak256(raw).to_string(),
+                receipt(vec![
+                    answer_updated_log(feed, round),
+                    answer_updated_log(feed, round + 1),
+                ]),
+            );
+        }
+        // A receipt whose transaction isn't in the diff sorts last
+        receipts.insert(
+            B256::ZERO.to_string(),
+            receipt(vec![answer_updated_log(feed, 1000)]),
+        );
+
+        let updates = fb.extract_answer_updates();
+        let order: Vec<_> = updates
+            .iter()
+            .map(|u| {
+                (
+                    u.context.tx_index,
+                    u.context.receipt_log_index,
+                    u.round_id.to::<u64>(),
+                )
+            })
+            .collect();
+
+        let mut expected: Vec<_> = (0..8u64)
+            .flat_map(|i| [(Some(i), 0, 10 * i), (Some(i), 1, 10 * i + 1)])
+            .collect();
+        expected.push((None, 0, 1000));
+        assert_eq!(order, expected);
+
+        // Later extractors reuse the receipts ordered by the first
+        let cached = fb.context_receipts().as_ptr();
+        assert_eq!(fb.extract_answer_updates().len(), updates.len());
+        assert!(fb.extract_new_rounds().is_empty());
+        assert_eq!(fb.context_receipts().as_ptr(), cached);
     }
 }
@@ -1,5 +1,15 @@
 pub mod aave;
+pub mod aave_positions;
 pub mod chainlink;
+pub mod dex;
+pub mod erc20;
+pub mod erc4337;
 pub mod flashblocks;
 pub mod morpho;
+pub mod price;
+pub mod tokens;
+pub mod transactions;
+pub mod univ2;
 pub mod univ3;
+pub mod univ4;
+pub mod weth;
@@ -1,8 +1,25 @@
-use alloy_primitives::{Address, B256, Bloom, BloomInput, U256};
-use alloy_sol_types::{SolEvent, sol};
-use serde::Serialize;
+//! Morpho Blue lending events.
+//!
+//! Every event but `CreateMarket` identifies its market only by id, the hash
+//! of the market's params. [`MorphoMarketRegistry`] learns the params from
+//! `CreateMarket` and from a snapshot of older markets, and attaches them to
+//! the other events as `market`.
+//!
+//! Positions are held as shares, whose value in assets follows the market's
+//! totals. [`MorphoTotalsCache`] tracks the totals of markets created while
+//! running or seeded from the snapshot, and values the events' shares.
 
-use crate::flashblocks::ReceiptLog;
+use std::collections::HashMap;
+use std::path::Path;
+
+use alloy_primitives::{Address, B256, Bloom, BloomInput, U256, address, keccak256, uint};
+use alloy_sol_types::{SolEvent, SolValue, sol};
+use serde::{Deserialize, Serialize};
+
+use crate::flashblocks::{ContextLog, EventContext, ReceiptLog};
+
+/// Morpho Blue on Base, at the same address as on Ethereum
+pub const MORPHO_BLUE: Address = address!("BBBBBbbBBb9cC5e90e3b3Af64bdAF62C37EEFFCb");
 
 // Morpho Blue events
 sol! {
@@ -87,8 +104,49 @@ sol! {
         bytes32 indexed id,
         MarketParams marketParams
     );
+
+    /// Emitted when interest accrues on a market's borrows
+    event AccrueInterest(
+        bytes32 indexed id,
+        uint256 prevBorrowRate,
+        uint256 interest,
+        uint256 feeShares
+    );
+
+    /// Emitted when a flash loan is taken
+    event FlashLoan(
+        address indexed caller,
+        address indexed token,
+        uint256 assets
+    );
+
+    /// Emitted when a market's fee is set
+    event SetFee(
+        bytes32 indexed id,
+        uint256 newFee
+    );
+
+    /// Emitted when an account grants or revokes another's right to manage its positions
+    event SetAuthorization(
+        address indexed caller,
+        address indexed authorizer,
+        address indexed authorized,
+        bool newIsAuthorized
+    );
+
+    /// Emitted when a signed authorization's nonce is used
+    event IncrementNonce(
+        address indexed caller,
+        address indexed authorizer,
+        uint256 usedNonce
+    );
 }
 
+/// Virtual shares added to a market's total shares to price them
+const VIRTUAL_SHARES: U256 = uint!(1_000_000_U256);
+/// Virtual assets added to a market's total assets to price its shares
+const VIRTUAL_ASSETS: U256 = uint!(1_U256);
+
 /// Detected Morpho events based on bloom filter
 #[derive(Debug, Default)]
 pub struct MorphoEvents {
@@ -100,6 +158,11 @@ pub struct MorphoEvents {
     pub may_have_withdraw_collateral: bool,
     pub may_have_liquidation: bool,
     pub may_have_create_market: bool,
+    pub may_have_accrue_interest: bool,
+    pub may_have_flash_loan: bool,
+    pub may_have_set_fee: bool,
+    pub may_have_set_authorization: bool,
+    pub may_have_increment_nonce: bool,
 }
 
 impl MorphoEvents {
@@ -107,17 +170,32 @@ impl MorphoEvents {
     /// Note: Bloom filters can have false positives but no false negatives.
     pub fn from_bloom(bloom: &Bloom) -> Self {
         Self {
-            may_have_supply: bloom.contains_input(BloomInput::Hash(Supply::SIGNATURE_HASH)),
-            may_have_withdraw: bloom.contains_input(BloomInput::Hash(Withdraw::SIGNATURE_HASH)),
-            may_have_borrow: bloom.contains_input(BloomInput::Hash(Borrow::SIGNATURE_HASH)),
-            may_have_repay: bloom.contains_input(BloomInput::Hash(Repay::SIGNATURE_HASH)),
+            may_have_supply: bloom
+                .contains_input(BloomInput::Raw(Supply::SIGNATURE_HASH.as_slice())),
+            may_have_withdraw: bloom
+                .contains_input(BloomInput::Raw(Withdraw::SIGNATURE_HASH.as_slice())),
+            may_have_borrow: bloom
+                .contains_input(BloomInput::Raw(Borrow::SIGNATURE_HASH.as_slice())),
+            may_have_repay: bloom.contains_input(BloomInput::Raw(Repay::SIGNATURE_HASH.as_slice())),
             may_have_supply_collateral: bloom
-                .contains_input(BloomInput::Hash(SupplyCollateral::SIGNATURE_HASH)),
-            may_have_withdraw_collateral: bloom
-                .contains_input(BloomInput::Hash(WithdrawCollateral::SIGNATURE_HASH)),
-            may_have_liquidation: bloom.contains_input(BloomInput::Hash(Liquidate::SIGNATURE_HASH)),
+                .contains_input(BloomInput::Raw(SupplyCollateral::SIGNATURE_HASH.as_slice())),
+            may_have_withdraw_collateral: bloom.contains_input(BloomInput::Raw(
+                WithdrawCollateral::SIGNATURE_HASH.as_slice(),
+            )),
+            may_have_liquidation: bloom
+                .contains_input(BloomInput::Raw(Liquidate::SIGNATURE_HASH.as_slice())),
             may_have_create_market: bloom
-                .contains_input(BloomInput::Hash(CreateMarket::SIGNATURE_HASH)),
+                .contains_input(BloomInput::Raw(CreateMarket::SIGNATURE_HASH.as_slice())),
+            may_have_accrue_interest: bloom
+                .contains_input(BloomInput::Raw(AccrueInterest::SIGNATURE_HASH.as_slice())),
+            may_have_flash_loan: bloom
+                .contains_input(BloomInput::Raw(FlashLoan::SIGNATURE_HASH.as_slice())),
+            may_have_set_fee: bloom
+                .contains_input(BloomInput::Raw(SetFee::SIGNATURE_HASH.as_slice())),
+            may_have_set_authorization: bloom
+                .contains_input(BloomInput::Raw(SetAuthorization::SIGNATURE_HASH.as_slice())),
+            may_have_increment_nonce: bloom
+                .contains_input(BloomInput::Raw(IncrementNonce::SIGNATURE_HASH.as_slice())),
         }
     }
 
@@ -131,16 +209,57 @@ impl MorphoEvents {
             || self.may_have_withdraw_collateral
             || self.may_have_liquidation
             || self.may_have_create_market
+            || self.may_have_accrue_interest
+            || self.may_have_flash_loan
+            || self.may_have_set_fee
+            || self.may_have_set_authorization
+            || self.may_have_increment_nonce
+    }
+}
+
+/// The params that define a Morpho Blue market
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MorphoMarketParams {
+    /// The token lent and borrowed
+    pub loan_token: Address,
+    /// The token borrowers post as collateral
+    pub collateral_token: Address,
+    /// The oracle pricing collateral in the loan token
+    pub oracle: Address,
+    /// The interest rate model
+    pub irm: Address,
+    /// Liquidation loan-to-value ratio, scaled by 1e18
+    pub lltv: U256,
+}
+
+impl MorphoMarketParams {
+    /// The market id: the keccak256 of the ABI-encoded params
+    pub fn id(&self) -> B256 {
+        keccak256(
+            MarketParams {
+                loanToken: self.loan_token,
+                collateralToken: self.collateral_token,
+                oracle: self.oracle,
+                irm: self.irm,
+                lltv: self.lltv,
+            }
+            .abi_encode(),
+        )
     }
 }
 
 /// Parsed Morpho Supply event
 #[derive(Debug, Clone, Serialize)]
 pub struct ParsedMorphoSupply {
+    /// Where the event was emitted
+    pub context: EventContext,
     /// Address of the Morpho contract
     pub morpho: Address,
     /// Market identifier
     pub market_id: B256,
+    /// The market's params, if its CreateMarket or a snapshot entry has been seen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market: Option<MorphoMarketParams>,
     /// The caller initiating the supply
     pub caller: Address,
     /// The beneficiary of the supply
@@ -149,11 +268,14 @@ pub struct ParsedMorphoSupply {
     pub assets: U256,
     /// Amount of shares minted
     pub shares: U256,
+    /// The shares valued at the market's totals before the event, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shares_in_assets: Option<U256>,
 }
 
 impl ParsedMorphoSupply {
     /// Try to parse a Supply event from a log entry
-    pub fn try_from_log(log: &ReceiptLog) -> Option<Self> {
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
         if log.topics.len() != 4 {
             return None;
         }
@@ -165,28 +287,38 @@ impl ParsedMorphoSupply {
         let decoded = Supply::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
 
         Some(Self {
+            context: context.clone(),
             morpho: log.address,
             market_id: B256::from(decoded.id),
+            market: None,
             caller: decoded.caller,
             on_behalf_of: decoded.onBehalf,
             assets: decoded.assets,
             shares: decoded.shares,
+            shares_in_assets: None,
         })
     }
 
     /// Extract all Supply events from a slice of logs
-    pub fn extract_all(logs: &[ReceiptLog]) -> Vec<Self> {
-        logs.iter().filter_map(Self::try_from_log).collect()
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
     }
 }
 
 /// Parsed Morpho Withdraw event
 #[derive(Debug, Clone, Serialize)]
 pub struct ParsedMorphoWithdraw {
+    /// Where the event was emitted
+    pub context: EventContext,
     /// Address of the Morpho contract
     pub morpho: Address,
     /// Market identifier
     pub market_id: B256,
+    /// The market's params, if its CreateMarket or a snapshot entry has been seen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market: Option<MorphoMarketParams>,
     /// The caller initiating the withdrawal
     pub caller: Address,
     /// The owner of the position
@@ -197,11 +329,14 @@ pub struct ParsedMorphoWithdraw {
     pub assets: U256,
     /// Amount of shares burned
     pub shares: U256,
+    /// The shares valued at the market's totals before the event, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shares_in_assets: Option<U256>,
 }
 
 impl ParsedMorphoWithdraw {
     /// Try to parse a Withdraw event from a log entry
-    pub fn try_from_log(log: &ReceiptLog) -> Option<Self> {
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
         if log.topics.len() != 4 {
             return None;
         }
@@ -213,29 +348,39 @@ impl ParsedMorphoWithdraw {
         let decoded = Withdraw::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
 
         Some(Self {
+            context: context.clone(),
             morpho: log.address,
             market_id: B256::from(decoded.id),
+            market: None,
             caller: decoded.caller,
             on_behalf_of: decoded.onBehalf,
             receiver: decoded.receiver,
             assets: decoded.assets,
             shares: decoded.shares,
+            shares_in_assets: None,
         })
     }
 
     /// Extract all Withdraw events from a slice of logs
-    pub fn extract_all(logs: &[ReceiptLog]) -> Vec<Self> {
-        logs.iter().filter_map(Self::try_from_log).collect()
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
     }
 }
 
 /// Parsed Morpho Borrow event
 #[derive(Debug, Clone, Serialize)]
 pub struct ParsedMorphoBorrow {
+    /// Where the event was emitted
+    pub context: EventContext,
     /// Address of the Morpho contract
     pub morpho: Address,
     /// Market identifier
     pub market_id: B256,
+    /// The market's params, if its CreateMarket or a snapshot entry has been seen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market: Option<MorphoMarketParams>,
     /// The caller initiating the borrow
     pub caller: Address,
     /// The owner of the position
@@ -246,11 +391,14 @@ pub struct ParsedMorphoBorrow {
     pub assets: U256,
     /// Amount of shares minted (debt)
     pub shares: U256,
+    /// The shares valued at the market's totals before the event, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shares_in_assets: Option<U256>,
 }
 
 impl ParsedMorphoBorrow {
     /// Try to parse a Borrow event from a log entry
-    pub fn try_from_log(log: &ReceiptLog) -> Option<Self> {
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
         if log.topics.len() != 4 {
             return None;
         }
@@ -262,29 +410,39 @@ impl ParsedMorphoBorrow {
         let decoded = Borrow::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
 
         Some(Self {
+            context: context.clone(),
             morpho: log.address,
             market_id: B256::from(decoded.id),
+            market: None,
             caller: decoded.caller,
             on_behalf_of: decoded.onBehalf,
             receiver: decoded.receiver,
             assets: decoded.assets,
             shares: decoded.shares,
+            shares_in_assets: None,
         })
     }
 
     /// Extract all Borrow events from a slice of logs
-    pub fn extract_all(logs: &[ReceiptLog]) -> Vec<Self> {
-        logs.iter().filter_map(Self::try_from_log).collect()
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
     }
 }
 
 /// Parsed Morpho Repay event
 #[derive(Debug, Clone, Serialize)]
 pub struct ParsedMorphoRepay {
+    /// Where the event was emitted
+    pub context: EventContext,
     /// Address of the Morpho contract
     pub morpho: Address,
     /// Market identifier
     pub market_id: B256,
+    /// The market's params, if its CreateMarket or a snapshot entry has been seen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market: Option<MorphoMarketParams>,
     /// The caller initiating the repayment
     pub caller: Address,
     /// The owner of the position being repaid
@@ -293,11 +451,14 @@ pub struct ParsedMorphoRepay {
     pub assets: U256,
     /// Amount of shares burned (debt reduced)
     pub shares: U256,
+    /// The shares valued at the market's totals before the event, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shares_in_assets: Option<U256>,
 }
 
 impl ParsedMorphoRepay {
     /// Try to parse a Repay event from a log entry
-    pub fn try_from_log(log: &ReceiptLog) -> Option<Self> {
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
         if log.topics.len() != 4 {
             return None;
         }
@@ -309,28 +470,38 @@ impl ParsedMorphoRepay {
         let decoded = Repay::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
 
         Some(Self {
+            context: context.clone(),
             morpho: log.address,
             market_id: B256::from(decoded.id),
+            market: None,
             caller: decoded.caller,
             on_behalf_of: decoded.onBehalf,
             assets: decoded.assets,
             shares: decoded.shares,
+            shares_in_assets: None,
         })
     }
 
     /// Extract all Repay events from a slice of logs
-    pub fn extract_all(logs: &[ReceiptLog]) -> Vec<Self> {
-        logs.iter().filter_map(Self::try_from_log).collect()
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
     }
 }
 
 /// Parsed Morpho SupplyCollateral event
 #[derive(Debug, Clone, Serialize)]
 pub struct ParsedMorphoSupplyCollateral {
+    /// Where the event was emitted
+    pub context: EventContext,
     /// Address of the Morpho contract
     pub morpho: Address,
     /// Market identifier
     pub market_id: B256,
+    /// The market's params, if its CreateMarket or a snapshot entry has been seen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market: Option<MorphoMarketParams>,
     /// The caller initiating the collateral supply
     pub caller: Address,
     /// The beneficiary of the collateral
@@ -341,7 +512,7 @@ pub struct ParsedMorphoSupplyCollateral {
 
 impl ParsedMorphoSupplyCollateral {
     /// Try to parse a SupplyCollateral event from a log entry
-    pub fn try_from_log(log: &ReceiptLog) -> Option<Self> {
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
         if log.topics.len() != 4 {
             return None;
         }
@@ -354,8 +525,10 @@ impl ParsedMorphoSupplyCollateral {
             SupplyCollateral::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
 
         Some(Self {
+            context: context.clone(),
             morpho: log.address,
             market_id: B256::from(decoded.id),
+            market: None,
             caller: decoded.caller,
             on_behalf_of: decoded.onBehalf,
             assets: decoded.assets,
@@ -363,18 +536,25 @@ impl ParsedMorphoSupplyCollateral {
     }
 
     /// Extract all SupplyCollateral events from a slice of logs
-    pub fn extract_all(logs: &[ReceiptLog]) -> Vec<Self> {
-        logs.iter().filter_map(Self::try_from_log).collect()
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
     }
 }
 
 /// Parsed Morpho WithdrawCollateral event
 #[derive(Debug, Clone, Serialize)]
 pub struct ParsedMorphoWithdrawCollateral {
+    /// Where the event was emitted
+    pub context: EventContext,
     /// Address of the Morpho contract
     pub morpho: Address,
     /// Market identifier
     pub market_id: B256,
+    /// The market's params, if its CreateMarket or a snapshot entry has been seen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market: Option<MorphoMarketParams>,
     /// The caller initiating the collateral withdrawal
     pub caller: Address,
     /// The owner of the collateral
@@ -387,7 +567,7 @@ pub struct ParsedMorphoWithdrawCollateral {
 
 impl ParsedMorphoWithdrawCollateral {
     /// Try to parse a WithdrawCollateral event from a log entry
-    pub fn try_from_log(log: &ReceiptLog) -> Option<Self> {
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
         if log.topics.len() != 4 {
             return None;
         }
@@ -400,8 +580,10 @@ impl ParsedMorphoWithdrawCollateral {
             WithdrawCollateral::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
 
         Some(Self {
+            context: context.clone(),
             morpho: log.address,
             market_id: B256::from(decoded.id),
+            market: None,
             caller: decoded.caller,
             on_behalf_of: decoded.onBehalf,
             receiver: decoded.receiver,
@@ -410,18 +592,25 @@ impl ParsedMorphoWithdrawCollateral {
     }
 
     /// Extract all WithdrawCollateral events from a slice of logs
-    pub fn extract_all(logs: &[ReceiptLog]) -> Vec<Self> {
-        logs.iter().filter_map(Self::try_from_log).collect()
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
     }
 }
 
 /// Parsed Morpho Liquidate event
 #[derive(Debug, Clone, Serialize)]
 pub struct ParsedMorphoLiquidation {
+    /// Where the event was emitted
+    pub context: EventContext,
     /// Address of the Morpho contract
     pub morpho: Address,
     /// Market identifier
     pub market_id: B256,
+    /// The market's params, if its CreateMarket or a snapshot entry has been seen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market: Option<MorphoMarketParams>,
     /// The liquidator
     pub caller: Address,
     /// The borrower being liquidated
@@ -436,11 +625,17 @@ pub struct ParsedMorphoLiquidation {
     pub bad_debt_assets: U256,
     /// Bad debt shares (if any)
     pub bad_debt_shares: U256,
+    /// The repaid shares valued at the market's totals before the event, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub repaid_shares_in_assets: Option<U256>,
+    /// The bad debt shares valued at the market's totals before the event, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bad_debt_shares_in_assets: Option<U256>,
 }
 
 impl ParsedMorphoLiquidation {
     /// Try to parse a Liquidate event from a log entry
-    pub fn try_from_log(log: &ReceiptLog) -> Option<Self> {
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
         if log.topics.len() != 4 {
             return None;
         }
@@ -453,8 +648,10 @@ impl ParsedMorphoLiquidation {
             Liquidate::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
 
         Some(Self {
+            context: context.clone(),
             morpho: log.address,
             market_id: B256::from(decoded.id),
+            market: None,
             caller: decoded.caller,
             borrower: decoded.borrower,
             repaid_assets: decoded.repaidAssets,
@@ -462,18 +659,24 @@ impl ParsedMorphoLiquidation {
             seized_assets: decoded.seizedAssets,
             bad_debt_assets: decoded.badDebtAssets,
             bad_debt_shares: decoded.badDebtShares,
+            repaid_shares_in_assets: None,
+            bad_debt_shares_in_assets: None,
         })
     }
 
     /// Extract all Liquidate events from a slice of logs
-    pub fn extract_all(logs: &[ReceiptLog]) -> Vec<Self> {
-        logs.iter().filter_map(Self::try_from_log).collect()
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
     }
 }
 
 /// Parsed Morpho CreateMarket event
 #[derive(Debug, Clone, Serialize)]
 pub struct ParsedMorphoCreateMarket {
+    /// Where the event was emitted
+    pub context: EventContext,
     /// Address of the Morpho contract
     pub morpho: Address,
     /// Market identifier
@@ -492,7 +695,7 @@ pub struct ParsedMorphoCreateMarket {
 
 impl ParsedMorphoCreateMarket {
     /// Try to parse a CreateMarket event from a log entry
-    pub fn try_from_log(log: &ReceiptLog) -> Option<Self> {
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
         if log.topics.len() != 2 {
             return None;
         }
@@ -505,6 +708,7 @@ impl ParsedMorphoCreateMarket {
             CreateMarket::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
 
         Some(Self {
+            context: context.clone(),
             morpho: log.address,
             market_id: B256::from(decoded.id),
             loan_token: decoded.marketParams.loanToken,
@@ -516,8 +720,269 @@ impl ParsedMorphoCreateMarket {
     }
 
     /// Extract all CreateMarket events from a slice of logs
-    pub fn extract_all(logs: &[ReceiptLog]) -> Vec<Self> {
-        logs.iter().filter_map(Self::try_from_log).collect()
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+
+    /// The params of the created market
+    pub fn params(&self) -> MorphoMarketParams {
+        MorphoMarketParams {
+            loan_token: self.loan_token,
+            collateral_token: self.collateral_token,
+            oracle: self.oracle,
+            irm: self.irm,
+            lltv: self.lltv,
+        }
+    }
+}
+
+/// Parsed Morpho AccrueInterest event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedMorphoAccrueInterest {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// Address of the Morpho contract
+    pub morpho: Address,
+    /// Market identifier
+    pub market_id: B256,
+    /// The market's params, if its CreateMarket or a snapshot entry has been seen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market: Option<MorphoMarketParams>,
+    /// Borrow rate per second since the last accrual, scaled by 1e18
+    pub prev_borrow_rate: U256,
+    /// Interest added to the market's borrow and supply assets
+    pub interest: U256,
+    /// Supply shares minted to the fee recipient
+    pub fee_shares: U256,
+    /// The fee shares valued at the market's totals after the accrual, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee_shares_in_assets: Option<U256>,
+    /// The market's totals after the accrual, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub totals: Option<MorphoMarketTotals>,
+}
+
+impl ParsedMorphoAccrueInterest {
+    /// Try to parse a AccrueInterest event from a log entry
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 2 {
+            return None;
+        }
+
+        if log.topics[0] != AccrueInterest::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded =
+            AccrueInterest::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            morpho: log.address,
+            market_id: B256::from(decoded.id),
+            market: None,
+            prev_borrow_rate: decoded.prevBorrowRate,
+            interest: decoded.interest,
+            fee_shares: decoded.feeShares,
+            fee_shares_in_assets: None,
+            totals: None,
+        })
+    }
+
+    /// Extract all AccrueInterest events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// Parsed Morpho FlashLoan event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedMorphoFlashLoan {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// Address of the Morpho contract
+    pub morpho: Address,
+    /// The flash loan's borrower
+    pub caller: Address,
+    /// The token lent
+    pub token: Address,
+    /// Amount lent, repaid in the same transaction without a fee
+    pub assets: U256,
+}
+
+impl ParsedMorphoFlashLoan {
+    /// Try to parse a FlashLoan event from a log entry
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 3 {
+            return None;
+        }
+
+        if log.topics[0] != FlashLoan::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded =
+            FlashLoan::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            morpho: log.address,
+            caller: decoded.caller,
+            token: decoded.token,
+            assets: decoded.assets,
+        })
+    }
+
+    /// Extract all FlashLoan events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// Parsed Morpho SetFee event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedMorphoSetFee {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// Address of the Morpho contract
+    pub morpho: Address,
+    /// Market identifier
+    pub market_id: B256,
+    /// The market's params, if its CreateMarket or a snapshot entry has been seen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub market: Option<MorphoMarketParams>,
+    /// Share of interest taken as fee, scaled by 1e18
+    pub fee: U256,
+}
+
+impl ParsedMorphoSetFee {
+    /// Try to parse a SetFee event from a log entry
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 2 {
+            return None;
+        }
+
+        if log.topics[0] != SetFee::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded = SetFee::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            morpho: log.address,
+            market_id: B256::from(decoded.id),
+            market: None,
+            fee: decoded.newFee,
+        })
+    }
+
+    /// Extract all SetFee events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// Parsed Morpho SetAuthorization event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedMorphoSetAuthorization {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// Address of the Morpho contract
+    pub morpho: Address,
+    /// The caller setting the authorization, the authorizer or a signature relayer
+    pub caller: Address,
+    /// The account whose positions are managed
+    pub authorizer: Address,
+    /// The account allowed to manage them
+    pub authorized: Address,
+    /// Whether the authorization was granted or revoked
+    pub is_authorized: bool,
+}
+
+impl ParsedMorphoSetAuthorization {
+    /// Try to parse a SetAuthorization event from a log entry
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 4 {
+            return None;
+        }
+
+        if log.topics[0] != SetAuthorization::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded =
+            SetAuthorization::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            morpho: log.address,
+            caller: decoded.caller,
+            authorizer: decoded.authorizer,
+            authorized: decoded.authorized,
+            is_authorized: decoded.newIsAuthorized,
+        })
+    }
+
+    /// Extract all SetAuthorization events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// Parsed Morpho IncrementNonce event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedMorphoIncrementNonce {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// Address of the Morpho contract
+    pub morpho: Address,
+    /// The caller submitting the signed authorization
+    pub caller: Address,
+    /// The account that signed it
+    pub authorizer: Address,
+    /// The nonce consumed
+    pub used_nonce: U256,
+}
+
+impl ParsedMorphoIncrementNonce {
+    /// Try to parse a IncrementNonce event from a log entry
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 3 {
+            return None;
+        }
+
+        if log.topics[0] != IncrementNonce::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded =
+            IncrementNonce::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            morpho: log.address,
+            caller: decoded.caller,
+            authorizer: decoded.authorizer,
+            used_nonce: decoded.usedNonce,
+        })
+    }
+
+    /// Extract all IncrementNonce events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
     }
 }
 
@@ -532,11 +997,16 @@ pub struct MorphoUpdates {
     pub withdraw_collaterals: Vec<ParsedMorphoWithdrawCollateral>,
     pub liquidations: Vec<ParsedMorphoLiquidation>,
     pub create_markets: Vec<ParsedMorphoCreateMarket>,
+    pub accrue_interests: Vec<ParsedMorphoAccrueInterest>,
+    pub flash_loans: Vec<ParsedMorphoFlashLoan>,
+    pub set_fees: Vec<ParsedMorphoSetFee>,
+    pub set_authorizations: Vec<ParsedMorphoSetAuthorization>,
+    pub increment_nonces: Vec<ParsedMorphoIncrementNonce>,
 }
 
 impl MorphoUpdates {
     /// Extract all Morpho events from a slice of logs
-    pub fn extract_all(logs: &[ReceiptLog]) -> Self {
+    pub fn extract_all(logs: &[ContextLog]) -> Self {
         Self {
             supplies: ParsedMorphoSupply::extract_all(logs),
             withdraws: ParsedMorphoWithdraw::extract_all(logs),
@@ -546,6 +1016,11 @@ impl MorphoUpdates {
             withdraw_collaterals: ParsedMorphoWithdrawCollateral::extract_all(logs),
             liquidations: ParsedMorphoLiquidation::extract_all(logs),
             create_markets: ParsedMorphoCreateMarket::extract_all(logs),
+            accrue_interests: ParsedMorphoAccrueInterest::extract_all(logs),
+            flash_loans: ParsedMorphoFlashLoan::extract_all(logs),
+            set_fees: ParsedMorphoSetFee::extract_all(logs),
+            set_authorizations: ParsedMorphoSetAuthorization::extract_all(logs),
+            increment_nonces: ParsedMorphoIncrementNonce::extract_all(logs),
         }
     }
 
@@ -559,6 +1034,11 @@ impl MorphoUpdates {
             && self.withdraw_collaterals.is_empty()
             && self.liquidations.is_empty()
             && self.create_markets.is_empty()
+            && self.accrue_interests.is_empty()
+            && self.flash_loans.is_empty()
+            && self.set_fees.is_empty()
+            && self.set_authorizations.is_empty()
+            && self.increment_nonces.is_empty()
     }
 
     /// Total count of all events
@@ -571,12 +1051,438 @@ impl MorphoUpdates {
             + self.withdraw_collaterals.len()
             + self.liquidations.len()
             + self.create_markets.len()
+            + self.accrue_interests.len()
+            + self.flash_loans.len()
+            + self.set_fees.len()
+            + self.set_authorizations.len()
+            + self.increment_nonces.len()
+    }
+}
+
+/// A market as listed in the snapshot. The id is computed from the params
+/// when omitted.
+#[derive(Debug, Deserialize)]
+struct MarketEntry {
+    #[serde(default)]
+    id: Option<B256>,
+    #[serde(flatten)]
+    params: MorphoMarketParams,
+    /// The market's totals at snapshot time
+    #[serde(default)]
+    totals: Option<MorphoMarketTotals>,
+}
+
+/// On-disk layout of the market snapshot
+#[derive(Debug, Deserialize)]
+struct MarketRegistryFile {
+    #[serde(default)]
+    markets: Vec<MarketEntry>,
+}
+
+/// Parse a market snapshot, rejecting entries whose `id` doesn't match their params
+fn parse_snapshot(json: &str) -> Result<Vec<MarketEntry>, serde_json::Error> {
+    let file: MarketRegistryFile = serde_json::from_str(json)?;
+    for entry in &file.markets {
+        let id = entry.params.id();
+        if let Some(listed) = entry.id
+            && listed != id
+        {
+            return Err(serde::de::Error::custom(format!(
+                "market {listed} has params with id {id}"
+            )));
+        }
+    }
+    Ok(file.markets)
+}
+
+/// Market params by market id, learned from CreateMarket events and snapshots.
+///
+/// Ids are hashes of the params, so a market has the same id on every Morpho
+/// deployment and the registry isn't keyed by contract. Markets are only
+/// learned from Morpho Blue's own CreateMarket events whose id hashes their params.
+#[derive(Debug, Clone, Default)]
+pub struct MorphoMarketRegistry {
+    markets: HashMap<B256, MorphoMarketParams>,
+}
+
+impl MorphoMarketRegistry {
+    pub fn new(markets: Vec<MorphoMarketParams>) -> Self {
+        Self {
+            markets: markets.into_iter().map(|m| (m.id(), m)).collect(),
+        }
+    }
+
+    /// Parse a registry from JSON of the form `{"markets": [...]}`. Entries
+    /// whose `id` doesn't match their params are rejected.
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let markets = parse_snapshot(json)?;
+        Ok(Self::new(markets.into_iter().map(|m| m.params).collect()))
+    }
+
+    /// Load a registry from a JSON file
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let json = std::fs::read_to_string(path)?;
+        Ok(Self::from_json(&json)?)
+    }
+
+    /// Add the markets of `other`
+    pub fn extend(&mut self, other: MorphoMarketRegistry) {
+        self.markets.extend(other.markets);
+    }
+
+    /// Number of known markets
+    pub fn len(&self) -> usize {
+        self.markets.len()
+    }
+
+    /// Returns true if no markets are known
+    pub fn is_empty(&self) -> bool {
+        self.markets.is_empty()
+    }
+
+    /// Look up a market's params
+    pub fn market(&self, market_id: &B256) -> Option<&MorphoMarketParams> {
+        self.markets.get(market_id)
+    }
+
+    /// Record the markets Morpho Blue created in `updates`, then attach the
+    /// known market params to its other events. A CreateMarket whose id
+    /// doesn't hash its params is ignored, and known markets are kept.
+    pub fn annotate(&mut self, updates: &mut MorphoUpdates) {
+        for created in &updates.create_markets {
+            let params = created.params();
+            if created.morpho != MORPHO_BLUE || params.id() != created.market_id {
+                continue;
+            }
+            self.markets.entry(created.market_id).or_insert(params);
+        }
+        for supply in &mut updates.supplies {
+            supply.market = self.market(&supply.market_id).copied();
+        }
+        for withdraw in &mut updates.withdraws {
+            withdraw.market = self.market(&withdraw.market_id).copied();
+        }
+        for borrow in &mut updates.borrows {
+            borrow.market = self.market(&borrow.market_id).copied();
+        }
+        for repay in &mut updates.repays {
+            repay.market = self.market(&repay.market_id).copied();
+        }
+        for supply_collateral in &mut updates.supply_collaterals {
+            supply_collateral.market = self.market(&supply_collateral.market_id).copied();
+        }
+        for withdraw_collateral in &mut updates.withdraw_collaterals {
+            withdraw_collateral.market = self.market(&withdraw_collateral.market_id).copied();
+        }
+        for liquidation in &mut updates.liquidations {
+            liquidation.market = self.market(&liquidation.market_id).copied();
+        }
+        for accrual in &mut updates.accrue_interests {
+            accrual.market = self.market(&accrual.market_id).copied();
+        }
+        for set_fee in &mut updates.set_fees {
+            set_fee.market = self.market(&set_fee.market_id).copied();
+        }
+    }
+}
+
+/// A market's supply and borrow totals, as in Morpho's `market(id)`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MorphoMarketTotals {
+    pub supply_assets: U256,
+    pub supply_shares: U256,
+    pub borrow_assets: U256,
+    pub borrow_shares: U256,
+    /// Share of interest taken as fee, scaled by 1e18
+    #[serde(default)]
+    pub fee: U256,
+}
+
+impl MorphoMarketTotals {
+    /// Value supply shares in assets, rounding down like `SharesMathLib.toAssetsDown`
+    pub fn supply_shares_to_assets(&self, shares: U256) -> U256 {
+        shares.saturating_mul(self.supply_assets.saturating_add(VIRTUAL_ASSETS))
+            / self.supply_shares.saturating_add(VIRTUAL_SHARES)
+    }
+
+    /// Value borrow shares in assets, rounding up like `SharesMathLib.toAssetsUp`
+    pub fn borrow_shares_to_assets(&self, shares: U256) -> U256 {
+        shares
+            .saturating_mul(self.borrow_assets.saturating_add(VIRTUAL_ASSETS))
+            .div_ceil(self.borrow_shares.saturating_add(VIRTUAL_SHARES))
+    }
+}
+
+/// Supply and borrow totals by market id, for valuing shares.
+///
+/// Totals are only known for markets created while running, which start
+/// empty, and markets seeded from a snapshot; events of other markets are
+/// left unvalued. Keyed by market id and only fed Morpho Blue's events, as the
+/// same market id can exist on other deployments with different totals.
+///
+/// Totals as of the last completed block are kept apart from the changes of
+/// the current payload, which are dropped if the payload is replaced.
+#[derive(Debug, Clone, Default)]
+pub struct MorphoTotalsCache {
+    payload_id: Option<String>,
+    block_number: Option<u64>,
+    /// Totals as of the last completed block
+    markets: HashMap<B256, MorphoMarketTotals>,
+    /// Totals of the markets the current payload moved
+    pending: HashMap<B256, MorphoMarketTotals>,
+}
+
+impl MorphoTotalsCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse the totals of the snapshot's markets that list them, from JSON
+    /// of the form `{"markets": [...]}`
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let markets = parse_snapshot(json)?;
+        Ok(Self {
+            markets: markets
+                .into_iter()
+                .filter_map(|m| Some((m.params.id(), m.totals?)))
+                .collect(),
+            ..Default::default()
+        })
+    }
+
+    /// Load totals from a market snapshot file
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let json = std::fs::read_to_string(path)?;
+        Ok(Self::from_json(&json)?)
+    }
+
+    /// Number of markets with known totals
+    pub fn len(&self) -> usize {
+        self.markets.len()
+            + self
+                .pending
+                .keys()
+                .filter(|id| !self.markets.contains_key(*id))
+                .count()
+    }
+
+    /// Returns true if no market's totals are known
+    pub fn is_empty(&self) -> bool {
+        self.markets.is_empty() && self.pending.is_empty()
+    }
+
+    /// A market's totals, including the current payload's changes
+    pub fn get(&self, market_id: &B256) -> Option<&MorphoMarketTotals> {
+        self.pending
+            .get(market_id)
+            .or_else(|| self.markets.get(market_id))
+    }
+
+    /// Start tracking a flashblock's payload. A new payload for the next block
+    /// commits the previous payload's changes; one replacing the current
+    /// block's payload drops them, as they never landed.
+    pub fn start_payload(&mut self, payload_id: &str, block_number: u64) {
+        if self.payload_id.as_deref() == Some(payload_id) {
+            return;
+        }
+        if self.block_number == Some(block_number) {
+            self.pending.clear();
+        } else {
+            self.markets.extend(self.pending.drain());
+        }
+        self.payload_id = Some(payload_id.to_string());
+        self.block_number = Some(block_number);
+    }
+
+    /// A known market's totals in the current payload, copied from the last
+    /// completed block on the payload's first change
+    fn totals_mut(&mut self, market_id: &B256) -> Option<&mut MorphoMarketTotals> {
+        let totals = match self.markets.get(market_id) {
+            Some(committed) => self.pending.entry(*market_id).or_insert(*committed),
+            None => self.pending.get_mut(market_id)?,
+        };
+        Some(totals)
+    }
+
+    /// Apply the changes Morpho Blue's events in `updates` make to the totals
+    /// of known markets in execution order, mirroring the Morpho contract's accounting, and value
+    /// each event's shares at the totals in effect when it executed. User
+    /// actions are valued at the totals before them, which Morpho converts
+    /// their shares at; interest accruals carry the totals right after them.
+    pub fn apply(&mut self, updates: &mut MorphoUpdates) {
+        let mut events: Vec<_> =
+            updates
+                .create_markets
+                .iter()
+                .enumerate()
+                .map(|(i, e)| {
+                    (
+                        e.context.execution_order(),
+                        e.morpho,
+                        TotalsEvent::CreateMarket(i),
+                    )
+                })
+                .chain(updates.accrue_interests.iter().enumerate().map(|(i, e)| {
+                    (
+                        e.context.execution_order(),
+                        e.morpho,
+                        TotalsEvent::AccrueInterest(i),
+                    )
+                }))
+                .chain(updates.set_fees.iter().enumerate().map(|(i, e)| {
+                    (
+                        e.context.execution_order(),
+                        e.morpho,
+                        TotalsEvent::SetFee(i),
+                    )
+                }))
+                .chain(updates.supplies.iter().enumerate().map(|(i, e)| {
+                    (
+                        e.context.execution_order(),
+                        e.morpho,
+                        TotalsEvent::Supply(i),
+                    )
+                }))
+                .chain(updates.withdraws.iter().enumerate().map(|(i, e)| {
+                    (
+                        e.context.execution_order(),
+                        e.morpho,
+                        TotalsEvent::Withdraw(i),
+                    )
+                }))
+                .chain(updates.borrows.iter().enumerate().map(|(i, e)| {
+                    (
+                        e.context.execution_order(),
+                        e.morpho,
+                        TotalsEvent::Borrow(i),
+                    )
+                }))
+                .chain(
+                    updates.repays.iter().enumerate().map(|(i, e)| {
+                        (e.context.execution_order(), e.morpho, TotalsEvent::Repay(i))
+                    }),
+                )
+                .chain(updates.liquidations.iter().enumerate().map(|(i, e)| {
+                    (
+                        e.context.execution_order(),
+                        e.morpho,
+                        TotalsEvent::Liquidation(i),
+                    )
+                }))
+                .collect();
+        events.retain(|(_, morpho, _)| *morpho == MORPHO_BLUE);
+        events.sort_by_key(|(order, _, _)| *order);
+
+        for (_, _, event) in events {
+            match event {
+                TotalsEvent::CreateMarket(i) => {
+                    let market_id = updates.create_markets[i].market_id;
+                    if self.get(&market_id).is_none() {
+                        self.pending
+                            .insert(market_id, MorphoMarketTotals::default());
+                    }
+                }
+                TotalsEvent::AccrueInterest(i) => {
+                    let accrual = &mut updates.accrue_interests[i];
+                    let Some(totals) = self.totals_mut(&accrual.market_id) else {
+                        continue;
+                    };
+                    totals.borrow_assets = totals.borrow_assets.saturating_add(accrual.interest);
+                    totals.supply_assets = totals.supply_assets.saturating_add(accrual.interest);
+                    totals.supply_shares = totals.supply_shares.saturating_add(accrual.fee_shares);
+                    accrual.fee_shares_in_assets =
+                        Some(totals.supply_shares_to_assets(accrual.fee_shares));
+                    accrual.totals = Some(*totals);
+                }
+                TotalsEvent::SetFee(i) => {
+                    let set_fee = &updates.set_fees[i];
+                    if let Some(totals) = self.totals_mut(&set_fee.market_id) {
+                        totals.fee = set_fee.fee;
+                    }
+                }
+                TotalsEvent::Supply(i) => {
+                    let supply = &mut updates.supplies[i];
+                    let Some(totals) = self.totals_mut(&supply.market_id) else {
+                        continue;
+                    };
+                    supply.shares_in_assets = Some(totals.supply_shares_to_assets(supply.shares));
+                    totals.supply_assets = totals.supply_assets.saturating_add(supply.assets);
+                    totals.supply_shares = totals.supply_shares.saturating_add(supply.shares);
+                }
+                TotalsEvent::Withdraw(i) => {
+                    let withdraw = &mut updates.withdraws[i];
+                    let Some(totals) = self.totals_mut(&withdraw.market_id) else {
+                        continue;
+                    };
+                    withdraw.shares_in_assets =
+                        Some(totals.supply_shares_to_assets(withdraw.shares));
+                    totals.supply_assets = totals.supply_assets.saturating_sub(withdraw.assets);
+                    totals.supply_shares = totals.supply_shares.saturating_sub(withdraw.shares);
+                }
+                TotalsEvent::Borrow(i) => {
+                    let borrow = &mut updates.borrows[i];
+                    let Some(totals) = self.totals_mut(&borrow.market_id) else {
+                        continue;
+                    };
+                    borrow.shares_in_assets = Some(totals.borrow_shares_to_assets(borrow.shares));
+                    totals.borrow_assets = totals.borrow_assets.saturating_add(borrow.assets);
+                    totals.borrow_shares = totals.borrow_shares.saturating_add(borrow.shares);
+                }
+                TotalsEvent::Repay(i) => {
+                    let repay = &mut updates.repays[i];
+                    let Some(totals) = self.totals_mut(&repay.market_id) else {
+                        continue;
+                    };
+                    repay.shares_in_assets = Some(totals.borrow_shares_to_assets(repay.shares));
+                    totals.borrow_assets = totals.borrow_assets.saturating_sub(repay.assets);
+                    totals.borrow_shares = totals.borrow_shares.saturating_sub(repay.shares);
+                }
+                TotalsEvent::Liquidation(i) => {
+                    let liquidation = &mut updates.liquidations[i];
+                    let Some(totals) = self.totals_mut(&liquidation.market_id) else {
+                        continue;
+                    };
+                    liquidation.repaid_shares_in_assets =
+                        Some(totals.borrow_shares_to_assets(liquidation.repaid_shares));
+                    liquidation.bad_debt_shares_in_assets =
+                        Some(totals.borrow_shares_to_assets(liquidation.bad_debt_shares));
+                    totals.borrow_shares = totals.borrow_shares.saturating_sub(
+                        liquidation
+                            .repaid_shares
+                            .saturating_add(liquidation.bad_debt_shares),
+                    );
+                    totals.borrow_assets = totals.borrow_assets.saturating_sub(
+                        liquidation
+                            .repaid_assets
+                            .saturating_add(liquidation.bad_debt_assets),
+                    );
+                    // Bad debt is socialized among suppliers
+                    totals.supply_assets = totals
+                        .supply_assets
+                        .saturating_sub(liquidation.bad_debt_assets);
+                }
+            }
+        }
     }
 }
 
+/// A Morpho event that moves a market's totals, by its index in `MorphoUpdates`
+#[derive(Debug, Clone, Copy)]
+enum TotalsEvent {
+    CreateMarket(usize),
+    AccrueInterest(usize),
+    SetFee(usize),
+    Supply(usize),
+    Withdraw(usize),
+    Borrow(usize),
+    Repay(usize),
+    Liquidation(usize),
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::flashblocks::test_logs::context_log;
 
     #[test]
     fn test_morpho_events_default() {
@@ -654,6 +1560,323 @@ mod tests {
         assert_eq!(CreateMarket::SIGNATURE_HASH, expected_sig);
     }
 
+    fn weth_usdc() -> MorphoMarketParams {
+        MorphoMarketParams {
+            loan_token: Address::repeat_byte(0x5d),
+            collateral_token: Address::repeat_byte(0xe7),
+            oracle: Address::repeat_byte(0x0c),
+            irm: Address::repeat_byte(0x12),
+            lltv: U256::from(860_000_000_000_000_000u64),
+        }
+    }
+
+    fn supply(id: B256) -> Supply {
+        Supply {
+            id,
+            caller: Address::repeat_byte(0x01),
+            onBehalf: Address::repeat_byte(0x01),
+            assets: U256::from(1_000),
+            shares: U256::from(1_000_000),
+        }
+    }
+
+    #[test]
+    fn test_create_market_annotates_later_events() {
+        let params = weth_usdc();
+        let created = CreateMarket {
+            id: params.id(),
+            marketParams: MarketParams {
+                loanToken: params.loan_token,
+                collateralToken: params.collateral_token,
+                oracle: params.oracle,
+                irm: params.irm,
+                lltv: params.lltv,
+            },
+        };
+        let unknown = B256::repeat_byte(0x99);
+        let logs = vec![
+            context_log(MORPHO_BLUE, created.encode_log_data()),
+            context_log(MORPHO_BLUE, supply(params.id()).encode_log_data()),
+            context_log(MORPHO_BLUE, supply(unknown).encode_log_data()),
+        ];
+
+        let mut registry = MorphoMarketRegistry::default();
+        let mut updates = MorphoUpdates::extract_all(&logs);
+        registry.annotate(&mut updates);
+
+        assert_eq!(updates.create_markets[0].params(), params);
+        assert_eq!(registry.market(&params.id()), Some(&params));
+        assert_eq!(updates.supplies[0].market, Some(params));
+        assert_eq!(updates.supplies[1].market, None);
+        let json = serde_json::to_value(&updates.supplies[1]).unwrap();
+        assert!(json.get("market").is_none());
+    }
+
+    #[test]
+    fn test_create_market_ignores_forgeries() {
+        let params = weth_usdc();
+        let create = |id: B256, params: MorphoMarketParams| CreateMarket {
+            id,
+            marketParams: MarketParams {
+                loanToken: params.loan_token,
+                collateralToken: params.collateral_token,
+                oracle: params.oracle,
+                irm: params.irm,
+                lltv: params.lltv,
+            },
+        };
+        let other = MorphoMarketParams {
+            oracle: Address::repeat_byte(0x66),
+            ..params
+        };
+        let fork = Address::repeat_byte(0xbb);
+        let logs = vec![
+            // Another contract creating a market
+            context_log(fork, create(other.id(), other).encode_log_data()),
+            // Params that don't hash to the id of a known market
+            context_log(MORPHO_BLUE, create(params.id(), other).encode_log_data()),
+            context_log(MORPHO_BLUE, supply(other.id()).encode_log_data()),
+            context_log(MORPHO_BLUE, supply(params.id()).encode_log_data()),
+        ];
+
+        let mut registry = MorphoMarketRegistry::new(vec![params]);
+        let mut updates = MorphoUpdates::extract_all(&logs);
+        assert_eq!(updates.create_markets.len(), 2);
+        registry.annotate(&mut updates);
+
+        assert_eq!(registry.len(), 1);
+        assert_eq!(registry.market(&params.id()), Some(&params));
+        assert_eq!(registry.market(&other.id()), None);
+        assert_eq!(updates.supplies[0].market, None);
+        assert_eq!(updates.supplies[1].market, Some(params));
+    }
+
+    #[test]
+    fn test_market_snapshot() {
+        let params = weth_usdc();
+        let entry = |id: B256| {
+            format!(
+                r#"{{"id": "{id}", "loan_token": "{}", "collateral_token": "{}",
+                    "oracle": "{}", "irm": "{}", "lltv": "{}"}}"#,
+                params.loan_token, params.collateral_token, params.oracle, params.irm, params.lltv
+            )
+        };
+
+        let registry =
+            MorphoMarketRegistry::from_json(&format!(r#"{{"markets": [{}]}}"#, entry(params.id())))
+                .unwrap();
+        assert_eq!(registry.len(), 1);
+        assert_eq!(registry.market(&params.id()), Some(&params));
+
+        let mismatched = format!(r#"{{"markets": [{}]}}"#, entry(B256::repeat_byte(0x01)));
+        assert!(MorphoMarketRegistry::from_json(&mismatched).is_err());
+    }
+
+    #[test]
+    fn test_accrue_interest_signature() {
+        let expected_sig =
+            alloy_primitives::keccak256(b"AccrueInterest(bytes32,uint256,uint256,uint256)");
+        assert_eq!(AccrueInterest::SIGNATURE_HASH, expected_sig);
+    }
+
+    #[test]
+    fn test_flash_loan_signature() {
+        let expected_sig = alloy_primitives::keccak256(b"FlashLoan(address,address,uint256)");
+        assert_eq!(FlashLoan::SIGNATURE_HASH, expected_sig);
+    }
+
+    #[test]
+    fn test_set_fee_signature() {
+        let expected_sig = alloy_primitives::keccak256(b"SetFee(bytes32,uint256)");
+        assert_eq!(SetFee::SIGNATURE_HASH, expected_sig);
+    }
+
+    #[test]
+    fn test_set_authorization_signature() {
+        let expected_sig =
+            alloy_primitives::keccak256(b"SetAuthorization(address,address,address,bool)");
+        assert_eq!(SetAuthorization::SIGNATURE_HASH, expected_sig);
+    }
+
+    #[test]
+    fn test_increment_nonce_signature() {
+        let expected_sig = alloy_primitives::keccak256(b"IncrementNonce(address,address,uint256)");
+        assert_eq!(IncrementNonce::SIGNATURE_HASH, expected_sig);
+    }
+
+    #[test]
+    fn test_totals_value_shares() {
+        let params = weth_usdc();
+        let id = params.id();
+        let seeded = format!(
+            r#"{{"markets": [{{"loan_token": "{}", "collateral_token": "{}",
+                "oracle": "{}", "irm": "{}", "lltv": "{}",
+                "totals": {{"supply_assets": "1000000", "supply_shares": "1000000000000",
+                            "borrow_assets": "500000", "borrow_shares": "500000000000"}}}}]}}"#,
+            params.loan_token, params.collateral_token, params.oracle, params.irm, params.lltv
+        );
+        let mut cache = MorphoTotalsCache::from_json(&seeded).unwrap();
+        assert_eq!(cache.len(), 1);
+
+        let at = |data: alloy_primitives::LogData, tx: u64, log: u64| ContextLog {
+            context: EventContext {
+                tx_hash: B256::with_last_byte(tx as u8),
+                tx_index: Some(tx),
+                receipt_log_index: log,
+                ..Default::default()
+            },
+            ..context_log(MORPHO_BLUE, data)
+        };
+        let supply = |id: B256, assets: u64| Supply {
+            assets: U256::from(assets),
+            shares: U256::from(1_000_000_000u64),
+            ..supply(id)
+        };
+
+        // The same market id on another deployment
+        let mut fork_supply = at(supply(id, 5_000).encode_log_data(), 1, 3);
+        fork_supply.log.address = Address::repeat_byte(0xbb);
+
+        // A supply, then 100_000 assets of interest with 10% minted to the fee
+        // recipient as shares, then a supply at the higher share price. Logs
+        // are out of order to check they're applied in execution order.
+        let logs = vec![
+            at(
+                AccrueInterest {
+                    id,
+                    prevBorrowRate: U256::from(1_000_000_000u64),
+                    interest: U256::from(100_000),
+                    feeShares: U256::from(9_175_069_500u64),
+                }
+                .encode_log_data(),
+                1,
+                0,
+            ),
+            at(supply(id, 1_090).encode_log_data(), 1, 1),
+            at(supply(id, 1_000).encode_log_data(), 0, 0),
+            at(
+                supply(B256::repeat_byte(0x99), 1_000).encode_log_data(),
+                1,
+                2,
+            ),
+            fork_supply,
+        ];
+        let mut updates = MorphoUpdates::extract_all(&logs);
+        assert_eq!(updates.total_count(), 5);
+        cache.apply(&mut updates);
+
+        // Each supply is valued at the totals before it
+        assert_eq!(
+            updates.supplies[1].shares_in_assets,
+            Some(U256::from(1_000))
+        );
+        assert_eq!(
+            updates.supplies[0].shares_in_assets,
+            Some(U256::from(1_089))
+        );
+        assert_eq!(updates.supplies[2].shares_in_assets, None);
+        assert_eq!(updates.supplies[3].shares_in_assets, None);
+
+        // The accrual carries the totals right after it, without the later supply
+        let accrual = &updates.accrue_interests[0];
+        let after_accrual = accrual.totals.unwrap();
+        assert_eq!(after_accrual.supply_assets, U256::from(1_101_000));
+        assert_eq!(
+            after_accrual.supply_shares,
+            U256::from(1_010_175_069_500u64)
+        );
+        assert_eq!(after_accrual.borrow_assets, U256::from(600_000));
+        assert_eq!(accrual.fee_shares_in_assets, Some(U256::from(9_999)));
+
+        let totals = cache.get(&id).unwrap();
+        assert_eq!(totals.supply_assets, U256::from(1_102_090));
+        assert_eq!(totals.supply_shares, U256::from(1_011_175_069_500u64));
+        assert_eq!(totals.borrow_assets, U256::from(600_000));
+
+        // Borrow shares round up, supply shares down
+        assert_eq!(
+            totals.borrow_shares_to_assets(U256::from(1_500_000)),
+            U256::from(2)
+        );
+        assert_eq!(
+            totals.supply_shares_to_assets(U256::from(1_500_000)),
+            U256::from(1)
+        );
+    }
+
+    #[test]
+    fn test_replaced_payload_is_dropped() {
+        let params = weth_usdc();
+        let id = params.id();
+        let seeded = format!(
+            r#"{{"markets": [{{"loan_token": "{}", "collateral_token": "{}",
+                "oracle": "{}", "irm": "{}", "lltv": "{}",
+                "totals": {{"supply_assets": "1000000", "supply_shares": "1000000000",
+                            "borrow_assets": "0", "borrow_shares": "0"}}}}]}}"#,
+            params.loan_token, params.collateral_token, params.oracle, params.irm, params.lltv
+        );
+        let mut cache = MorphoTotalsCache::from_json(&seeded).unwrap();
+        let logs = vec![context_log(MORPHO_BLUE, supply(id).encode_log_data())];
+
+        cache.start_payload("0x01", 100);
+        let mut first = MorphoUpdates::extract_all(&logs);
+        cache.apply(&mut first);
+        assert_eq!(cache.get(&id).unwrap().supply_assets, U256::from(1_001_000));
+
+        // The block is rebuilt under a new payload: the supply is undone
+        // until the new payload includes it again
+        cache.start_payload("0x02", 100);
+        assert_eq!(cache.get(&id).unwrap().supply_assets, U256::from(1_000_000));
+        let mut updates = MorphoUpdates::extract_all(&logs);
+        cache.apply(&mut updates);
+        assert_eq!(
+            updates.supplies[0].shares_in_assets,
+            first.supplies[0].shares_in_assets
+        );
+
+        // The next block keeps it, counted once
+        cache.start_payload("0x03", 101);
+        cache.start_payload("0x04", 101);
+        let totals = cache.get(&id).unwrap();
+        assert_eq!(totals.supply_assets, U256::from(1_001_000));
+        assert_eq!(totals.supply_shares, U256::from(1_001_000_000u64));
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_totals_saturate() {
+        let params = weth_usdc();
+        let id = params.id();
+        let mut cache = MorphoTotalsCache::new();
+        let create = CreateMarket {
+            id,
+            marketParams: MarketParams {
+                loanToken: params.loan_token,
+                collateralToken: params.collateral_token,
+                oracle: params.oracle,
+                irm: params.irm,
+                lltv: params.lltv,
+            },
+        };
+        let huge = Supply {
+            assets: U256::MAX,
+            shares: U256::MAX,
+            ..supply(id)
+        };
+        let logs = vec![
+            context_log(MORPHO_BLUE, create.encode_log_data()),
+            context_log(MORPHO_BLUE, huge.encode_log_data()),
+            context_log(MORPHO_BLUE, huge.encode_log_data()),
+        ];
+        let mut updates = MorphoUpdates::extract_all(&logs);
+        cache.apply(&mut updates);
+
+        let totals = cache.get(&id).unwrap();
+        assert_eq!(totals.supply_assets, U256::MAX);
+        assert_eq!(totals.supply_shares, U256::MAX);
+        assert!(updates.supplies[1].shares_in_assets.is_some());
+    }
+
     #[test]
     fn test_morpho_updates_empty() {
         let updates = MorphoUpdates::default();
@@ -0,0 +1,467 @@
+//! Exact concentrated-liquidity price math.
+//!
+//! Prices are kept as exact fractions of wide integers, so nothing loses
+//! precision until a caller asks for a decimal string or an `f64`. Parsed
+//! prices are capped at 256 bits, and scaling or conversions that would
+//! overflow return `None`.
+//! Tick conversions follow Uniswap V3's `TickMath` bit for bit.
+
+use std::cmp::Ordering;
+
+use alloy_primitives::aliases::U1024;
+use alloy_primitives::{U160, U256, uint};
+
+/// Lowest tick a pool can reach
+pub const MIN_TICK: i32 = -887_272;
+/// Highest tick a pool can reach
+pub const MAX_TICK: i32 = 887_272;
+/// `sqrt_ratio_at_tick(MIN_TICK)`
+pub const MIN_SQRT_RATIO: U160 = uint!(4295128739_U160);
+/// `sqrt_ratio_at_tick(MAX_TICK)`
+pub const MAX_SQRT_RATIO: U160 = uint!(1461446703485210103287273052203988822378723970342_U160);
+
+/// `1 / sqrt(1.0001)^(2^i)` as Q128.128, for bit `i` of the absolute tick
+const TICK_FACTORS: [U256; 20] = uint!([
+    0xfffcb933bd6fad37aa2d162d1a594001_U256,
+    0xfff97272373d413259a46990580e213a_U256,
+    0xfff2e50f5f656932ef12357cf3c7fdcc_U256,
+    0xffe5caca7e10e4e61c3624eaa0941cd0_U256,
+    0xffcb9843d60f6159c9db58835c926644_U256,
+    0xff973b41fa98c081472e6896dfb254c0_U256,
+    0xff2ea16466c96a3843ec78b326b52861_U256,
+    0xfe5dee046a99a2a811c461f1969c3053_U256,
+    0xfcbe86c7900a88aedcffc83b479aa3a4_U256,
+    0xf987a7253ac413176f2b074cf7815e54_U256,
+    0xf3392b0822b70005940c7a398e4b70f3_U256,
+    0xe7159475a2c29b7443b29c7fa6e889d9_U256,
+    0xd097f3bdfd2022b8845ad8f792aa5825_U256,
+    0xa9f746462d870fdf8a65dc1f90e061e5_U256,
+    0x70d869a156d2a1b890bb3df62baf32f7_U256,
+    0x31be135f97d08fd981231505542fcfa6_U256,
+    0x9aa508b5b7a84e1c677de54f3e99bc9_U256,
+    0x5d6af8dedb81196699c329225ee604_U256,
+    0x2216e584f5fa1ea926041bedfe98_U256,
+    0x48a170391f7dc42444e8fa2_U256,
+]);
+
+/// sqrt(1.0001^tick) as a Q64.96, exactly as `TickMath.getSqrtRatioAtTick`.
+/// Returns `None` for ticks outside `MIN_TICK..=MAX_TICK`.
+pub fn sqrt_ratio_at_tick(tick: i32) -> Option<U160> {
+    if !(MIN_TICK..=MAX_TICK).contains(&tick) {
+        return None;
+    }
+    let abs_tick = tick.unsigned_abs();
+
+    let mut ratio = if abs_tick & 1 != 0 {
+        TICK_FACTORS[0]
+    } else {
+        U256::from(1) << 128
+    };
+    for (bit, factor) in TICK_FACTORS.iter().enumerate().skip(1) {
+        if abs_tick & (1 << bit) != 0 {
+            ratio = (ratio * factor) >> 128;
+        }
+    }
+    if tick > 0 {
+        ratio = U256::MAX / ratio;
+    }
+
+    // Q128.128 -> Q64.96, rounding up so the result is never below the true ratio
+    let remainder: U256 = ratio % (U256::from(1) << 32);
+    let rounded: U256 = (ratio >> 32) + U256::from(!remainder.is_zero() as u8);
+    Some(rounded.to())
+}
+
+/// The greatest tick whose sqrt ratio is at most `sqrt_price_x96`, as
+/// `TickMath.getTickAtSqrtRatio`. Returns `None` outside the valid price range.
+pub fn tick_at_sqrt_ratio(sqrt_price_x96: U160) -> Option<i32> {
+    if sqrt_price_x96 < MIN_SQRT_RATIO || sqrt_price_x96 >= MAX_SQRT_RATIO {
+        return None;
+    }
+
+    // sqrt_ratio_at_tick is strictly increasing, so binary search it
+    let (mut low, mut high) = (MIN_TICK, MAX_TICK);
+    while low < high {
+        let mid = low + (high - low + 1) / 2;
+        if sqrt_ratio_at_tick(mid)? <= sqrt_price_x96 {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    Some(low)
+}
+
+/// An exact, non-negative price expressed as a fraction
+#[derive(Debug, Clone, Copy)]
+pub struct Price {
+    numerator: U1024,
+    denominator: U1024,
+}
+
+impl PartialEq for Price {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Price {}
+
+impl PartialOrd for Price {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Price {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Scaled prices can reach close to 1024 bits, where cross-multiplying
+        // overflows; those fall back to comparing quotients
+        match (
+            self.numerator.checked_mul(other.denominator),
+            other.numerator.checked_mul(self.denominator),
+        ) {
+            (Some(lhs), Some(rhs)) => lhs.cmp(&rhs),
+            _ => cmp_fractions(
+                self.numerator,
+                self.denominator,
+                other.numerator,
+                other.denominator,
+            ),
+        }
+    }
+}
+
+/// Compare `a / b` with `c / d` without multiplying, through their
+/// continued fraction expansions
+fn cmp_fractions(mut a: U1024, mut b: U1024, mut c: U1024, mut d: U1024) -> Ordering {
+    // Each step compares the reciprocals of the remainders, flipping the order
+    let mut flipped = false;
+    loop {
+        let (qa, ra) = (a / b, a % b);
+        let (qc, rc) = (c / d, c % d);
+        let ordering = match qa.cmp(&qc) {
+            Ordering::Equal => match (ra.is_zero(), rc.is_zero()) {
+                (true, true) => Ordering::Equal,
+                (true, false) => Ordering::Less,
+                (false, true) => Ordering::Greater,
+                (false, false) => {
+                    (a, b, c, d) = (b, ra, d, rc);
+                    flipped = !flipped;
+                    continue;
+                }
+            },
+            ordering => ordering,
+        };
+        return if flipped {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+    }
+}
+
+impl Price {
+    /// A price of `numerator / denominator`. Returns `None` if the denominator is zero.
+    pub fn new(numerator: U256, denominator: U256) -> Option<Self> {
+        (!denominator.is_zero()).then(|| Self {
+            numerator: U1024::from(numerator),
+            denominator: U1024::from(denominator),
+        })
+    }
+
+    /// Price of token0 in token1 (raw units) from a pool's sqrtPriceX96:
+    /// `sqrtPriceX96^2 / 2^192`
+    pub fn from_sqrt_price_x96(sqrt_price_x96: U160) -> Self {
+        let sqrt = U1024::from(sqrt_price_x96);
+        Self {
+            numerator: sqrt * sqrt,
+            denominator: U1024::from(1) << 192,
+        }
+    }
+
+    /// Price of token0 in token1 (raw units) at a tick
+    pub fn at_tick(tick: i32) -> Option<Self> {
+        sqrt_ratio_at_tick(tick).map(Self::from_sqrt_price_x96)
+    }
+
+    /// Parse a non-negative decimal string such as `"2500.125"`. Returns
+    /// `None` unless its digits and `10^fraction digits` each fit in 256 bits.
+    pub fn from_decimal_str(s: &str) -> Option<Self> {
+        let (int, frac) = s.split_once('.').unwrap_or((s, ""));
+        if int.is_empty() && frac.is_empty() {
+            return None;
+        }
+        let digits = format!("{int}{frac}");
+        if !digits.bytes().all(|b| b.is_ascii_digit()) || frac.len() > 77 {
+            return None;
+        }
+        // All zeros trims to an empty string, which parses as nothing rather than zero
+        let digits = digits.trim_start_matches('0');
+        let numerator = match digits {
+            "" => U256::ZERO,
+            digits => digits.parse().ok()?,
+        };
+        Some(Self {
+            numerator: U1024::from(numerator),
+            denominator: pow10(frac.len())?,
+        })
+    }
+
+    /// The inverse price, or `None` if this price is zero
+    pub fn invert(self) -> Option<Self> {
+        (!self.numerator.is_zero()).then_some(Self {
+            numerator: self.denominator,
+            denominator: self.numerator,
+        })
+    }
+
+    /// Convert a raw-unit price of token0 in token1 into whole-token units.
+    /// Returns `None` if the decimals are too far apart for the price.
+    pub fn to_human(self, decimals0: u8, decimals1: u8) -> Option<Self> {
+        self.scale(i32::from(decimals0) - i32::from(decimals1))
+    }
+
+    /// Convert a whole-token price of token0 in token1 into raw units.
+    /// Returns `None` if the decimals are too far apart for the price.
+    pub fn from_human(self, decimals0: u8, decimals1: u8) -> Option<Self> {
+        self.scale(i32::from(decimals1) - i32::from(decimals0))
+    }
+
+    /// Multiply by `10^exponent`, or `None` on overflow
+    fn scale(self, exponent: i32) -> Option<Self> {
+        let factor = pow10(exponent.unsigned_abs() as usize)?;
+        let scaled = if exponent >= 0 {
+            Self {
+                numerator: self.numerator.checked_mul(factor)?,
+                denominator: self.denominator,
+            }
+        } else {
+            Self {
+                numerator: self.numerator,
+                denominator: self.denominator.checked_mul(factor)?,
+            }
+        };
+        Some(scaled.reduced())
+    }
+
+    /// Divide out common powers of two so repeated scaling stays small
+    fn reduced(self) -> Self {
+        let shift = self
+            .numerator
+            .trailing_zeros()
+            .min(self.denominator.trailing_zeros());
+        Self {
+            numerator: self.numerator >> shift,
+            denominator: self.denominator >> shift,
+        }
+    }
+
+    /// The pool sqrtPriceX96 for this raw-unit price, rounded down.
+    /// Returns `None` if it doesn't fit in a U160.
+    pub fn to_sqrt_price_x96(self) -> Option<U160> {
+        let squared: U1024 = self.numerator.checked_shl(192)? / self.denominator;
+        let sqrt = squared.root(2);
+        (sqrt.bit_len() <= 160).then(|| sqrt.to())
+    }
+
+    /// The greatest tick at or below this raw-unit price
+    pub fn to_tick(self) -> Option<i32> {
+        tick_at_sqrt_ratio(self.to_sqrt_price_x96()?)
+    }
+
+    /// Format with up to `max_fraction_digits` digits after the point,
+    /// truncated, with trailing zeros removed. Fewer digits are printed when
+    /// the remainder scaled to `max_fraction_digits` would overflow.
+    pub fn to_decimal_string(self, max_fraction_digits: usize) -> String {
+        let integer = self.numerator / self.denominator;
+        let remainder = self.numerator % self.denominator;
+        // 10^309 is the first power of ten past U1024::MAX
+        let mut max_fraction_digits = max_fraction_digits.min(308);
+        let fraction = loop {
+            // 10^0 = 1 never overflows, so this ends
+            if let Some(scaled) =
+                pow10(max_fraction_digits).and_then(|factor| remainder.checked_mul(factor))
+            {
+                break scaled / self.denominator;
+            }
+            max_fraction_digits -= 1;
+        };
+
+        let mut out = integer.to_string();
+        if max_fraction_digits > 0 && !fraction.is_zero() {
+            let fraction = format!("{fraction:0>max_fraction_digits$}");
+            out.push('.');
+            out.push_str(fraction.trim_end_matches('0'));
+        }
+        out
+    }
+
+    /// Nearest `f64`, for display and approximate comparisons
+    pub fn to_f64(self) -> f64 {
+        let (n, n_exp) = leading_bits(self.numerator);
+        let (d, d_exp) = leading_bits(self.denominator);
+        (n / d) * 2f64.powi(n_exp - d_exp)
+    }
+}
+
+/// `10^exponent` as a wide integer, or `None` if it doesn't fit
+fn pow10(exponent: usize) -> Option<U1024> {
+    U1024::from(10).checked_pow(U1024::from(exponent))
+}
+
+/// Split `value` into its top 64 bits (as `f64`) and the power-of-two exponent they sit at
+fn leading_bits(value: U1024) -> (f64, i32) {
+    let shift = value.bit_len().saturating_sub(64);
+    ((value >> shift).to::<u64>() as f64, shift as i32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sqrt_ratio_bounds() {
+        assert_eq!(sqrt_ratio_at_tick(MIN_TICK), Some(MIN_SQRT_RATIO));
+        assert_eq!(sqrt_ratio_at_tick(MAX_TICK), Some(MAX_SQRT_RATIO));
+        assert_eq!(sqrt_ratio_at_tick(0), Some(U160::from(1) << 96));
+        assert_eq!(sqrt_ratio_at_tick(MIN_TICK - 1), None);
+        assert_eq!(sqrt_ratio_at_tick(MAX_TICK + 1), None);
+    }
+
+    #[test]
+    fn test_sqrt_ratio_matches_float_for_every_tick_bit() {
+        // One tick per factor bit, plus a few mixed ones, on both sides of zero
+        let ticks = (0..20)
+            .map(|bit| 1 << bit)
+            .chain([3, 50, 12_345, 200_000, 887_271])
+            .filter(|t| *t <= MAX_TICK);
+        for tick in ticks.flat_map(|t| [t, -t]) {
+            let exact = Price::from_sqrt_price_x96(sqrt_ratio_at_tick(tick).unwrap()).to_f64();
+            let expected = 1.0001f64.powi(tick);
+            let error = (exact - expected).abs() / expected;
+            assert!(error < 1e-9, "tick {tick}: {exact} vs {expected}");
+        }
+    }
+
+    #[test]
+    fn test_tick_round_trip() {
+        for tick in [MIN_TICK, -200_000, -1, 0, 1, 60, 195_000, MAX_TICK - 1] {
+            let sqrt = sqrt_ratio_at_tick(tick).unwrap();
+            assert_eq!(tick_at_sqrt_ratio(sqrt), Some(tick));
+            if tick > MIN_TICK {
+                assert_eq!(tick_at_sqrt_ratio(sqrt - U160::from(1)), Some(tick - 1));
+            }
+        }
+        assert_eq!(tick_at_sqrt_ratio(MAX_SQRT_RATIO), None);
+        assert_eq!(tick_at_sqrt_ratio(MIN_SQRT_RATIO - U160::from(1)), None);
+    }
+
+    #[test]
+    fn test_extreme_sqrt_price_does_not_panic() {
+        // Above 2^128, where the old u128 conversion panicked
+        let sqrt = MAX_SQRT_RATIO - U160::from(1);
+        let price = Price::from_sqrt_price_x96(sqrt);
+        assert!(price.to_f64() > 3.4e38);
+        assert!(price.invert().unwrap().to_f64() < 3e-38);
+        assert_eq!(price.to_tick(), Some(MAX_TICK - 1));
+    }
+
+    #[test]
+    fn test_human_prices() {
+        // WETH (18 decimals) / USDC (6 decimals) at 2500 USDC per WETH
+        let human = Price::from_decimal_str("2500").unwrap();
+        let raw = human.from_human(18, 6).unwrap();
+        assert_eq!(
+            raw,
+            Price::new(U256::from(2500), U256::from(10).pow(U256::from(12))).unwrap()
+        );
+        assert_eq!(raw.to_human(18, 6), Some(human));
+
+        let tick = raw.to_tick().unwrap();
+        let at_tick = Price::at_tick(tick)
+            .unwrap()
+            .to_human(18, 6)
+            .unwrap()
+            .to_f64();
+        assert!((2499.0..=2500.0).contains(&at_tick));
+
+        assert_eq!(human.invert().unwrap().to_decimal_string(6), "0.0004");
+        assert_eq!(
+            Price::from_decimal_str("1.50")
+                .unwrap()
+                .to_decimal_string(4),
+            "1.5"
+        );
+        assert_eq!(
+            Price::from_decimal_str("0.000")
+                .unwrap()
+                .to_decimal_string(4),
+            "0"
+        );
+        assert!(Price::from_decimal_str("abc").is_none());
+        assert!(Price::from_decimal_str(".").is_none());
+    }
+
+    #[test]
+    fn test_wide_denominator_decimal_string() {
+        // (10^300 - 1) / 10^300 leaves room to scale the remainder by only
+        // 10^8, so fewer digits are printed instead of wrapped ones
+        let denominator = pow10(300).unwrap();
+        let wide = Price {
+            numerator: denominator - U1024::from(1),
+            denominator,
+        };
+        assert_eq!(wide.to_decimal_string(32), "0.99999999");
+
+        let half = Price::new(U256::from(1), U256::from(2)).unwrap();
+        assert_eq!(half.to_decimal_string(400), "0.5");
+        assert_eq!(half.to_decimal_string(usize::MAX), "0.5");
+        assert_eq!(half.scale(400), None);
+    }
+
+    #[test]
+    fn test_oversized_decimal_is_rejected() {
+        // 78 nines need more than 256 bits; leading zeros don't count
+        let oversized = "9".repeat(78);
+        assert!(Price::from_decimal_str(&oversized).is_none());
+        assert!(Price::from_decimal_str(&format!("{oversized}.5")).is_none());
+        assert!(Price::from_decimal_str(&format!("0.{oversized}")).is_none());
+        assert!(Price::from_decimal_str(&"9".repeat(300)).is_none());
+        assert!(Price::from_decimal_str(&format!("000{}", "9".repeat(77))).is_some());
+    }
+
+    #[test]
+    fn test_wide_prices_compare_exactly() {
+        let largest = Price::from_decimal_str(&"9".repeat(77)).unwrap();
+        let max_sqrt = Price::from_sqrt_price_x96(MAX_SQRT_RATIO);
+        let min_sqrt = Price::from_sqrt_price_x96(MIN_SQRT_RATIO);
+        assert!(largest > max_sqrt);
+        assert!(min_sqrt < largest);
+        assert_ne!(largest, max_sqrt);
+
+        // Scaled near 1024 bits, cross-multiplying overflows
+        let huge = largest.scale(230).unwrap();
+        let tiny = min_sqrt.scale(-200).unwrap();
+        assert!(huge > max_sqrt);
+        assert!(max_sqrt < huge);
+        assert!(tiny < min_sqrt);
+        assert!(huge > tiny);
+        assert_eq!(huge.cmp(&huge), Ordering::Equal);
+        assert_eq!(
+            cmp_fractions(
+                U1024::from(7),
+                U1024::from(3),
+                U1024::from(9),
+                U1024::from(4)
+            ),
+            Ordering::Greater
+        );
+
+        // Too wide to scale further or to convert to a sqrt price
+        assert!(huge.scale(255).is_none());
+        assert!(huge.to_sqrt_price_x96().is_none());
+        assert!(largest.to_human(0, 255).is_some());
+        assert!(huge.to_human(255, 0).is_none());
+    }
+}
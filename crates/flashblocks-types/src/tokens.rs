@@ -0,0 +1,344 @@
+//! Token metadata for human-readable amounts.
+//!
+//! The registry maps token addresses to their symbol and decimals, and pool
+//! addresses to their token pair. It starts from a bundled list of common Base
+//! and Base Sepolia tokens and can be extended with a local JSON or TOML file.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use alloy_primitives::Address;
+use alloy_primitives::utils::{ParseUnits, format_units};
+use serde::{Deserialize, Serialize};
+
+use crate::price::Price;
+
+/// Token list bundled with the crate
+const BUNDLED_TOKENS: &str = include_str!("../tokens/base.json");
+
+/// A token as declared in a token list
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TokenInfo {
+    pub address: Address,
+    pub symbol: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    pub decimals: u8,
+    /// Chain the entry was listed for; informational only, since the
+    /// predeploys (e.g. WETH) share an address across chains
+    #[serde(default, rename = "chainId")]
+    pub chain_id: Option<u64>,
+}
+
+/// The token pair of a pool
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct PoolTokens {
+    /// The pool contract
+    pub address: Address,
+    pub token0: Address,
+    pub token1: Address,
+}
+
+/// On-disk layout of a token list, in JSON or TOML
+#[derive(Debug, Default, Deserialize)]
+struct TokenListFile {
+    #[serde(default)]
+    tokens: Vec<TokenInfo>,
+    #[serde(default)]
+    pools: Vec<PoolTokens>,
+}
+
+/// A raw event amount resolved against the token registry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TokenAmount {
+    /// The event field the amount came from, e.g. "amount0"
+    pub field: &'static str,
+    pub token: Address,
+    pub symbol: String,
+    pub decimals: u8,
+    /// The amount scaled by the token's decimals
+    pub amount: String,
+}
+
+/// A stream event together with its resolved token amounts.
+/// Serializes as the event's own fields plus `tokens` and `price` when known.
+#[derive(Debug, Serialize)]
+pub struct Annotated<'a, T: Serialize> {
+    #[serde(flatten)]
+    pub event: &'a T,
+    /// Amounts in the event whose token is in the registry
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tokens: Vec<TokenAmount>,
+    /// Price of one whole token0 in whole token1, for pool events whose tokens are both known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub price: Option<String>,
+}
+
+impl<'a, T: Serialize> Annotated<'a, T> {
+    pub fn new(event: &'a T, tokens: Vec<TokenAmount>) -> Self {
+        Self {
+            event,
+            tokens,
+            price: None,
+        }
+    }
+
+    /// Attach a decimal-adjusted pool price
+    pub fn with_price(mut self, price: Option<String>) -> Self {
+        self.price = price;
+        self
+    }
+}
+
+/// Maps token addresses to their metadata and pools to their token pair
+#[derive(Debug, Clone, Default)]
+pub struct TokenRegistry {
+    tokens: HashMap<Address, TokenInfo>,
+    pools: HashMap<Address, PoolTokens>,
+}
+
+impl TokenRegistry {
+    pub fn new(tokens: Vec<TokenInfo>, pools: Vec<PoolTokens>) -> Self {
+        Self {
+            tokens: tokens.into_iter().map(|t| (t.address, t)).collect(),
+            pools: pools.into_iter().map(|p| (p.address, p)).collect(),
+        }
+    }
+
+    /// The registry bundled with the crate: common Base and Base Sepolia tokens
+    pub fn bundled() -> Self {
+        Self::from_json(BUNDLED_TOKENS).expect("bundled token list is valid")
+    }
+
+    /// Parse a registry from JSON of the form `{"tokens": [...], "pools": [...]}`
+    pub fn from_json(json: &str) -> Result<Self, serde_json::Error> {
+        let file: TokenListFile = serde_json::from_str(json)?;
+        Ok(Self::new(file.tokens, file.pools))
+    }
+
+    /// Parse a registry from TOML with `[[tokens]]` and `[[pools]]` tables
+    pub fn from_toml(toml: &str) -> Result<Self, toml::de::Error> {
+        let file: TokenListFile = toml::from_str(toml)?;
+        Ok(Self::new(file.tokens, file.pools))
+    }
+
+    /// Load a registry from a `.toml` file, or JSON for any other extension
+    pub fn load(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let contents = std::fs::read_to_string(path)?;
+        if path.extension().is_some_and(|ext| ext == "toml") {
+            Ok(Self::from_toml(&contents)?)
+        } else {
+            Ok(Self::from_json(&contents)?)
+        }
+    }
+
+    /// Add the entries of `other`, replacing any existing entries for the same address
+    pub fn extend(&mut self, other: TokenRegistry) {
+        self.tokens.extend(other.tokens);
+        self.pools.extend(other.pools);
+    }
+
+    /// Number of tokens in the registry
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    /// Returns true if the registry has no tokens
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// Number of pools in the registry
+    pub fn pool_count(&self) -> usize {
+        self.pools.len()
+    }
+
+    /// Addresses of every token in the registry
+    pub fn token_addresses(&self) -> impl Iterator<Item = Address> + '_ {
+        self.tokens.keys().copied()
+    }
+
+    pub fn token(&self, address: &Address) -> Option<&TokenInfo> {
+        self.tokens.get(address)
+    }
+
+    pub fn pool(&self, address: &Address) -> Option<&PoolTokens> {
+        self.pools.get(address)
+    }
+
+    /// Resolve a raw amount of `token`, if the token is known
+    pub fn amount(
+        &self,
+        field: &'static str,
+        token: Address,
+        raw: impl Into<ParseUnits>,
+    ) -> Option<TokenAmount> {
+        let info = self.token(&token)?;
+        Some(TokenAmount {
+            field,
+            token,
+            symbol: info.symbol.clone(),
+            decimals: info.decimals,
+            amount: format_units(raw, info.decimals).ok()?,
+        })
+    }
+
+    /// Resolve a list of `(field, token, raw amount)`, skipping unknown tokens
+    pub fn amounts(
+        &self,
+        amounts: impl IntoIterator<Item = (&'static str, Address, ParseUnits)>,
+    ) -> Vec<TokenAmount> {
+        amounts
+            .into_iter()
+            .filter_map(|(field, token, raw)| self.amount(field, token, raw))
+            .collect()
+    }
+
+    /// Resolve a pool event's `amount0`/`amount1` through the pool's token pair
+    pub fn pool_amounts(
+        &self,
+        pool: &Address,
+        amount0: impl Into<ParseUnits>,
+        amount1: impl Into<ParseUnits>,
+    ) -> Vec<TokenAmount> {
+        let Some(pair) = self.pool(pool) else {
+            return Vec::new();
+        };
+        self.pair_amounts(&pair.token0, &pair.token1, amount0, amount1)
+    }
+
+    /// Resolve `amount0`/`amount1` of a known token pair
+    pub fn pair_amounts(
+        &self,
+        token0: &Address,
+        token1: &Address,
+        amount0: impl Into<ParseUnits>,
+        amount1: impl Into<ParseUnits>,
+    ) -> Vec<TokenAmount> {
+        self.amounts([
+            ("amount0", *token0, amount0.into()),
+            ("amount1", *token1, amount1.into()),
+        ])
+    }
+
+    /// Convert a raw price of `token0` in `token1` into whole-token units,
+    /// if both tokens are known
+    pub fn pair_price(&self, token0: &Address, token1: &Address, raw: Price) -> Option<String> {
+        let decimals0 = self.token(token0)?.decimals;
+        let decimals1 = self.token(token1)?.decimals;
+        Some(raw.to_human(decimals0, decimals1)?.to_decimal_string(18))
+    }
+
+    /// Convert a pool's raw price of token0 in token1 into whole-token units
+    pub fn pool_price(&self, pool: &Address, raw: Price) -> Option<String> {
+        let pair = self.pool(pool)?;
+        self.pair_price(&pair.token0, &pair.token1, raw)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::{I256, U256, address};
+
+    const WETH: Address = address!("4200000000000000000000000000000000000006");
+    const USDC: Address = address!("833589fCD6eDb6E08f4c7C32D4f71b54bdA02913");
+    const WETH_USDC_POOL: Address = address!("d0b53D9277642d899DF5C87A3966A349A798F224");
+
+    #[test]
+    fn test_bundled_registry() {
+        let registry = TokenRegistry::bundled();
+        assert_eq!(registry.token(&WETH).unwrap().decimals, 18);
+        assert_eq!(registry.token(&USDC).unwrap().symbol, "USDC");
+        assert_eq!(registry.pool(&WETH_USDC_POOL).unwrap().token1, USDC);
+    }
+
+    #[test]
+    fn test_toml_overrides_bundled() {
+        let mut registry = TokenRegistry::bundled();
+        registry.extend(
+            TokenRegistry::from_toml(
+                r#"
+                [[tokens]]
+                address = "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913"
+                symbol = "USDC.base"
+                decimals = 6
+
+                [[pools]]
+                address = "0x00000000000000000000000000000000000000aa"
+                token0 = "0x4200000000000000000000000000000000000006"
+                token1 = "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913"
+                "#,
+            )
+            .unwrap(),
+        );
+        assert_eq!(registry.token(&USDC).unwrap().symbol, "USDC.base");
+        assert!(registry.pool(&Address::with_last_byte(0xaa)).is_some());
+        assert!(registry.pool(&WETH_USDC_POOL).is_some());
+    }
+
+    #[test]
+    fn test_pool_amounts_and_price() {
+        let registry = TokenRegistry::bundled();
+        let amounts = registry.pool_amounts(
+            &WETH_USDC_POOL,
+            I256::try_from(-1_500_000_000_000_000_000i128).unwrap(),
+            I256::try_from(3_750_000_000i64).unwrap(),
+        );
+        let summary: Vec<_> = amounts
+            .iter()
+            .map(|a| (a.field, a.symbol.as_str(), a.amount.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("amount0", "WETH", "-1.500000000000000000"),
+                ("amount1", "USDC", "3750.000000"),
+            ]
+        );
+
+        // 2500 USDC per WETH is 2500e-12 in raw units
+        let sqrt_price_x96 = Price::from_decimal_str("2500")
+            .unwrap()
+            .from_human(18, 6)
+            .unwrap()
+            .to_sqrt_price_x96()
+            .unwrap();
+        let raw = Price::from_sqrt_price_x96(sqrt_price_x96);
+        let price = registry.pool_price(&WETH_USDC_POOL, raw).unwrap();
+        assert!(price.starts_with("2499.99999"), "{price}");
+
+        assert!(
+            registry
+                .pool_amounts(&Address::ZERO, U256::from(1), U256::from(1))
+                .is_empty()
+        );
+        assert!(registry.pool_price(&Address::ZERO, raw).is_none());
+    }
+
+    #[test]
+    fn test_annotated_serialization() {
+        #[derive(Serialize)]
+        struct Event {
+            amount: U256,
+        }
+
+        let registry = TokenRegistry::bundled();
+        let event = Event {
+            amount: U256::from(2_500_000),
+        };
+        let annotated = Annotated::new(
+            &event,
+            registry.amounts([("amount", USDC, event.amount.into())]),
+        );
+        let json = serde_json::to_value(&annotated).unwrap();
+        assert_eq!(json["amount"], "0x2625a0");
+        assert_eq!(json["tokens"][0]["symbol"], "USDC");
+        assert_eq!(json["tokens"][0]["amount"], "2.500000");
+        assert!(json.get("price").is_none());
+
+        let unknown = Annotated::new(&event, Vec::new());
+        let json = serde_json::to_value(&unknown).unwrap();
+        assert!(json.get("tokens").is_none());
+    }
+}
@@ -0,0 +1,367 @@
+use alloy_consensus::{Transaction, TxEnvelope, TxType as EnvelopeTxType};
+use alloy_eips::eip2718::{Decodable2718, Eip2718Error};
+use alloy_primitives::{Address, B256, Bytes, U256};
+use op_alloy_consensus::{DEPOSIT_TX_TYPE_ID, TxDeposit};
+use serde::Serialize;
+use thiserror::Error;
+
+use crate::flashblocks::FlashblockReceipt;
+
+/// EIP-2718 transaction type of a decoded transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxType {
+    Legacy,
+    Eip2930,
+    Eip1559,
+    Eip4844,
+    Eip7702,
+    /// OP Stack deposit transaction (type 0x7E)
+    Deposit,
+}
+
+/// Error decoding a transaction from the payload diff
+#[derive(Error, Debug)]
+pub enum TxDecodeError {
+    /// The bytes are not a valid EIP-2718 envelope
+    #[error("invalid transaction envelope: {0}")]
+    Envelope(Eip2718Error),
+    /// The signature does not recover to an address
+    #[error("signer recovery failed: {0}")]
+    Signature(String),
+}
+
+/// Fields specific to OP Stack deposit transactions
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DepositFields {
+    /// Hash that uniquely identifies the L1 origin of the deposit
+    pub source_hash: B256,
+    /// ETH minted on L2
+    pub mint: Option<u128>,
+    /// Whether this is a system transaction
+    pub is_system_transaction: bool,
+}
+
+/// A transaction decoded from `ExecutionPayloadDiff::transactions`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DecodedTransaction {
+    /// Transaction hash
+    pub hash: B256,
+    /// Transaction type
+    pub tx_type: TxType,
+    /// Sender, recovered from the signature (or the `from` field of deposits)
+    pub from: Address,
+    /// Recipient, `None` for contract creation
+    pub to: Option<Address>,
+    /// ETH value transferred
+    pub value: U256,
+    /// Sender nonce (deposits have none)
+    pub nonce: Option<u64>,
+    /// Gas limit
+    pub gas_limit: u64,
+    /// Gas price (legacy and EIP-2930 only)
+    pub gas_price: Option<u128>,
+    /// Max fee per gas (EIP-1559 and later)
+    pub max_fee_per_gas: Option<u128>,
+    /// Max priority fee per gas (EIP-1559 and later)
+    pub max_priority_fee_per_gas: Option<u128>,
+    /// Max fee per blob gas (EIP-4844 only)
+    pub max_fee_per_blob_gas: Option<u128>,
+    /// Calldata
+    pub input: Bytes,
+    /// Deposit-only fields
+    pub deposit: Option<DepositFields>,
+}
+
+impl DecodedTransaction {
+    /// Decode an EIP-2718 encoded transaction and recover its sender
+    pub fn decode(raw: &[u8]) -> Result<Self, TxDecodeError> {
+        if raw.first() == Some(&DEPOSIT_TX_TYPE_ID) {
+            let tx = TxDeposit::decode_2718(&mut &raw[..]).map_err(TxDecodeError::Envelope)?;
+            return Ok(Self::from_deposit(&tx));
+        }
+
+        let envelope = TxEnvelope::decode_2718(&mut &raw[..]).map_err(TxDecodeError::Envelope)?;
+        let from = envelope
+            .recover_signer()
+            .map_err(|e| TxDecodeError::Signature(e.to_string()))?;
+
+        let tx_type = match envelope.tx_type() {
+            EnvelopeTxType::Legacy => TxType::Legacy,
+            EnvelopeTxType::Eip2930 => TxType::Eip2930,
+            EnvelopeTxType::Eip1559 => TxType::Eip1559,
+            EnvelopeTxType::Eip4844 => TxType::Eip4844,
+            EnvelopeTxType::Eip7702 => TxType::Eip7702,
+        };
+
+        Ok(Self {
+            hash: *envelope.tx_hash(),
+            tx_type,
+            from,
+            to: envelope.to(),
+            value: envelope.value(),
+            nonce: Some(envelope.nonce()),
+            gas_limit: envelope.gas_limit(),
+            gas_price: envelope.gas_price(),
+            max_fee_per_gas: envelope
+                .is_dynamic_fee()
+                .then(|| envelope.max_fee_per_gas()),
+            max_priority_fee_per_gas: envelope.max_priority_fee_per_gas(),
+            max_fee_per_blob_gas: envelope.max_fee_per_blob_gas(),
+            input: envelope.input().clone(),
+            deposit: None,
+        })
+    }
+
+    fn from_deposit(tx: &TxDeposit) -> Self {
+        Self {
+            hash: tx.tx_hash(),
+            tx_type: TxType::Deposit,
+            from: tx.from,
+            to: tx.to.to().copied(),
+            value: tx.value,
+            nonce: None,
+            gas_limit: tx.gas_limit,
+            gas_price: None,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            max_fee_per_blob_gas: None,
+            input: tx.input.clone(),
+            deposit: Some(DepositFields {
+                source_hash: tx.source_hash,
+                mint: tx.mint,
+                is_system_transaction: tx.is_system_transaction,
+            }),
+        }
+    }
+
+    /// Decode every transaction in a payload diff, keeping failures in place
+    pub fn decode_all(raw: &[Bytes]) -> Vec<Result<Self, TxDecodeError>> {
+        raw.iter().map(|tx| Self::decode(tx)).collect()
+    }
+}
+
+/// A decoded transaction joined with its receipt from the flashblock metadata
+#[derive(Debug)]
+pub struct TransactionWithReceipt<'a> {
+    pub transaction: DecodedTransaction,
+    /// Receipt for this transaction, if the metadata carried one
+    pub receipt: Option<&'a FlashblockReceipt>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_consensus::{
+        SignableTransaction, TxEip1559, TxEip2930, TxEip4844, TxEip7702, TxLegacy,
+    };
+    use alloy_eips::eip2718::Encodable2718;
+    use alloy_eips::eip2930::{AccessList, AccessListItem};
+    use alloy_eips::eip7702::Authorization;
+    use alloy_primitives::TxKind;
+    use alloy_signer::SignerSync;
+    use alloy_signer_local::PrivateKeySigner;
+
+    fn sign<T: SignableTransaction<alloy_primitives::PrimitiveSignature>>(
+        signer: &PrivateKeySigner,
+        tx: T,
+    ) -> alloy_consensus::Signed<T> {
+        let signature = signer.sign_hash_sync(&tx.signature_hash()).unwrap();
+        tx.into_signed(signature)
+    }
+
+    /// Encode a signed envelope, decode it back and check the common fields
+    fn round_trip(
+        signer: &PrivateKeySigner,
+        envelope: TxEnvelope,
+        tx_type: TxType,
+    ) -> DecodedTransaction {
+        let decoded = DecodedTransaction::decode(&envelope.encoded_2718()).unwrap();
+        assert_eq!(decoded.hash, *envelope.tx_hash());
+        assert_eq!(decoded.tx_type, tx_type);
+        assert_eq!(decoded.from, signer.address());
+        assert_eq!(decoded.to, envelope.to());
+        assert_eq!(decoded.value, envelope.value());
+        assert_eq!(decoded.nonce, Some(envelope.nonce()));
+        assert_eq!(decoded.gas_limit, envelope.gas_limit());
+        assert_eq!(decoded.input, *envelope.input());
+        decoded
+    }
+
+    fn access_list() -> AccessList {
+        AccessList(vec![AccessListItem {
+            address: Address::repeat_byte(0x22),
+            storage_keys: vec![B256::repeat_byte(0x01)],
+        }])
+    }
+
+    #[test]
+    fn test_round_trip_eip2930() {
+        let signer = PrivateKeySigner::random();
+        let tx = TxEip2930 {
+            chain_id: 84532,
+            nonce: 3,
+            gas_price: 1_500_000_000,
+            gas_limit: 60_000,
+            to: TxKind::Call(Address::repeat_byte(0x11)),
+            value: U256::from(5u64),
+            access_list: access_list(),
+            input: Bytes::from_static(&[0x01, 0x02]),
+        };
+        let envelope = TxEnvelope::from(sign(&signer, tx));
+
+        let decoded = round_trip(&signer, envelope, TxType::Eip2930);
+        assert_eq!(decoded.gas_price, Some(1_500_000_000));
+        assert_eq!(decoded.max_fee_per_gas, None);
+    }
+
+    #[test]
+    fn test_round_trip_eip4844() {
+        let signer = PrivateKeySigner::random();
+        let tx = TxEip4844 {
+            chain_id: 84532,
+            nonce: 9,
+            gas_limit: 100_000,
+            max_fee_per_gas: 3_000_000_000,
+            max_priority_fee_per_gas: 2_000_000,
+            to: Address::repeat_byte(0x33),
+            value: U256::ZERO,
+            access_list: access_list(),
+            blob_versioned_hashes: vec![B256::repeat_byte(0x01)],
+            max_fee_per_blob_gas: 1,
+            input: Bytes::from_static(&[0xab]),
+        };
+        let envelope = TxEnvelope::from(sign(&signer, tx));
+
+        let decoded = round_trip(&signer, envelope, TxType::Eip4844);
+        assert_eq!(decoded.max_fee_per_gas, Some(3_000_000_000));
+        assert_eq!(decoded.max_priority_fee_per_gas, Some(2_000_000));
+    }
+
+    #[test]
+    fn test_round_trip_eip7702() {
+        let signer = PrivateKeySigner::random();
+        let authorization = Authorization {
+            chain_id: 84532,
+            address: Address::repeat_byte(0x44),
+            nonce: 1,
+        };
+        let signature = signer
+            .sign_hash_sync(&authorization.signature_hash())
+            .unwrap();
+        let tx = TxEip7702 {
+            chain_id: 84532,
+            nonce: 12,
+            gas_limit: 80_000,
+            max_fee_per_gas: 2_500_000_000,
+            max_priority_fee_per_gas: 1_500_000,
+            to: signer.address(),
+            value: U256::ZERO,
+            access_list: AccessList::default(),
+            authorization_list: vec![authorization.into_signed(signature)],
+            input: Bytes::from_static(&[0xcd, 0xef]),
+        };
+        let envelope = TxEnvelope::from(sign(&signer, tx));
+
+        let decoded = round_trip(&signer, envelope, TxType::Eip7702);
+        assert_eq!(decoded.to, Some(signer.address()));
+        assert_eq!(decoded.max_fee_per_gas, Some(2_500_000_000));
+    }
+
+    #[test]
+    fn test_decode_eip1559() {
+        let signer = PrivateKeySigner::random();
+        let to = Address::repeat_byte(0x11);
+        let tx = TxEip1559 {
+            chain_id: 84532,
+            nonce: 7,
+            gas_limit: 21_000,
+            max_fee_per_gas: 2_000_000_000,
+            max_priority_fee_per_gas: 1_000_000,
+            to: TxKind::Call(to),
+            value: U256::from(1_000u64),
+            input: Bytes::from_static(&[0xde, 0xad, 0xbe, 0xef]),
+            ..Default::default()
+        };
+        let envelope = TxEnvelope::from(sign(&signer, tx));
+        let raw = envelope.encoded_2718();
+
+        let decoded = DecodedTransaction::decode(&raw).unwrap();
+        assert_eq!(decoded.hash, *envelope.tx_hash());
+        assert_eq!(decoded.tx_type, TxType::Eip1559);
+        assert_eq!(decoded.from, signer.address());
+        assert_eq!(decoded.to, Some(to));
+        assert_eq!(decoded.nonce, Some(7));
+        assert_eq!(decoded.gas_limit, 21_000);
+        assert_eq!(decoded.gas_price, None);
+        assert_eq!(decoded.max_fee_per_gas, Some(2_000_000_000));
+        assert_eq!(decoded.max_priority_fee_per_gas, Some(1_000_000));
+        assert_eq!(decoded.input.as_ref(), &[0xde, 0xad, 0xbe, 0xef]);
+    }
+
+    #[test]
+    fn test_decode_legacy_create() {
+        let signer = PrivateKeySigner::random();
+        let tx = TxLegacy {
+            chain_id: Some(84532),
+            nonce: 0,
+            gas_price: 1_000_000_000,
+            gas_limit: 500_000,
+            to: TxKind::Create,
+            value: U256::ZERO,
+            input: Bytes::from_static(&[0x60, 0x80]),
+        };
+        let raw = TxEnvelope::from(sign(&signer, tx)).encoded_2718();
+
+        let decoded = DecodedTransaction::decode(&raw).unwrap();
+        assert_eq!(decoded.tx_type, TxType::Legacy);
+        assert_eq!(decoded.from, signer.address());
+        assert_eq!(decoded.to, None);
+        assert_eq!(decoded.gas_price, Some(1_000_000_000));
+        assert_eq!(decoded.max_fee_per_gas, None);
+    }
+
+    #[test]
+    fn test_decode_deposit() {
+        let tx = TxDeposit {
+            source_hash: B256::repeat_byte(0x42),
+            from: "0xDeaDDEaDDeAdDeAdDEAdDEaddeAddEAdDEAd0001"
+                .parse()
+                .unwrap(),
+            to: TxKind::Call(
+                "0x4200000000000000000000000000000000000015"
+                    .parse()
+                    .unwrap(),
+            ),
+            mint: None,
+            value: U256::ZERO,
+            gas_limit: 1_000_000,
+            is_system_transaction: false,
+            input: Bytes::from_static(&[0x44, 0x0a, 0x5e, 0x20]),
+        };
+        let raw = tx.encoded_2718();
+
+        let decoded = DecodedTransaction::decode(&raw).unwrap();
+        assert_eq!(decoded.hash, tx.tx_hash());
+        assert_eq!(decoded.tx_type, TxType::Deposit);
+        assert_eq!(decoded.from, tx.from);
+        assert_eq!(decoded.nonce, None);
+        assert_eq!(
+            decoded.deposit.as_ref().map(|d| d.source_hash),
+            Some(tx.source_hash)
+        );
+    }
+
+    #[test]
+    fn test_decode_garbage() {
+        assert!(matches!(
+            DecodedTransaction::decode(&[0x02, 0x01]),
+            Err(TxDecodeError::Envelope(_))
+        ));
+        let err = DecodedTransaction::decode(&[0x02, 0x01]).unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("invalid transaction envelope: ")
+        );
+    }
+}
@@ -0,0 +1,350 @@
+use alloy_primitives::{Address, B256, Bloom, BloomInput, U256};
+use alloy_sol_types::{SolEvent, sol};
+use serde::Serialize;
+
+use crate::flashblocks::{ContextLog, EventContext, ReceiptLog};
+use crate::price::Price;
+
+// Uniswap V2 Pair events, shared by most V2 forks
+sol! {
+    /// Emitted by the pair for any swaps between token0 and token1
+    event Swap(
+        address indexed sender,
+        uint256 amount0In,
+        uint256 amount1In,
+        uint256 amount0Out,
+        uint256 amount1Out,
+        address indexed to
+    );
+
+    /// Emitted by the pair whenever its reserves change
+    event Sync(uint112 reserve0, uint112 reserve1);
+}
+
+// Aerodrome / Velodrome V2 Pool events. Same data, different signatures.
+sol! {
+    interface AerodromePool {
+        /// Emitted by the pool for any swaps between token0 and token1
+        event Swap(
+            address indexed sender,
+            address indexed to,
+            uint256 amount0In,
+            uint256 amount1In,
+            uint256 amount0Out,
+            uint256 amount1Out
+        );
+
+        /// Emitted by the pool whenever its reserves change
+        event Sync(uint256 reserve0, uint256 reserve1);
+    }
+}
+
+/// Which V2 event layout a pool emitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum V2PoolKind {
+    /// Uniswap V2 and its forks
+    UniswapV2,
+    /// Aerodrome / Velodrome V2 volatile and stable pools
+    Aerodrome,
+}
+
+/// Detected V2-style pool events based on bloom filter
+#[derive(Debug, Default)]
+pub struct UniV2Events {
+    pub may_have_swap: bool,
+    pub may_have_sync: bool,
+}
+
+impl UniV2Events {
+    /// Check the bloom filter for potential V2-style pool events, of either layout.
+    /// Note: Bloom filters can have false positives but no false negatives.
+    pub fn from_bloom(bloom: &Bloom) -> Self {
+        let contains = |topic: B256| bloom.contains_input(BloomInput::Raw(topic.as_slice()));
+        Self {
+            may_have_swap: contains(Swap::SIGNATURE_HASH)
+                || contains(AerodromePool::Swap::SIGNATURE_HASH),
+            may_have_sync: contains(Sync::SIGNATURE_HASH)
+                || contains(AerodromePool::Sync::SIGNATURE_HASH),
+        }
+    }
+
+    /// Returns true if any V2-style pool event might be present
+    pub fn any(&self) -> bool {
+        self.may_have_swap || self.may_have_sync
+    }
+}
+
+/// A decoded V2-style Swap event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedV2Swap {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// The pool contract that emitted the event
+    pub pool: Address,
+    /// Which event layout the pool uses
+    pub kind: V2PoolKind,
+    /// The address that initiated the swap
+    pub sender: Address,
+    /// The address that received the output
+    pub to: Address,
+    /// Amount of token0 sent into the pool
+    pub amount0_in: U256,
+    /// Amount of token1 sent into the pool
+    pub amount1_in: U256,
+    /// Amount of token0 sent out of the pool
+    pub amount0_out: U256,
+    /// Amount of token1 sent out of the pool
+    pub amount1_out: U256,
+}
+
+impl ParsedV2Swap {
+    /// Try to parse a Uniswap V2 or Aerodrome Swap event from a log
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 3 {
+            return None;
+        }
+
+        let topics = log.topics.iter().copied();
+        let (kind, sender, to, (amount0_in, amount1_in, amount0_out, amount1_out)) =
+            if log.topics[0] == Swap::SIGNATURE_HASH {
+                let d = Swap::decode_raw_log(topics, &log.data, true).ok()?;
+                let amounts = (d.amount0In, d.amount1In, d.amount0Out, d.amount1Out);
+                (V2PoolKind::UniswapV2, d.sender, d.to, amounts)
+            } else if log.topics[0] == AerodromePool::Swap::SIGNATURE_HASH {
+                let d = AerodromePool::Swap::decode_raw_log(topics, &log.data, true).ok()?;
+                let amounts = (d.amount0In, d.amount1In, d.amount0Out, d.amount1Out);
+                (V2PoolKind::Aerodrome, d.sender, d.to, amounts)
+            } else {
+                return None;
+            };
+
+        Some(Self {
+            context: context.clone(),
+            pool: log.address,
+            kind,
+            sender,
+            to,
+            amount0_in,
+            amount1_in,
+            amount0_out,
+            amount1_out,
+        })
+    }
+
+    /// Extract all V2-style Swap events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// A decoded V2-style Sync event: the pool's reserves after a change
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedV2Sync {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// The pool contract that emitted the event
+    pub pool: Address,
+    /// Which event layout the pool uses
+    pub kind: V2PoolKind,
+    /// Reserve of token0
+    pub reserve0: U256,
+    /// Reserve of token1
+    pub reserve1: U256,
+}
+
+impl ParsedV2Sync {
+    /// Try to parse a Uniswap V2 or Aerodrome Sync event from a log
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 1 {
+            return None;
+        }
+
+        let topics = log.topics.iter().copied();
+        let (kind, reserve0, reserve1) = if log.topics[0] == Sync::SIGNATURE_HASH {
+            let d = Sync::decode_raw_log(topics, &log.data, true).ok()?;
+            (
+                V2PoolKind::UniswapV2,
+                U256::from(d.reserve0),
+                U256::from(d.reserve1),
+            )
+        } else if log.topics[0] == AerodromePool::Sync::SIGNATURE_HASH {
+            let d = AerodromePool::Sync::decode_raw_log(topics, &log.data, true).ok()?;
+            (V2PoolKind::Aerodrome, d.reserve0, d.reserve1)
+        } else {
+            return None;
+        };
+
+        Some(Self {
+            context: context.clone(),
+            pool: log.address,
+            kind,
+            reserve0,
+            reserve1,
+        })
+    }
+
+    /// Extract all V2-style Sync events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+
+    /// Exact spot price of token0 in token1 (raw units): `reserve1 / reserve0`.
+    /// For Aerodrome stable pools this is the reserve ratio, not the marginal price.
+    pub fn spot_price(&self) -> Option<Price> {
+        Price::new(self.reserve1, self.reserve0)
+    }
+
+    /// Pool state after this Sync, with the spot price in both directions
+    pub fn pool_state(&self) -> V2PoolState {
+        V2PoolState {
+            context: self.context.clone(),
+            pool: self.pool,
+            kind: self.kind,
+            reserve0: self.reserve0,
+            reserve1: self.reserve1,
+            price_0_in_1: self.spot_price().map(Price::to_f64),
+            price_1_in_0: Price::new(self.reserve0, self.reserve1).map(Price::to_f64),
+        }
+    }
+}
+
+/// V2-style pool reserves and spot price, derived from a Sync event
+#[derive(Debug, Clone, Serialize)]
+pub struct V2PoolState {
+    /// Where the Sync event was emitted
+    pub context: EventContext,
+    pub pool: Address,
+    pub kind: V2PoolKind,
+    pub reserve0: U256,
+    pub reserve1: U256,
+    /// Price of token0 in terms of token1 (raw units); none if reserve0 is empty
+    pub price_0_in_1: Option<f64>,
+    /// Price of token1 in terms of token0 (raw units); none if reserve1 is empty
+    pub price_1_in_0: Option<f64>,
+}
+
+/// Collection of V2-style pool events
+#[derive(Debug, Default)]
+pub struct UniV2Updates {
+    pub swaps: Vec<ParsedV2Swap>,
+    pub syncs: Vec<ParsedV2Sync>,
+}
+
+impl UniV2Updates {
+    /// Extract all V2-style pool events from logs
+    pub fn extract_all(logs: &[ContextLog]) -> Self {
+        Self {
+            swaps: ParsedV2Swap::extract_all(logs),
+            syncs: ParsedV2Sync::extract_all(logs),
+        }
+    }
+
+    /// Returns true if no events were found
+    pub fn is_empty(&self) -> bool {
+        self.swaps.is_empty() && self.syncs.is_empty()
+    }
+
+    /// Total count of all events
+    pub fn total_count(&self) -> usize {
+        self.swaps.len() + self.syncs.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flashblocks::test_logs::receipt_log;
+    use alloy_primitives::aliases::U112;
+
+    const PAIR: Address = Address::repeat_byte(0xaa);
+
+    #[test]
+    fn test_signatures() {
+        use alloy_primitives::keccak256;
+        assert_eq!(
+            Swap::SIGNATURE_HASH,
+            keccak256(b"Swap(address,uint256,uint256,uint256,uint256,address)")
+        );
+        assert_eq!(Sync::SIGNATURE_HASH, keccak256(b"Sync(uint112,uint112)"));
+        assert_eq!(
+            AerodromePool::Swap::SIGNATURE_HASH,
+            keccak256(b"Swap(address,address,uint256,uint256,uint256,uint256)")
+        );
+        assert_eq!(
+            AerodromePool::Sync::SIGNATURE_HASH,
+            keccak256(b"Sync(uint256,uint256)")
+        );
+    }
+
+    #[test]
+    fn test_parse_swaps_of_both_layouts() {
+        let uniswap = Swap {
+            sender: Address::repeat_byte(0x01),
+            amount0In: U256::from(10),
+            amount1In: U256::ZERO,
+            amount0Out: U256::ZERO,
+            amount1Out: U256::from(25),
+            to: Address::repeat_byte(0x02),
+        };
+        let aerodrome = AerodromePool::Swap {
+            sender: Address::repeat_byte(0x01),
+            to: Address::repeat_byte(0x02),
+            amount0In: U256::ZERO,
+            amount1In: U256::from(30),
+            amount0Out: U256::from(12),
+            amount1Out: U256::ZERO,
+        };
+
+        let context = EventContext::default();
+        let swap =
+            ParsedV2Swap::try_from_log(&receipt_log(PAIR, uniswap.encode_log_data()), &context)
+                .unwrap();
+        assert_eq!(swap.kind, V2PoolKind::UniswapV2);
+        assert_eq!(swap.to, uniswap.to);
+        assert_eq!(swap.amount0_in, U256::from(10));
+        assert_eq!(swap.amount1_out, U256::from(25));
+
+        let swap =
+            ParsedV2Swap::try_from_log(&receipt_log(PAIR, aerodrome.encode_log_data()), &context)
+                .unwrap();
+        assert_eq!(swap.kind, V2PoolKind::Aerodrome);
+        assert_eq!(swap.sender, aerodrome.sender);
+        assert_eq!(swap.to, aerodrome.to);
+        assert_eq!(swap.amount1_in, U256::from(30));
+        assert_eq!(swap.amount0_out, U256::from(12));
+    }
+
+    #[test]
+    fn test_sync_pool_state() {
+        let context = EventContext::default();
+        let uniswap = Sync {
+            reserve0: U112::from(4_000),
+            reserve1: U112::from(1_000),
+        };
+        let state =
+            ParsedV2Sync::try_from_log(&receipt_log(PAIR, uniswap.encode_log_data()), &context)
+                .unwrap()
+                .pool_state();
+        assert_eq!(state.kind, V2PoolKind::UniswapV2);
+        assert_eq!(state.price_0_in_1, Some(0.25));
+        assert_eq!(state.price_1_in_0, Some(4.0));
+
+        // Reserves above u128 and an empty side don't panic
+        let aerodrome = AerodromePool::Sync {
+            reserve0: U256::ZERO,
+            reserve1: U256::MAX,
+        };
+        let state =
+            ParsedV2Sync::try_from_log(&receipt_log(PAIR, aerodrome.encode_log_data()), &context)
+                .unwrap()
+                .pool_state();
+        assert_eq!(state.kind, V2PoolKind::Aerodrome);
+        assert_eq!(state.price_0_in_1, None);
+        assert_eq!(state.price_1_in_0, Some(0.0));
+    }
+}
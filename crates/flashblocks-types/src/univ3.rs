@@ -1,8 +1,10 @@
-use alloy_primitives::{Address, Bloom, BloomInput, I256, LogData, U160};
+use alloy_primitives::{Address, Bloom, BloomInput, I256, LogData, U160, U256};
 use alloy_sol_types::{SolEvent, sol};
 use serde::Serialize;
 
-use crate::flashblocks::ReceiptLog;
+use crate::dex;
+use crate::flashblocks::{ContextLog, EventContext, ReceiptLog};
+use crate::price::Price;
 
 // Uniswap V3 Pool events
 sol! {
@@ -57,6 +59,43 @@ sol! {
         uint256 paid0,
         uint256 paid1
     );
+
+    /// Emitted by the factory when a pool is created (also Sushi V3 and PancakeSwap V3)
+    event PoolCreated(
+        address indexed token0,
+        address indexed token1,
+        uint24 indexed fee,
+        int24 tickSpacing,
+        address pool
+    );
+}
+
+// V3 forks whose events differ from Uniswap's
+sol! {
+    interface PancakeV3Pool {
+        /// Uniswap's Swap plus the protocol fees taken in each token
+        event Swap(
+            address indexed sender,
+            address indexed recipient,
+            int256 amount0,
+            int256 amount1,
+            uint160 sqrtPriceX96,
+            uint128 liquidity,
+            int24 tick,
+            uint128 protocolFeesToken0,
+            uint128 protocolFeesToken1
+        );
+    }
+
+    interface SlipstreamFactory {
+        /// Slipstream pools are keyed by tick spacing instead of fee
+        event PoolCreated(
+            address indexed token0,
+            address indexed token1,
+            int24 indexed tickSpacing,
+            address pool
+        );
+    }
 }
 
 /// Detected UniswapV3 events based on bloom filter
@@ -67,6 +106,7 @@ pub struct UniV3Events {
     pub may_have_burn: bool,
     pub may_have_swap: bool,
     pub may_have_flash: bool,
+    pub may_have_pool_created: bool,
 }
 
 impl UniV3Events {
@@ -74,11 +114,20 @@ impl UniV3Events {
     /// Note: Bloom filters can have false positives but no false negatives.
     pub fn from_bloom(bloom: &Bloom) -> Self {
         Self {
-            may_have_mint: bloom.contains_input(BloomInput::Hash(Mint::SIGNATURE_HASH)),
-            may_have_collect: bloom.contains_input(BloomInput::Hash(Collect::SIGNATURE_HASH)),
-            may_have_burn: bloom.contains_input(BloomInput::Hash(Burn::SIGNATURE_HASH)),
-            may_have_swap: bloom.contains_input(BloomInput::Hash(Swap::SIGNATURE_HASH)),
-            may_have_flash: bloom.contains_input(BloomInput::Hash(Flash::SIGNATURE_HASH)),
+            may_have_mint: bloom.contains_input(BloomInput::Raw(Mint::SIGNATURE_HASH.as_slice())),
+            may_have_collect: bloom
+                .contains_input(BloomInput::Raw(Collect::SIGNATURE_HASH.as_slice())),
+            may_have_burn: bloom.contains_input(BloomInput::Raw(Burn::SIGNATURE_HASH.as_slice())),
+            may_have_swap: bloom.contains_input(BloomInput::Raw(Swap::SIGNATURE_HASH.as_slice()))
+                || bloom.contains_input(BloomInput::Raw(
+                    PancakeV3Pool::Swap::SIGNATURE_HASH.as_slice(),
+                )),
+            may_have_flash: bloom.contains_input(BloomInput::Raw(Flash::SIGNATURE_HASH.as_slice())),
+            may_have_pool_created: bloom
+                .contains_input(BloomInput::Raw(PoolCreated::SIGNATURE_HASH.as_slice()))
+                || bloom.contains_input(BloomInput::Raw(
+                    SlipstreamFactory::PoolCreated::SIGNATURE_HASH.as_slice(),
+                )),
         }
     }
 
@@ -89,6 +138,7 @@ impl UniV3Events {
             || self.may_have_burn
             || self.may_have_swap
             || self.may_have_flash
+            || self.may_have_pool_created
     }
 }
 
@@ -125,16 +175,27 @@ pub struct PoolState {
 }
 
 impl PoolState {
-    /// Calculate the price of token0 in terms of token1.
+    /// Exact price of token0 in terms of token1, in raw token units.
     /// price = (sqrtPriceX96 / 2^96)^2
+    pub fn price(&self) -> Price {
+        Price::from_sqrt_price_x96(self.sqrt_price_x96)
+    }
+
+    /// Exact price of one whole token0 in whole token1, given each token's decimals.
+    /// Returns `None` if the decimals are too far apart for the price.
+    pub fn human_price_0_in_1(&self, decimals0: u8, decimals1: u8) -> Option<Price> {
+        self.price().to_human(decimals0, decimals1)
+    }
+
+    /// Calculate the price of token0 in terms of token1.
     pub fn price_0_in_1(&self) -> f64 {
-        let sqrt_price = self.sqrt_price_x96.to::<u128>() as f64 / (2_u128.pow(96) as f64);
-        sqrt_price * sqrt_price
+        self.price().to_f64()
     }
 
     /// Calculate the price of token1 in terms of token0.
+    /// Infinite if the pool price is zero.
     pub fn price_1_in_0(&self) -> f64 {
-        1.0 / self.price_0_in_1()
+        self.price().invert().map_or(f64::INFINITY, Price::to_f64)
     }
 
     /// Get just the slot0 portion (sqrtPriceX96 and tick)
@@ -149,6 +210,8 @@ impl PoolState {
 /// A decoded Uniswap V3 Swap event with pool address
 #[derive(Debug, Clone, Serialize)]
 pub struct ParsedSwap {
+    /// Where the event was emitted
+    pub context: EventContext,
     /// The pool contract that emitted the event
     pub pool: Address,
     /// The address that initiated the swap
@@ -169,11 +232,24 @@ pub struct ParsedSwap {
     pub price_0_in_1: f64,
     /// Price of token1 in terms of token0
     pub price_1_in_0: f64,
+    /// The DEX the pool belongs to, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dex: Option<String>,
+    /// Protocol fee taken in token0 (PancakeSwap V3 only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol_fees_token0: Option<u128>,
+    /// Protocol fee taken in token1 (PancakeSwap V3 only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub protocol_fees_token1: Option<u128>,
 }
 
 impl ParsedSwap {
-    /// Try to decode a Swap event from a log
-    pub fn from_log(log: &ReceiptLog) -> Option<Self> {
+    /// Try to decode a Uniswap V3 or PancakeSwap V3 Swap event from a log
+    pub fn from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.first() == Some(&PancakeV3Pool::Swap::SIGNATURE_HASH) {
+            return Self::from_pancake_log(log, context);
+        }
+
         // Check if this log matches the Swap event signature
         if log.topics.first() != Some(&Swap::SIGNATURE_HASH) {
             return None;
@@ -193,6 +269,7 @@ impl ParsedSwap {
         };
 
         Some(Self {
+            context: context.clone(),
             pool: log.address,
             sender: decoded.sender,
             recipient: decoded.recipient,
@@ -203,12 +280,51 @@ impl ParsedSwap {
             tick: decoded.tick.as_i32(),
             price_0_in_1: pool_state.price_0_in_1(),
             price_1_in_0: pool_state.price_1_in_0(),
+            dex: None,
+            protocol_fees_token0: None,
+            protocol_fees_token1: None,
+        })
+    }
+
+    /// Decode PancakeSwap V3's extended Swap event
+    fn from_pancake_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 3 {
+            return None;
+        }
+
+        let decoded =
+            PancakeV3Pool::Swap::decode_raw_log(log.topics.iter().copied(), &log.data, true)
+                .ok()?;
+
+        let pool_state = PoolState {
+            sqrt_price_x96: decoded.sqrtPriceX96,
+            tick: decoded.tick.as_i32(),
+            liquidity: decoded.liquidity,
+        };
+
+        Some(Self {
+            context: context.clone(),
+            pool: log.address,
+            sender: decoded.sender,
+            recipient: decoded.recipient,
+            amount0: decoded.amount0,
+            amount1: decoded.amount1,
+            sqrt_price_x96: decoded.sqrtPriceX96,
+            liquidity: decoded.liquidity,
+            tick: decoded.tick.as_i32(),
+            price_0_in_1: pool_state.price_0_in_1(),
+            price_1_in_0: pool_state.price_1_in_0(),
+            dex: Some(dex::PANCAKESWAP_V3.to_string()),
+            protocol_fees_token0: Some(decoded.protocolFeesToken0),
+            protocol_fees_token1: Some(decoded.protocolFeesToken1),
         })
     }
 
     /// Extract all Swap events from a list of logs
-    pub fn extract_all(logs: &[ReceiptLog]) -> Vec<Self> {
-        logs.iter().filter_map(Self::from_log).collect()
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::from_log(&l.log, &l.context))
+            .collect()
     }
 
     /// Get the pool state after this swap (sqrtPriceX96, tick, and liquidity)
@@ -220,3 +336,418 @@ impl ParsedSwap {
         }
     }
 }
+
+/// A decoded Uniswap V3 Mint event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedMint {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// The pool contract that emitted the event
+    pub pool: Address,
+    /// The address that minted the liquidity
+    pub sender: Address,
+    /// The owner of the position
+    pub owner: Address,
+    /// The lower tick of the position
+    pub tick_lower: i32,
+    /// The upper tick of the position
+    pub tick_upper: i32,
+    /// The amount of liquidity minted
+    pub amount: u128,
+    /// Amount of token0 paid for the liquidity
+    pub amount0: U256,
+    /// Amount of token1 paid for the liquidity
+    pub amount1: U256,
+}
+
+impl ParsedMint {
+    /// Try to parse a Mint event from a log entry
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        // Mint has 3 indexed parameters (owner, tickLower, tickUpper)
+        if log.topics.len() != 4 {
+            return None;
+        }
+
+        if log.topics[0] != Mint::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded = Mint::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            pool: log.address,
+            sender: decoded.sender,
+            owner: decoded.owner,
+            tick_lower: decoded.tickLower.as_i32(),
+            tick_upper: decoded.tickUpper.as_i32(),
+            amount: decoded.amount,
+            amount0: decoded.amount0,
+            amount1: decoded.amount1,
+        })
+    }
+
+    /// Extract all Mint events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// A decoded Uniswap V3 Burn event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedBurn {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// The pool contract that emitted the event
+    pub pool: Address,
+    /// The owner of the position
+    pub owner: Address,
+    /// The lower tick of the position
+    pub tick_lower: i32,
+    /// The upper tick of the position
+    pub tick_upper: i32,
+    /// The amount of liquidity removed
+    pub amount: u128,
+    /// Amount of token0 owed to the position
+    pub amount0: U256,
+    /// Amount of token1 owed to the position
+    pub amount1: U256,
+}
+
+impl ParsedBurn {
+    /// Try to parse a Burn event from a log entry
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        // Burn has 3 indexed parameters (owner, tickLower, tickUpper)
+        if log.topics.len() != 4 {
+            return None;
+        }
+
+        if log.topics[0] != Burn::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded = Burn::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            pool: log.address,
+            owner: decoded.owner,
+            tick_lower: decoded.tickLower.as_i32(),
+            tick_upper: decoded.tickUpper.as_i32(),
+            amount: decoded.amount,
+            amount0: decoded.amount0,
+            amount1: decoded.amount1,
+        })
+    }
+
+    /// Extract all Burn events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// A decoded Uniswap V3 Collect event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedCollect {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// The pool contract that emitted the event
+    pub pool: Address,
+    /// The owner of the position
+    pub owner: Address,
+    /// The address that received the fees
+    pub recipient: Address,
+    /// The lower tick of the position
+    pub tick_lower: i32,
+    /// The upper tick of the position
+    pub tick_upper: i32,
+    /// Amount of token0 collected
+    pub amount0: u128,
+    /// Amount of token1 collected
+    pub amount1: u128,
+}
+
+impl ParsedCollect {
+    /// Try to parse a Collect event from a log entry
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        // Collect has 3 indexed parameters (owner, tickLower, tickUpper)
+        if log.topics.len() != 4 {
+            return None;
+        }
+
+        if log.topics[0] != Collect::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded = Collect::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            pool: log.address,
+            owner: decoded.owner,
+            recipient: decoded.recipient,
+            tick_lower: decoded.tickLower.as_i32(),
+            tick_upper: decoded.tickUpper.as_i32(),
+            amount0: decoded.amount0,
+            amount1: decoded.amount1,
+        })
+    }
+
+    /// Extract all Collect events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// A decoded Uniswap V3 Flash event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedFlash {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// The pool contract that emitted the event
+    pub pool: Address,
+    /// The address that initiated the flash loan
+    pub sender: Address,
+    /// The address that received the tokens
+    pub recipient: Address,
+    /// Amount of token0 borrowed
+    pub amount0: U256,
+    /// Amount of token1 borrowed
+    pub amount1: U256,
+    /// Amount of token0 paid back, including the fee
+    pub paid0: U256,
+    /// Amount of token1 paid back, including the fee
+    pub paid1: U256,
+}
+
+impl ParsedFlash {
+    /// Try to parse a Flash event from a log entry
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        // Flash has 2 indexed parameters (sender, recipient)
+        if log.topics.len() != 3 {
+            return None;
+        }
+
+        if log.topics[0] != Flash::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded = Flash::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            pool: log.address,
+            sender: decoded.sender,
+            recipient: decoded.recipient,
+            amount0: decoded.amount0,
+            amount1: decoded.amount1,
+            paid0: decoded.paid0,
+            paid1: decoded.paid1,
+        })
+    }
+
+    /// Extract all Flash events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// A decoded V3-style factory PoolCreated event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedPoolCreated {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// The factory that emitted the event
+    pub factory: Address,
+    /// The new pool
+    pub pool: Address,
+    pub token0: Address,
+    pub token1: Address,
+    /// The pool fee in hundredths of a bip; none for Slipstream, which keys pools by tick spacing
+    pub fee: Option<u32>,
+    pub tick_spacing: i32,
+    /// The DEX the factory belongs to, if known
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dex: Option<String>,
+}
+
+impl ParsedPoolCreated {
+    /// Try to parse a Uniswap V3-style or Slipstream PoolCreated event from a log
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 4 {
+            return None;
+        }
+
+        let topics = log.topics.iter().copied();
+        let (token0, token1, fee, tick_spacing, pool) = if log.topics[0]
+            == PoolCreated::SIGNATURE_HASH
+        {
+            let d = PoolCreated::decode_raw_log(topics, &log.data, true).ok()?;
+            (d.token0, d.token1, Some(d.fee.to()), d.tickSpacing, d.pool)
+        } else if log.topics[0] == SlipstreamFactory::PoolCreated::SIGNATURE_HASH {
+            let d = SlipstreamFactory::PoolCreated::decode_raw_log(topics, &log.data, true).ok()?;
+            (d.token0, d.token1, None, d.tickSpacing, d.pool)
+        } else {
+            return None;
+        };
+
+        Some(Self {
+            context: context.clone(),
+            factory: log.address,
+            pool,
+            token0,
+            token1,
+            fee,
+            tick_spacing: tick_spacing.as_i32(),
+            dex: None,
+        })
+    }
+
+    /// Extract all PoolCreated events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// All Uniswap V3 pool events extracted from logs
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct UniV3Updates {
+    pub swaps: Vec<ParsedSwap>,
+    pub mints: Vec<ParsedMint>,
+    pub burns: Vec<ParsedBurn>,
+    pub collects: Vec<ParsedCollect>,
+    pub flashes: Vec<ParsedFlash>,
+    pub pools_created: Vec<ParsedPoolCreated>,
+}
+
+impl UniV3Updates {
+    /// Extract all UniV3 pool events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Self {
+        Self {
+            swaps: ParsedSwap::extract_all(logs),
+            mints: ParsedMint::extract_all(logs),
+            burns: ParsedBurn::extract_all(logs),
+            collects: ParsedCollect::extract_all(logs),
+            flashes: ParsedFlash::extract_all(logs),
+            pools_created: ParsedPoolCreated::extract_all(logs),
+        }
+    }
+
+    /// Returns true if no UniV3 events were found
+    pub fn is_empty(&self) -> bool {
+        self.swaps.is_empty()
+            && self.mints.is_empty()
+            && self.burns.is_empty()
+            && self.collects.is_empty()
+            && self.flashes.is_empty()
+            && self.pools_created.is_empty()
+    }
+
+    /// Total count of all events
+    pub fn total_count(&self) -> usize {
+        self.swaps.len()
+            + self.mints.len()
+            + self.burns.len()
+            + self.collects.len()
+            + self.flashes.len()
+            + self.pools_created.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flashblocks::test_logs::receipt_log;
+    use alloy_primitives::aliases::I24;
+
+    #[test]
+    fn test_univ3_events_default() {
+        let events = UniV3Events::default();
+        assert!(!events.any());
+    }
+
+    #[test]
+    fn test_mint_signature() {
+        let expected_sig = alloy_primitives::keccak256(
+            b"Mint(address,address,int24,int24,uint128,uint256,uint256)",
+        );
+        assert_eq!(Mint::SIGNATURE_HASH, expected_sig);
+    }
+
+    #[test]
+    fn test_burn_signature() {
+        let expected_sig =
+            alloy_primitives::keccak256(b"Burn(address,int24,int24,uint128,uint256,uint256)");
+        assert_eq!(Burn::SIGNATURE_HASH, expected_sig);
+    }
+
+    #[test]
+    fn test_collect_signature() {
+        let expected_sig =
+            alloy_primitives::keccak256(b"Collect(address,address,int24,int24,uint128,uint128)");
+        assert_eq!(Collect::SIGNATURE_HASH, expected_sig);
+    }
+
+    #[test]
+    fn test_flash_signature() {
+        let expected_sig =
+            alloy_primitives::keccak256(b"Flash(address,address,uint256,uint256,uint256,uint256)");
+        assert_eq!(Flash::SIGNATURE_HASH, expected_sig);
+    }
+
+    #[test]
+    fn test_parse_mint_negative_ticks() {
+        let event = Mint {
+            sender: Address::repeat_byte(0x01),
+            owner: Address::repeat_byte(0x02),
+            tickLower: I24::try_from(-887_220).unwrap(),
+            tickUpper: I24::try_from(-60).unwrap(),
+            amount: 1_000_000,
+            amount0: U256::from(5),
+            amount1: U256::from(7),
+        };
+        let data = event.encode_log_data();
+        let log = receipt_log(Address::repeat_byte(0xaa), data);
+
+        let mint = ParsedMint::try_from_log(&log, &EventContext::default()).unwrap();
+        assert_eq!(mint.pool, log.address);
+        assert_eq!(mint.owner, event.owner);
+        assert_eq!(mint.tick_lower, -887_220);
+        assert_eq!(mint.tick_upper, -60);
+        assert_eq!(mint.amount, 1_000_000);
+
+        assert!(ParsedBurn::try_from_log(&log, &EventContext::default()).is_none());
+    }
+
+    #[test]
+    fn test_parse_swap_above_u128_price() {
+        let sqrt_price_x96 = crate::price::MAX_SQRT_RATIO - U160::from(1);
+        let event = Swap {
+            sender: Address::repeat_byte(0x01),
+            recipient: Address::repeat_byte(0x02),
+            amount0: I256::try_from(-5).unwrap(),
+            amount1: I256::try_from(7).unwrap(),
+            sqrtPriceX96: sqrt_price_x96,
+            liquidity: 1,
+            tick: I24::try_from(887_271).unwrap(),
+        };
+        let data = event.encode_log_data();
+        let log = receipt_log(Address::repeat_byte(0xaa), data);
+
+        let swap = ParsedSwap::from_log(&log, &EventContext::default()).unwrap();
+        assert_eq!(swap.sqrt_price_x96, sqrt_price_x96);
+        assert!(swap.price_0_in_1 > 3.4e38);
+        assert!(swap.price_1_in_0 > 0.0 && swap.price_1_in_0 < 3e-38);
+        assert_eq!(swap.pool_state().price().to_tick(), Some(887_271));
+    }
+}
@@ -0,0 +1,550 @@
+use std::collections::HashMap;
+
+use alloy_primitives::{Address, B256, Bloom, BloomInput, I256, U160, U256};
+use alloy_sol_types::{SolEvent, sol};
+use serde::Serialize;
+
+use crate::flashblocks::{ContextLog, EventContext, ReceiptLog};
+use crate::univ3::PoolState;
+
+// Uniswap V4 PoolManager events. Every pool lives in the singleton
+// PoolManager and is identified by its PoolId (the hash of its PoolKey).
+sol! {
+    /// Emitted when a new pool is initialized
+    event Initialize(
+        bytes32 indexed id,
+        address indexed currency0,
+        address indexed currency1,
+        uint24 fee,
+        int24 tickSpacing,
+        address hooks,
+        uint160 sqrtPriceX96,
+        int24 tick
+    );
+
+    /// Emitted when a liquidity position is modified
+    event ModifyLiquidity(
+        bytes32 indexed id,
+        address indexed sender,
+        int24 tickLower,
+        int24 tickUpper,
+        int256 liquidityDelta,
+        bytes32 salt
+    );
+
+    /// Emitted for swaps between currency0 and currency1
+    event Swap(
+        bytes32 indexed id,
+        address indexed sender,
+        int128 amount0,
+        int128 amount1,
+        uint160 sqrtPriceX96,
+        uint128 liquidity,
+        int24 tick,
+        uint24 fee
+    );
+
+    /// Emitted for donations to in-range liquidity providers
+    event Donate(
+        bytes32 indexed id,
+        address indexed sender,
+        uint256 amount0,
+        uint256 amount1
+    );
+}
+
+/// Detected UniswapV4 events based on bloom filter
+#[derive(Debug, Default)]
+pub struct UniV4Events {
+    pub may_have_initialize: bool,
+    pub may_have_modify_liquidity: bool,
+    pub may_have_swap: bool,
+    pub may_have_donate: bool,
+}
+
+impl UniV4Events {
+    /// Check the bloom filter for potential UniV4 events.
+    /// Note: Bloom filters can have false positives but no false negatives.
+    pub fn from_bloom(bloom: &Bloom) -> Self {
+        Self {
+            may_have_initialize: bloom
+                .contains_input(BloomInput::Raw(Initialize::SIGNATURE_HASH.as_slice())),
+            may_have_modify_liquidity: bloom
+                .contains_input(BloomInput::Raw(ModifyLiquidity::SIGNATURE_HASH.as_slice())),
+            may_have_swap: bloom.contains_input(BloomInput::Raw(Swap::SIGNATURE_HASH.as_slice())),
+            may_have_donate: bloom
+                .contains_input(BloomInput::Raw(Donate::SIGNATURE_HASH.as_slice())),
+        }
+    }
+
+    /// Returns true if any UniV4 event might be present
+    pub fn any(&self) -> bool {
+        self.may_have_initialize
+            || self.may_have_modify_liquidity
+            || self.may_have_swap
+            || self.may_have_donate
+    }
+}
+
+/// The parameters that identify a V4 pool, learned from its Initialize event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct V4PoolKey {
+    /// The lower currency of the pool; the zero address is native ETH
+    pub currency0: Address,
+    /// The higher currency of the pool
+    pub currency1: Address,
+    /// The LP fee in hundredths of a bip, or the dynamic-fee flag
+    pub fee: u32,
+    pub tick_spacing: i32,
+    /// The pool's hooks contract, or the zero address for none
+    pub hooks: Address,
+}
+
+/// A decoded Uniswap V4 Initialize event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedV4Initialize {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// The PoolManager that emitted the event
+    pub pool_manager: Address,
+    pub pool_id: B256,
+    pub key: V4PoolKey,
+    /// The initial sqrt(price) as a Q64.96
+    pub sqrt_price_x96: U160,
+    /// The initial tick
+    pub tick: i32,
+}
+
+impl ParsedV4Initialize {
+    /// Try to parse an Initialize event from a log
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 4 {
+            return None;
+        }
+
+        if log.topics[0] != Initialize::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded =
+            Initialize::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            pool_manager: log.address,
+            pool_id: decoded.id,
+            key: V4PoolKey {
+                currency0: decoded.currency0,
+                currency1: decoded.currency1,
+                fee: decoded.fee.to(),
+                tick_spacing: decoded.tickSpacing.as_i32(),
+                hooks: decoded.hooks,
+            },
+            sqrt_price_x96: decoded.sqrtPriceX96,
+            tick: decoded.tick.as_i32(),
+        })
+    }
+
+    /// Extract all Initialize events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// A decoded Uniswap V4 Swap event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedV4Swap {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// The PoolManager that emitted the event
+    pub pool_manager: Address,
+    pub pool_id: B256,
+    /// The pool's key, if its Initialize event has been seen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool_key: Option<V4PoolKey>,
+    /// The address that initiated the swap
+    pub sender: Address,
+    /// Balance change of currency0 for the swapper (negative = paid into the pool).
+    /// Note the sign is the opposite of V3's `amount0`.
+    pub amount0: i128,
+    /// Balance change of currency1 for the swapper (negative = paid into the pool)
+    pub amount1: i128,
+    /// The sqrt(price) after the swap as a Q64.96
+    pub sqrt_price_x96: U160,
+    /// The liquidity after the swap
+    pub liquidity: u128,
+    /// The tick after the swap
+    pub tick: i32,
+    /// The swap fee in hundredths of a bip, including any hook override
+    pub fee: u32,
+    /// Price of currency0 in terms of currency1
+    pub price_0_in_1: f64,
+    /// Price of currency1 in terms of currency0
+    pub price_1_in_0: f64,
+}
+
+impl ParsedV4Swap {
+    /// Try to parse a Swap event from a log
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 3 {
+            return None;
+        }
+
+        if log.topics[0] != Swap::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded = Swap::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        // Build pool state to compute prices
+        let pool_state = PoolState {
+            sqrt_price_x96: decoded.sqrtPriceX96,
+            tick: decoded.tick.as_i32(),
+            liquidity: decoded.liquidity,
+        };
+
+        Some(Self {
+            context: context.clone(),
+            pool_manager: log.address,
+            pool_id: decoded.id,
+            pool_key: None,
+            sender: decoded.sender,
+            amount0: decoded.amount0,
+            amount1: decoded.amount1,
+            sqrt_price_x96: decoded.sqrtPriceX96,
+            liquidity: decoded.liquidity,
+            tick: decoded.tick.as_i32(),
+            fee: decoded.fee.to(),
+            price_0_in_1: pool_state.price_0_in_1(),
+            price_1_in_0: pool_state.price_1_in_0(),
+        })
+    }
+
+    /// Extract all Swap events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+
+    /// Get the pool state after this swap (sqrtPriceX96, tick, and liquidity)
+    pub fn pool_state(&self) -> PoolState {
+        PoolState {
+            sqrt_price_x96: self.sqrt_price_x96,
+            tick: self.tick,
+            liquidity: self.liquidity,
+        }
+    }
+}
+
+/// A decoded Uniswap V4 ModifyLiquidity event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedV4ModifyLiquidity {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// The PoolManager that emitted the event
+    pub pool_manager: Address,
+    pub pool_id: B256,
+    /// The pool's key, if its Initialize event has been seen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool_key: Option<V4PoolKey>,
+    /// The address that modified the position
+    pub sender: Address,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+    /// Liquidity added (positive) or removed (negative)
+    pub liquidity_delta: I256,
+    /// Distinguishes positions with the same owner and range
+    pub salt: B256,
+}
+
+impl ParsedV4ModifyLiquidity {
+    /// Try to parse a ModifyLiquidity event from a log
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 3 {
+            return None;
+        }
+
+        if log.topics[0] != ModifyLiquidity::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded =
+            ModifyLiquidity::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            pool_manager: log.address,
+            pool_id: decoded.id,
+            pool_key: None,
+            sender: decoded.sender,
+            tick_lower: decoded.tickLower.as_i32(),
+            tick_upper: decoded.tickUpper.as_i32(),
+            liquidity_delta: decoded.liquidityDelta,
+            salt: decoded.salt,
+        })
+    }
+
+    /// Extract all ModifyLiquidity events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// A decoded Uniswap V4 Donate event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedV4Donate {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// The PoolManager that emitted the event
+    pub pool_manager: Address,
+    pub pool_id: B256,
+    /// The pool's key, if its Initialize event has been seen
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool_key: Option<V4PoolKey>,
+    /// The address that donated
+    pub sender: Address,
+    /// Amount of currency0 donated
+    pub amount0: U256,
+    /// Amount of currency1 donated
+    pub amount1: U256,
+}
+
+impl ParsedV4Donate {
+    /// Try to parse a Donate event from a log
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.topics.len() != 3 {
+            return None;
+        }
+
+        if log.topics[0] != Donate::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded = Donate::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            pool_manager: log.address,
+            pool_id: decoded.id,
+            pool_key: None,
+            sender: decoded.sender,
+            amount0: decoded.amount0,
+            amount1: decoded.amount1,
+        })
+    }
+
+    /// Extract all Donate events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// Collection of all UniV4 PoolManager events
+#[derive(Debug, Default)]
+pub struct UniV4Updates {
+    pub initializes: Vec<ParsedV4Initialize>,
+    pub swaps: Vec<ParsedV4Swap>,
+    pub modify_liquidities: Vec<ParsedV4ModifyLiquidity>,
+    pub donates: Vec<ParsedV4Donate>,
+}
+
+impl UniV4Updates {
+    /// Extract all UniV4 events from logs
+    pub fn extract_all(logs: &[ContextLog]) -> Self {
+        Self {
+            initializes: ParsedV4Initialize::extract_all(logs),
+            swaps: ParsedV4Swap::extract_all(logs),
+            modify_liquidities: ParsedV4ModifyLiquidity::extract_all(logs),
+            donates: ParsedV4Donate::extract_all(logs),
+        }
+    }
+
+    /// Returns true if no events were found
+    pub fn is_empty(&self) -> bool {
+        self.initializes.is_empty()
+            && self.swaps.is_empty()
+            && self.modify_liquidities.is_empty()
+            && self.donates.is_empty()
+    }
+
+    /// Total count of all events
+    pub fn total_count(&self) -> usize {
+        self.initializes.len()
+            + self.swaps.len()
+            + self.modify_liquidities.len()
+            + self.donates.len()
+    }
+}
+
+/// Pool keys learned from Initialize events, by PoolManager and PoolId
+#[derive(Debug, Clone, Default)]
+pub struct V4PoolRegistry {
+    pools: HashMap<(Address, B256), V4PoolKey>,
+}
+
+impl V4PoolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of known pools
+    pub fn len(&self) -> usize {
+        self.pools.len()
+    }
+
+    /// Returns true if no pools are known
+    pub fn is_empty(&self) -> bool {
+        self.pools.is_empty()
+    }
+
+    /// Look up a pool's key
+    pub fn key(&self, pool_manager: &Address, pool_id: &B256) -> Option<&V4PoolKey> {
+        self.pools.get(&(*pool_manager, *pool_id))
+    }
+
+    /// Record the pools initialized in `updates`, then attach the known pool
+    /// keys to its swaps, liquidity changes and donations
+    pub fn annotate(&mut self, updates: &mut UniV4Updates) {
+        for init in &updates.initializes {
+            self.pools
+                .insert((init.pool_manager, init.pool_id), init.key);
+        }
+        for swap in &mut updates.swaps {
+            swap.pool_key = self.key(&swap.pool_manager, &swap.pool_id).copied();
+        }
+        for modify in &mut updates.modify_liquidities {
+            modify.pool_key = self.key(&modify.pool_manager, &modify.pool_id).copied();
+        }
+        for donate in &mut updates.donates {
+            donate.pool_key = self.key(&donate.pool_manager, &donate.pool_id).copied();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flashblocks::test_logs::receipt_log;
+    use alloy_primitives::aliases::{I24, U24};
+    use alloy_primitives::keccak256;
+
+    const POOL_MANAGER: Address = Address::repeat_byte(0x44);
+
+    #[test]
+    fn test_univ4_signatures() {
+        assert_eq!(
+            Initialize::SIGNATURE_HASH,
+            keccak256(b"Initialize(bytes32,address,address,uint24,int24,address,uint160,int24)")
+        );
+        assert_eq!(
+            ModifyLiquidity::SIGNATURE_HASH,
+            keccak256(b"ModifyLiquidity(bytes32,address,int24,int24,int256,bytes32)")
+        );
+        assert_eq!(
+            Swap::SIGNATURE_HASH,
+            keccak256(b"Swap(bytes32,address,int128,int128,uint160,uint128,int24,uint24)")
+        );
+        assert_eq!(
+            Donate::SIGNATURE_HASH,
+            keccak256(b"Donate(bytes32,address,uint256,uint256)")
+        );
+    }
+
+    #[test]
+    fn test_initialize_keys_later_swaps() {
+        let pool_id = B256::repeat_byte(0x11);
+        let initialize = Initialize {
+            id: pool_id,
+            currency0: Address::ZERO,
+            currency1: Address::repeat_byte(0xcc),
+            fee: U24::from(3_000),
+            tickSpacing: I24::try_from(60).unwrap(),
+            hooks: Address::repeat_byte(0x80),
+            sqrtPriceX96: U160::from(1) << 96,
+            tick: I24::ZERO,
+        };
+        let swap = Swap {
+            id: pool_id,
+            sender: Address::repeat_byte(0x01),
+            amount0: -1_000,
+            amount1: 990,
+            sqrtPriceX96: U160::from(1) << 96,
+            liquidity: 5_000,
+            tick: I24::try_from(-1).unwrap(),
+            fee: U24::from(3_000),
+        };
+        let other_pool_swap = Swap {
+            id: B256::repeat_byte(0x22),
+            ..swap.clone()
+        };
+
+        let context = EventContext::default();
+        let logs: Vec<_> = [
+            initialize.encode_log_data(),
+            swap.encode_log_data(),
+            other_pool_swap.encode_log_data(),
+        ]
+        .into_iter()
+        .map(|data| ContextLog {
+            log: receipt_log(POOL_MANAGER, data),
+            context: context.clone(),
+        })
+        .collect();
+
+        let mut updates = UniV4Updates::extract_all(&logs);
+        assert_eq!(updates.initializes.len(), 1);
+        assert_eq!(updates.swaps.len(), 2);
+        assert_eq!(updates.initializes[0].key.fee, 3_000);
+        assert_eq!(updates.initializes[0].key.tick_spacing, 60);
+
+        let mut registry = V4PoolRegistry::new();
+        registry.annotate(&mut updates);
+        assert_eq!(registry.len(), 1);
+
+        let parsed = &updates.swaps[0];
+        assert_eq!(parsed.pool_key, Some(updates.initializes[0].key));
+        assert_eq!(parsed.amount0, -1_000);
+        assert_eq!(parsed.tick, -1);
+        assert_eq!(parsed.price_0_in_1, 1.0);
+        assert_eq!(updates.swaps[1].pool_key, None);
+    }
+
+    #[test]
+    fn test_parse_modify_liquidity_and_donate() {
+        let context = EventContext::default();
+        let modify = ModifyLiquidity {
+            id: B256::repeat_byte(0x11),
+            sender: Address::repeat_byte(0x01),
+            tickLower: I24::try_from(-120).unwrap(),
+            tickUpper: I24::try_from(120).unwrap(),
+            liquidityDelta: I256::try_from(-5_000).unwrap(),
+            salt: B256::ZERO,
+        };
+        let parsed = ParsedV4ModifyLiquidity::try_from_log(
+            &receipt_log(POOL_MANAGER, modify.encode_log_data()),
+            &context,
+        )
+        .unwrap();
+        assert_eq!(parsed.tick_lower, -120);
+        assert_eq!(parsed.tick_upper, 120);
+        assert_eq!(parsed.liquidity_delta, modify.liquidityDelta);
+
+        let donate = Donate {
+            id: B256::repeat_byte(0x11),
+            sender: Address::repeat_byte(0x01),
+            amount0: U256::from(7),
+            amount1: U256::from(9),
+        };
+        let log = receipt_log(POOL_MANAGER, donate.encode_log_data());
+        let parsed = ParsedV4Donate::try_from_log(&log, &context).unwrap();
+        assert_eq!(parsed.amount1, U256::from(9));
+        assert!(ParsedV4Swap::try_from_log(&log, &context).is_none());
+    }
+}
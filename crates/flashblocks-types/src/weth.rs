@@ -0,0 +1,230 @@
+//! WETH9 wrapping and unwrapping on Base's WETH predeploy.
+
+use alloy_primitives::{Address, Bloom, BloomInput, U256, address};
+use alloy_sol_types::{SolEvent, sol};
+use serde::Serialize;
+
+use crate::flashblocks::{ContextLog, EventContext, ReceiptLog};
+
+/// The WETH predeploy, at the same address on Base and Base Sepolia
+pub const WETH: Address = address!("4200000000000000000000000000000000000006");
+
+// WETH9 events
+sol! {
+    /// Emitted when ETH is wrapped into WETH
+    event Deposit(address indexed dst, uint256 wad);
+
+    /// Emitted when WETH is unwrapped into ETH
+    event Withdrawal(address indexed src, uint256 wad);
+}
+
+/// Detected WETH events based on bloom filter
+#[derive(Debug, Default)]
+pub struct WethEvents {
+    pub may_have_deposit: bool,
+    pub may_have_withdrawal: bool,
+}
+
+impl WethEvents {
+    /// Check the bloom filter for potential WETH events.
+    /// Note: Bloom filters can have false positives but no false negatives.
+    pub fn from_bloom(bloom: &Bloom) -> Self {
+        // Every WETH log has the predeploy's address in the bloom
+        if !bloom.contains_input(BloomInput::Raw(WETH.as_slice())) {
+            return Self::default();
+        }
+        Self {
+            may_have_deposit: bloom
+                .contains_input(BloomInput::Raw(Deposit::SIGNATURE_HASH.as_slice())),
+            may_have_withdrawal: bloom
+                .contains_input(BloomInput::Raw(Withdrawal::SIGNATURE_HASH.as_slice())),
+        }
+    }
+
+    /// Returns true if any WETH event might be present
+    pub fn any(&self) -> bool {
+        self.may_have_deposit || self.may_have_withdrawal
+    }
+}
+
+/// A decoded WETH Deposit event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedDeposit {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// The account credited with WETH
+    pub account: Address,
+    /// Amount of ETH wrapped, in wei
+    pub amount: U256,
+}
+
+impl ParsedDeposit {
+    /// Try to parse a Deposit event from a log of the WETH predeploy
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.address != WETH || log.topics.len() != 2 {
+            return None;
+        }
+
+        if log.topics[0] != Deposit::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded = Deposit::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            account: decoded.dst,
+            amount: decoded.wad,
+        })
+    }
+
+    /// Extract all WETH Deposit events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// A decoded WETH Withdrawal event
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedWithdrawal {
+    /// Where the event was emitted
+    pub context: EventContext,
+    /// The account whose WETH was burned and sent ETH
+    pub account: Address,
+    /// Amount of WETH unwrapped, in wei
+    pub amount: U256,
+}
+
+impl ParsedWithdrawal {
+    /// Try to parse a Withdrawal event from a log of the WETH predeploy
+    pub fn try_from_log(log: &ReceiptLog, context: &EventContext) -> Option<Self> {
+        if log.address != WETH || log.topics.len() != 2 {
+            return None;
+        }
+
+        if log.topics[0] != Withdrawal::SIGNATURE_HASH {
+            return None;
+        }
+
+        let decoded =
+            Withdrawal::decode_raw_log(log.topics.iter().copied(), &log.data, true).ok()?;
+
+        Some(Self {
+            context: context.clone(),
+            account: decoded.src,
+            amount: decoded.wad,
+        })
+    }
+
+    /// Extract all WETH Withdrawal events from a slice of logs
+    pub fn extract_all(logs: &[ContextLog]) -> Vec<Self> {
+        logs.iter()
+            .filter_map(|l| Self::try_from_log(&l.log, &l.context))
+            .collect()
+    }
+}
+
+/// Collection of WETH events
+#[derive(Debug, Default)]
+pub struct WethUpdates {
+    pub deposits: Vec<ParsedDeposit>,
+    pub withdrawals: Vec<ParsedWithdrawal>,
+}
+
+impl WethUpdates {
+    /// Extract all WETH events from logs
+    pub fn extract_all(logs: &[ContextLog]) -> Self {
+        Self {
+            deposits: ParsedDeposit::extract_all(logs),
+            withdrawals: ParsedWithdrawal::extract_all(logs),
+        }
+    }
+
+    /// Returns true if no events were found
+    pub fn is_empty(&self) -> bool {
+        self.deposits.is_empty() && self.withdrawals.is_empty()
+    }
+
+    /// Total count of all events
+    pub fn total_count(&self) -> usize {
+        self.deposits.len() + self.withdrawals.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::flashblocks::test_logs::receipt_log;
+    use alloy_primitives::keccak256;
+
+    #[test]
+    fn test_weth_signatures() {
+        assert_eq!(
+            Deposit::SIGNATURE_HASH,
+            keccak256(b"Deposit(address,uint256)")
+        );
+        assert_eq!(
+            Withdrawal::SIGNATURE_HASH,
+            keccak256(b"Withdrawal(address,uint256)")
+        );
+    }
+
+    #[test]
+    fn test_parse_deposit_and_withdrawal() {
+        let context = EventContext::default();
+        let account = Address::repeat_byte(0x01);
+        let deposit = Deposit {
+            dst: account,
+            wad: U256::from(10u64.pow(18)),
+        };
+        let withdrawal = Withdrawal {
+            src: account,
+            wad: U256::from(5),
+        };
+
+        let parsed =
+            ParsedDeposit::try_from_log(&receipt_log(WETH, deposit.encode_log_data()), &context)
+                .unwrap();
+        assert_eq!(parsed.account, account);
+        assert_eq!(parsed.amount, U256::from(10u64.pow(18)));
+
+        let parsed = ParsedWithdrawal::try_from_log(
+            &receipt_log(WETH, withdrawal.encode_log_data()),
+            &context,
+        )
+        .unwrap();
+        assert_eq!(parsed.account, account);
+        assert_eq!(parsed.amount, U256::from(5));
+
+        // Other contracts reuse the signatures, e.g. vaults and other WETH9 clones
+        let other = Address::repeat_byte(0x99);
+        assert!(
+            ParsedDeposit::try_from_log(&receipt_log(other, deposit.encode_log_data()), &context)
+                .is_none()
+        );
+        assert!(
+            ParsedWithdrawal::try_from_log(&receipt_log(WETH, deposit.encode_log_data()), &context)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_bloom_of_deposit_log() {
+        let deposit = Deposit {
+            dst: Address::repeat_byte(0x01),
+            wad: U256::from(1),
+        };
+        let mut bloom = Bloom::default();
+        bloom.accrue_log(&alloy_primitives::Log {
+            address: WETH,
+            data: deposit.encode_log_data(),
+        });
+
+        let events = WethEvents::from_bloom(&bloom);
+        assert!(events.may_have_deposit);
+        assert!(!events.may_have_withdrawal);
+        assert!(!WethEvents::from_bloom(&Bloom::default()).any());
+    }
+}
@@ -89,10 +89,9 @@ impl SseServer {
 
                             if let Err(e) =
                                 http1::Builder::new().serve_connection(io, service).await
+                                && !e.is_incomplete_message()
                             {
-                                if !e.is_incomplete_message() {
-                                    error!("Error serving connection from {}: {}", addr, e);
-                                }
+                                error!("Error serving connection from {}: {}", addr, e);
                             }
                         });
                     }
@@ -1,5 +1,20 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use alloy_primitives::Address;
 use clap::{Parser, ValueEnum};
 
+use flashblocks_types::aave_positions::AavePositionBook;
+use flashblocks_types::chainlink::FeedRegistry;
+use flashblocks_types::dex::DexRegistry;
+use flashblocks_types::morpho::{MorphoMarketRegistry, MorphoTotalsCache};
+use flashblocks_types::tokens::TokenRegistry;
+use tracing::info;
+
+use crate::protocols::{ProtocolConfig, ThresholdSpec};
+use crate::record::MIN_REPLAY_SPEED;
+use crate::upstream::ReconnectConfig;
+
 #[derive(Debug, Clone, Copy, ValueEnum, Default)]
 pub enum StreamType {
     /// Output to WebSocket server (default)
@@ -15,9 +30,15 @@ pub enum StreamType {
 #[command(name = "flashblocks-digestor")]
 #[command(about = "Digest and stream Flashblocks data")]
 pub struct Args {
-    /// Flashblocks WebSocket URL to connect to
-    #[arg(short, long, default_value = "wss://sepolia.flashblocks.base.org/ws")]
-    pub url: String,
+    /// Flashblocks WebSocket URL(s) to connect to.
+    /// Repeat the flag or pass a comma-separated list to race several upstreams.
+    #[arg(
+        short,
+        long = "url",
+        default_value = "wss://sepolia.flashblocks.base.org/ws",
+        value_delimiter = ','
+    )]
+    pub urls: Vec<String>,
 
     /// Stream output type
     #[arg(short, long, value_enum, default_value_t = StreamType::default())]
@@ -26,4 +47,181 @@ pub struct Args {
     /// Server address (used with websocket and sse stream types)
     #[arg(long, default_value = "localhost:9001")]
     pub addr: String,
+
+    /// Initial delay before reconnecting to the upstream, in milliseconds
+    #[arg(long, default_value_t = 250)]
+    pub reconnect_initial_ms: u64,
+
+    /// Maximum delay between upstream reconnect attempts, in milliseconds
+    #[arg(long, default_value_t = 30_000)]
+    pub reconnect_max_ms: u64,
+
+    /// Reconnect when the upstream sends nothing for this many seconds
+    #[arg(long, default_value_t = 10)]
+    pub idle_timeout_secs: u64,
+
+    /// Give up after this many consecutive failed reconnects (0 = retry forever)
+    #[arg(long, default_value_t = 0)]
+    pub max_reconnect_attempts: u32,
+
+    /// Interval between per-upstream stats reports, in seconds
+    #[arg(long, default_value_t = 30)]
+    pub stats_interval_secs: u64,
+
+    /// Append every raw upstream frame to this file
+    #[arg(long, conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Read frames from a recording instead of connecting to the upstreams.
+    /// Frames keep their recorded upstream, so dedup and per-upstream stats
+    /// match the live run.
+    #[arg(long)]
+    pub replay: Option<PathBuf>,
+
+    /// Replay speed multiplier (1 = original speed, 0 = as fast as possible).
+    /// Non-zero values must be at least 0.001.
+    #[arg(long, default_value_t = 1.0, requires = "replay", value_parser = parse_replay_speed)]
+    pub replay_speed: f64,
+
+    /// Only stream `Balance_changed` events for these addresses (default: all).
+    /// Repeat the flag or pass a comma-separated list.
+    #[arg(long = "watch-address", value_delimiter = ',')]
+    pub watch_addresses: Vec<Address>,
+
+    /// Chainlink feed registry (JSON) mapping aggregators to proxies, pairs and decimals
+    #[arg(long)]
+    pub chainlink_feeds: Option<PathBuf>,
+
+    /// Token list (JSON or TOML) of token decimals/symbols and pool token pairs,
+    /// added on top of the bundled Base and Base Sepolia list
+    #[arg(long)]
+    pub token_list: Option<PathBuf>,
+
+    /// DEX registry (JSON) of V3-style pool factories and pools,
+    /// added on top of the bundled Base and Base Sepolia factories.
+    /// Pools created before startup are only attributed if listed here, or if
+    /// their factory has an `init_code_hash` and both tokens are in the token list.
+    #[arg(long)]
+    pub dex_factories: Option<PathBuf>,
+
+    /// Stream `Erc20_transfer` and `Erc20_approval` events touching these token
+    /// or account addresses. Repeat the flag or pass a comma-separated list.
+    #[arg(long = "erc20-watch", value_delimiter = ',')]
+    pub erc20_watch: Vec<Address>,
+
+    /// Stream every ERC-20 transfer and approval, ignoring `--erc20-watch`
+    #[arg(long)]
+    pub erc20_all: bool,
+
+    /// Emit `Large_transfer` when a token moves at least this much, as
+    /// `<token>=<amount>` in whole tokens or `<token>=raw:<amount>` in base units.
+    /// Repeat the flag or pass a comma-separated list.
+    #[arg(long = "large-transfer", value_delimiter = ',')]
+    pub large_transfers: Vec<ThresholdSpec>,
+
+    /// AAVE position snapshot (JSON) of reserve liquidation thresholds and price
+    /// feeds and users' scaled balances, used to stream `Aave_liquidatable`
+    #[arg(long)]
+    pub aave_snapshot: Option<PathBuf>,
+
+    /// Morpho Blue market snapshot (JSON) of markets created before startup,
+    /// whose params are attached to their events and whose optional totals
+    /// value their shares
+    #[arg(long)]
+    pub morpho_markets: Option<PathBuf>,
+}
+
+/// Parse `--replay-speed`: 0, or a finite factor of at least [`MIN_REPLAY_SPEED`]
+fn parse_replay_speed(s: &str) -> Result<f64, String> {
+    let factor: f64 = s.parse().map_err(|e| format!("{e}"))?;
+    if factor == 0.0 || (factor.is_finite() && factor >= MIN_REPLAY_SPEED) {
+        Ok(factor)
+    } else {
+        Err(format!("must be 0 or a number from {MIN_REPLAY_SPEED} up"))
+    }
+}
+
+impl Args {
+    /// Build the upstream reconnect policy from the CLI arguments
+    pub fn reconnect_config(&self) -> ReconnectConfig {
+        ReconnectConfig {
+            initial_backoff: Duration::from_millis(self.reconnect_initial_ms),
+            max_backoff: Duration::from_millis(self.reconnect_max_ms),
+            idle_timeout: Duration::from_secs(self.idle_timeout_secs),
+            max_attempts: (self.max_reconnect_attempts > 0).then_some(self.max_reconnect_attempts),
+        }
+    }
+
+    /// Load the protocol handler configuration files named on the command line
+    pub fn protocol_config(&self) -> Result<ProtocolConfig, Box<dyn std::error::Error>> {
+        let mut config = ProtocolConfig {
+            tokens: TokenRegistry::bundled(),
+            dexes: DexRegistry::bundled(),
+            ..Default::default()
+        };
+        if let Some(path) = &self.chainlink_feeds {
+            config.feeds = FeedRegistry::load(path)
+                .map_err(|e| format!("failed to load {}: {}", path.display(), e))?;
+            info!(
+                "Loaded {} Chainlink feeds from {}",
+                config.feeds.len(),
+                path.display()
+            );
+        }
+        if let Some(path) = &self.token_list {
+            let tokens = TokenRegistry::load(path)
+                .map_err(|e| format!("failed to load {}: {}", path.display(), e))?;
+            info!(
+                "Loaded {} tokens and {} pools from {}",
+                tokens.len(),
+                tokens.pool_count(),
+                path.display()
+            );
+            config.tokens.extend(tokens);
+        }
+        if let Some(path) = &self.dex_factories {
+            let dexes = DexRegistry::load(path)
+                .map_err(|e| format!("failed to load {}: {}", path.display(), e))?;
+            info!(
+                "Loaded {} DEX factories and {} pools from {}",
+                dexes.factory_count(),
+                dexes.pool_count(),
+                path.display()
+            );
+            config.dexes.extend(dexes);
+        }
+        let derived = config.dexes.derive_pools(config.tokens.token_addresses());
+        info!("Derived {} V3 pool addresses from the token list", derived);
+        if let Some(path) = &self.aave_snapshot {
+            config.aave_positions = AavePositionBook::load(path)
+                .map_err(|e| format!("failed to load {}: {}", path.display(), e))?;
+            info!(
+                "Loaded {} AAVE reserves and {} users from {}",
+                config.aave_positions.reserve_count(),
+                config.aave_positions.user_count(),
+                path.display()
+            );
+        }
+        if let Some(path) = &self.morpho_markets {
+            config.morpho_markets = MorphoMarketRegistry::load(path)
+                .map_err(|e| format!("failed to load {}: {}", path.display(), e))?;
+            config.morpho_totals = MorphoTotalsCache::load(path)
+                .map_err(|e| format!("failed to load {}: {}", path.display(), e))?;
+            info!(
+                "Loaded {} Morpho markets ({} with totals) from {}",
+                config.morpho_markets.len(),
+                config.morpho_totals.len(),
+                path.display()
+            );
+        }
+        config.erc20.watchlist = self.erc20_watch.iter().copied().collect();
+        config.erc20.all = self.erc20_all;
+        for spec in &self.large_transfers {
+            let (token, threshold) = spec
+                .resolve(&config.tokens)
+                .map_err(|e| format!("invalid --large-transfer: {}", e))?;
+            config.erc20.thresholds.insert(token, threshold);
+        }
+        Ok(config)
+    }
 }
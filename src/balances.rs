@@ -0,0 +1,393 @@
+//! Account balance tracking from `new_account_balances`.
+//!
+//! Each flashblock reports the balances of the accounts it touched. The tracker
+//! remembers the latest balance of every account seen in the current block,
+//! plus the final balances of the previous block as a baseline, and turns each
+//! report into a balance change with its delta. Combined with the flashblock's
+//! WETH deposits and withdrawals, the changes also give a per-flashblock
+//! summary of each account's net ETH flow.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use alloy_primitives::{Address, I256, U256};
+use flashblocks_indexer_streams::{DataStream, StreamOutput};
+use flashblocks_types::flashblocks::Flashblock;
+use flashblocks_types::weth::WethUpdates;
+use serde::Serialize;
+use tracing::{debug, error};
+
+/// A change to an account's ETH balance, streamed as `Balance_changed`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BalanceChanged {
+    pub address: Address,
+    /// Last known balance, if the account was seen in this or the previous block
+    pub previous_balance: Option<U256>,
+    pub new_balance: U256,
+    /// `new_balance - previous_balance`, if the previous balance is known
+    pub delta: Option<I256>,
+    pub block_number: u64,
+    pub flashblock_index: u64,
+    pub payload_id: String,
+}
+
+/// An account's ETH movements within one flashblock
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AccountFlow {
+    pub address: Address,
+    /// Change in ETH balance, if the previous balance is known. Zero when the
+    /// account was reported with an unchanged balance.
+    pub balance_delta: Option<I256>,
+    /// ETH wrapped into WETH
+    pub wrapped: U256,
+    /// WETH unwrapped into ETH
+    pub unwrapped: U256,
+    /// Change in ETH plus WETH held: `balance_delta + wrapped - unwrapped`,
+    /// so wrapping and unwrapping net out
+    pub net: Option<I256>,
+}
+
+/// Net ETH flow of the accounts touched by a flashblock, streamed as `Eth_flow`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EthFlow {
+    pub block_number: u64,
+    pub flashblock_index: u64,
+    pub payload_id: String,
+    /// Accounts with a balance change or WETH activity, sorted by address
+    pub accounts: Vec<AccountFlow>,
+}
+
+/// Keeps account balances across the flashblocks of a block
+#[derive(Debug, Default)]
+pub struct BalanceTracker {
+    /// Accounts to report; empty means all
+    watchlist: HashSet<Address>,
+    payload_id: Option<String>,
+    block_number: Option<u64>,
+    /// Final balances of the accounts touched in the previous block
+    baseline: HashMap<Address, U256>,
+    /// Latest balances of the accounts touched in the current block
+    current: HashMap<Address, U256>,
+}
+
+impl BalanceTracker {
+    pub fn new(watchlist: impl IntoIterator<Item = Address>) -> Self {
+        Self {
+            watchlist: watchlist.into_iter().collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Update balances from a flashblock, then log and stream any changes
+    /// and the flashblock's ETH flow
+    pub fn observe(&mut self, fb: &Flashblock, stream: &StreamOutput) {
+        let changes = self.update(fb);
+        for change in &changes {
+            debug!(
+                address = %change.address,
+                previous_balance = ?change.previous_balance,
+                new_balance = %change.new_balance,
+                delta = ?change.delta,
+                "Balance changed"
+            );
+            stream.send("Balance_changed", change).unwrap_or_else(|e| {
+                error!("Failed to send balance change to stream: {}", e);
+            });
+        }
+
+        if let Some(flow) = self.eth_flow(fb, &changes, &fb.extract_weth_updates()) {
+            debug!(
+                block_number = flow.block_number,
+                flashblock_index = flow.flashblock_index,
+                accounts = flow.accounts.len(),
+                "ETH flow"
+            );
+            stream.send("Eth_flow", &flow).unwrap_or_else(|e| {
+                error!("Failed to send ETH flow to stream: {}", e);
+            });
+        }
+    }
+
+    /// Update balances from a flashblock and return the changes to watched accounts
+    pub fn update(&mut self, fb: &Flashblock) -> Vec<BalanceChanged> {
+        let Some(metadata) = &fb.metadata else {
+            return Vec::new();
+        };
+
+        if self.payload_id.as_deref() != Some(fb.payload_id.as_str()) {
+            if self.block_number == Some(metadata.block_number) {
+                // The payload was replaced; its balances never landed
+                self.current.clear();
+            } else {
+                self.baseline = std::mem::take(&mut self.current);
+            }
+            self.payload_id = Some(fb.payload_id.clone());
+            self.block_number = Some(metadata.block_number);
+        }
+
+        let mut balances: Vec<_> = metadata.new_account_balances.iter().collect();
+        balances.sort_unstable_by_key(|(address, _)| **address);
+
+        let mut changes = Vec::new();
+        for (&address, &new_balance) in balances {
+            let previous_balance = self
+                .current
+                .insert(address, new_balance)
+                .or_else(|| self.baseline.get(&address).copied());
+
+            if previous_balance == Some(new_balance) || !self.is_watched(&address) {
+                continue;
+            }
+
+            changes.push(BalanceChanged {
+                address,
+                previous_balance,
+                new_balance,
+                // Balances are far below 2^255, so the wrapped difference is the signed delta
+                delta: previous_balance.map(|prev| I256::from_raw(new_balance.wrapping_sub(prev))),
+                block_number: metadata.block_number,
+                flashblock_index: fb.index,
+                payload_id: fb.payload_id.clone(),
+            });
+        }
+        changes
+    }
+
+    /// Summarize the net ETH flow of watched accounts from the flashblock's
+    /// balance `changes` (as returned by `update`) and WETH events
+    pub fn eth_flow(
+        &self,
+        fb: &Flashblock,
+        changes: &[BalanceChanged],
+        weth: &WethUpdates,
+    ) -> Option<EthFlow> {
+        let metadata = fb.metadata.as_ref()?;
+
+        let new_flow = |address: Address| AccountFlow {
+            address,
+            // Reported but not changed means the balance held steady
+            balance_delta: metadata
+                .new_account_balances
+                .contains_key(&address)
+                .then_some(I256::ZERO),
+            wrapped: U256::ZERO,
+            unwrapped: U256::ZERO,
+            net: None,
+        };
+
+        let mut accounts: BTreeMap<Address, AccountFlow> = BTreeMap::new();
+        for change in changes {
+            accounts
+                .entry(change.address)
+                .or_insert_with(|| new_flow(change.address))
+                .balance_delta = change.delta;
+        }
+        for deposit in weth.deposits.iter().filter(|d| self.is_watched(&d.account)) {
+            let flow = accounts
+                .entry(deposit.account)
+                .or_insert_with(|| new_flow(deposit.account));
+            flow.wrapped = flow.wrapped.saturating_add(deposit.amount);
+        }
+        for withdrawal in weth
+            .withdrawals
+            .iter()
+            .filter(|w| self.is_watched(&w.account))
+        {
+            let flow = accounts
+                .entry(withdrawal.account)
+                .or_insert_with(|| new_flow(withdrawal.account));
+            flow.unwrapped = flow.unwrapped.saturating_add(withdrawal.amount);
+        }
+
+        if accounts.is_empty() {
+            return None;
+        }
+
+        let accounts = accounts
+            .into_values()
+            .map(|mut flow| {
+                // WETH amounts are bounded by the ETH supply, so the wrapped
+                // differences are exact signed values
+                flow.net = flow.balance_delta.map(|delta| {
+                    delta + I256::from_raw(flow.wrapped) - I256::from_raw(flow.unwrapped)
+                });
+                flow
+            })
+            .collect();
+
+        Some(EthFlow {
+            block_number: metadata.block_number,
+            flashblock_index: fb.index,
+            payload_id: fb.payload_id.clone(),
+            accounts,
+        })
+    }
+
+    fn is_watched(&self, address: &Address) -> bool {
+        self.watchlist.is_empty() || self.watchlist.contains(address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flashblocks_types::flashblocks::EventContext;
+    use flashblocks_types::weth::{ParsedDeposit, ParsedWithdrawal};
+
+    fn flashblock(
+        payload_id: &str,
+        index: u64,
+        block_number: u64,
+        balances: &[(Address, u64)],
+    ) -> Flashblock {
+        let balances: serde_json::Map<_, _> = balances
+            .iter()
+            .map(|(address, balance)| (address.to_string(), format!("{:#x}", balance).into()))
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "payload_id": payload_id,
+            "index": index,
+            "metadata": {
+                "receipts": {},
+                "new_account_balances": balances,
+                "block_number": block_number,
+            }
+        }))
+        .unwrap()
+    }
+
+    fn summary(changes: &[BalanceChanged]) -> Vec<(Address, Option<U256>, U256, Option<I256>)> {
+        changes
+            .iter()
+            .map(|c| (c.address, c.previous_balance, c.new_balance, c.delta))
+            .collect()
+    }
+
+    #[test]
+    fn test_deltas_across_flashblocks() {
+        let alice = Address::repeat_byte(0xa1);
+        let bob = Address::repeat_byte(0xb0);
+        let mut tracker = BalanceTracker::new([]);
+
+        let changes = tracker.update(&flashblock("0x01", 0, 100, &[(alice, 1_000), (bob, 50)]));
+        assert_eq!(
+            summary(&changes),
+            vec![
+                (alice, None, U256::from(1_000), None),
+                (bob, None, U256::from(50), None),
+            ]
+        );
+
+        let changes = tracker.update(&flashblock("0x01", 1, 100, &[(alice, 400), (bob, 50)]));
+        assert_eq!(
+            summary(&changes),
+            vec![(
+                alice,
+                Some(U256::from(1_000)),
+                U256::from(400),
+                Some(I256::try_from(-600).unwrap())
+            )]
+        );
+
+        // The next block starts from the previous block's final balances
+        let changes = tracker.update(&flashblock("0x02", 0, 101, &[(bob, 80)]));
+        assert_eq!(
+            summary(&changes),
+            vec![(
+                bob,
+                Some(U256::from(50)),
+                U256::from(80),
+                Some(I256::try_from(30).unwrap())
+            )]
+        );
+    }
+
+    #[test]
+    fn test_replaced_payload_rolls_back() {
+        let alice = Address::repeat_byte(0xa1);
+        let mut tracker = BalanceTracker::new([]);
+
+        tracker.update(&flashblock("0x01", 0, 100, &[(alice, 1_000)]));
+        tracker.update(&flashblock("0x02", 0, 101, &[(alice, 900)]));
+
+        // 0x03 replaces 0x02 at block 101, so 0x02's balance is discarded
+        let changes = tracker.update(&flashblock("0x03", 0, 101, &[(alice, 950)]));
+        assert_eq!(
+            summary(&changes),
+            vec![(
+                alice,
+                Some(U256::from(1_000)),
+                U256::from(950),
+                Some(I256::try_from(-50).unwrap())
+            )]
+        );
+    }
+
+    #[test]
+    fn test_watchlist() {
+        let alice = Address::repeat_byte(0xa1);
+        let bob = Address::repeat_byte(0xb0);
+        let mut tracker = BalanceTracker::new([bob]);
+
+        let changes = tracker.update(&flashblock("0x01", 0, 100, &[(alice, 1), (bob, 2)]));
+        assert_eq!(summary(&changes), vec![(bob, None, U256::from(2), None)]);
+    }
+
+    #[test]
+    fn test_eth_flow_nets_out_wrapping() {
+        let alice = Address::repeat_byte(0xa1);
+        let bob = Address::repeat_byte(0xb0);
+        let carol = Address::repeat_byte(0xc0);
+        let mut tracker = BalanceTracker::new([]);
+        tracker.update(&flashblock("0x01", 0, 100, &[(alice, 1_000), (carol, 70)]));
+
+        // Alice wraps 500 and pays 100 elsewhere; carol unwraps 20 but her
+        // reported balance is unchanged; bob's balance isn't reported
+        let fb = flashblock("0x01", 1, 100, &[(alice, 400), (carol, 70)]);
+        let changes = tracker.update(&fb);
+        let weth = WethUpdates {
+            deposits: vec![
+                ParsedDeposit {
+                    context: EventContext::default(),
+                    account: alice,
+                    amount: U256::from(500),
+                },
+                ParsedDeposit {
+                    context: EventContext::default(),
+                    account: bob,
+                    amount: U256::from(50),
+                },
+            ],
+            withdrawals: vec![ParsedWithdrawal {
+                context: EventContext::default(),
+                account: carol,
+                amount: U256::from(20),
+            }],
+        };
+
+        let flow = tracker.eth_flow(&fb, &changes, &weth).unwrap();
+        assert_eq!((flow.block_number, flow.flashblock_index), (100, 1));
+        let summary: Vec<_> = flow
+            .accounts
+            .iter()
+            .map(|a| (a.address, a.balance_delta, a.wrapped, a.unwrapped, a.net))
+            .collect();
+        let i256 = |v: i64| Some(I256::try_from(v).unwrap());
+        assert_eq!(
+            summary,
+            vec![
+                (alice, i256(-600), U256::from(500), U256::ZERO, i256(-100)),
+                (bob, None, U256::from(50), U256::ZERO, None),
+                (carol, i256(0), U256::ZERO, U256::from(20), i256(-20)),
+            ]
+        );
+
+        // Nothing moved, nothing to report
+        let fb = flashblock("0x01", 2, 100, &[(alice, 400)]);
+        let changes = tracker.update(&fb);
+        assert!(
+            tracker
+                .eth_flow(&fb, &changes, &WethUpdates::default())
+                .is_none()
+        );
+    }
+}
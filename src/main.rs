@@ -1,17 +1,23 @@
 mod args;
+mod balances;
+mod merge;
 mod protocols;
+mod record;
+mod sequencer;
+mod upstream;
 mod utils;
 
-use std::sync::{Arc, atomic::AtomicBool};
+use std::time::Duration;
 
+use balances::BalanceTracker;
 use clap::Parser;
 use flashblocks_types::flashblocks::Flashblock;
-use futures_util::StreamExt;
-use protocols::process_all_protocols;
-use tokio_tungstenite::connect_async;
-use tokio_tungstenite::tungstenite::protocol::Message;
+use merge::UpstreamMerger;
+use protocols::{ProtocolHandler, build_handlers, process_all_protocols};
+use record::{Recorder, ReplaySpeed};
+use sequencer::FlashblockSequencer;
+use tokio::sync::{mpsc, watch};
 use tracing::{debug, error, info, warn};
-use utils::decompress_brotli;
 
 use crate::args::{Args, StreamType};
 use flashblocks_indexer_streams::StreamOutput;
@@ -35,6 +41,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         )
         .init();
 
+    // Load protocol handler configuration before connecting to anything
+    let handlers = build_handlers(args.protocol_config()?);
+
     // Create stream output based on CLI argument
     let stream_output = match args.stream {
         StreamType::Websocket => {
@@ -54,15 +63,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Start the stream output (starts WebSocket/SSE server if applicable)
     stream_output.start(&args.addr).await?;
 
-    let url = &args.url;
-
-    info!("Connecting to Flashblocks WebSocket: {url}");
-    let (ws_stream, _) = connect_async(url).await?;
-    info!("Connected. Streaming Flashblocks… (Ctrl-C to exit)");
-
-    let (_, mut read) = ws_stream.split();
-    let done = Arc::new(AtomicBool::new(false));
-    let done_clone = done.clone();
+    // Shutdown signal shared with the upstream supervisors
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
 
     // listen for CTRL-C in the background to shutdown gracefully
     tokio::task::spawn(async move {
@@ -70,86 +72,125 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             .await
             .expect("Failed to listen for Ctrl-C");
         warn!("Ctrl-C received, shutting down.");
-        done_clone.store(true, std::sync::atomic::Ordering::SeqCst);
+        let _ = shutdown_tx.send(true);
     });
 
-    info!("parsing flashblocks...");
-    while let Some(msg_result) = read.next().await {
-        if done.load(std::sync::atomic::Ordering::SeqCst) {
-            info!("Shutting down stream reader.");
-            break;
+    let (events_tx, mut events_rx) = mpsc::channel(1024);
+    let mut recording = None;
+    let mut merger = match &args.replay {
+        Some(path) => {
+            // Replay a recording through the same merge and processing path,
+            // with one merger slot per recorded upstream
+            let labels: Vec<_> = (0..record::recorded_upstreams(path)?)
+                .map(|id| format!("replay:{}#{id}", path.display()))
+                .collect();
+            let path = path.clone();
+            let speed = ReplaySpeed::from_factor(args.replay_speed);
+            let shutdown = shutdown_rx.clone();
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = record::replay(&path, speed, shutdown, events_tx) {
+                    error!("Replay of {} failed: {}", path.display(), e);
+                }
+            });
+            UpstreamMerger::new(&labels)
         }
-        match msg_result {
-            Ok(Message::Text(text)) => {
-                handle_message(&text, &stream_output);
+        None => {
+            let (recorder, writer) = args
+                .record
+                .as_deref()
+                .map(Recorder::create)
+                .transpose()?
+                .unzip();
+            recording = writer;
+
+            // One supervised connection per upstream, all feeding the same channel
+            let reconnect = args.reconnect_config();
+            for (id, url) in args.urls.iter().enumerate() {
+                tokio::spawn(upstream::supervise(
+                    id,
+                    url.clone(),
+                    reconnect.clone(),
+                    shutdown_rx.clone(),
+                    events_tx.clone(),
+                    recorder.clone(),
+                ));
             }
-            Ok(Message::Binary(bin)) => {
-                // Binary frames are Brotli-compressed JSON
-                match decompress_brotli(&bin) {
-                    Ok(text) => handle_message(&text, &stream_output),
-                    Err(e) => {
-                        warn!("Failed to decompress binary frame: {e}");
-                        info!("Binary frame ({} bytes)", bin.len());
+            // Only the supervisors hold senders, so the channel closes once they all stop
+            drop(events_tx);
+            UpstreamMerger::new(&args.urls)
+        }
+    };
+
+    let mut sequencer = FlashblockSequencer::new();
+    let mut balances = BalanceTracker::new(args.watch_addresses.iter().copied());
+    let mut stats_interval =
+        tokio::time::interval(Duration::from_secs(args.stats_interval_secs.max(1)));
+    stats_interval.tick().await;
+
+    info!("parsing flashblocks...");
+    loop {
+        tokio::select! {
+            event = events_rx.recv() => {
+                let Some(event) = event else {
+                    break;
+                };
+                if let Some(mut fb) = merger.accept(event, &stream_output)
+                    && sequencer.observe(&fb, &stream_output)
+                {
+                    // Later flashblocks share the base sent with index 0
+                    if fb.base.is_none() {
+                        fb.base = sequencer.base_for(&fb.payload_id).cloned();
                     }
+                    handle_flashblock(&handlers, &fb, &stream_output);
+                    balances.observe(&fb, &stream_output);
                 }
             }
-            Ok(Message::Ping(_)) | Ok(Message::Pong(_)) | Ok(Message::Frame(_)) => {
-                // Ignore; tungstenite handles pings/pongs at the protocol level.
-                info!("Received control frame");
-            }
-            Ok(Message::Close(frame)) => {
-                info!("WebSocket closed: {frame:?}");
-                break;
-            }
-            Err(e) => {
-                error!("WebSocket read error: {e}");
-                break;
-            }
+            _ = stats_interval.tick() => merger.report(&stream_output),
         }
     }
 
     info!("Stream ended");
+    // The supervisors dropped their recorders as they stopped, so the writer
+    // flushes the rest of the recording and exits
+    if let Some(writer) = recording {
+        let _ = tokio::task::spawn_blocking(move || writer.finish()).await;
+    }
+    if merger.all_gave_up() && !*shutdown_rx.borrow() {
+        return Err("all upstreams failed".into());
+    }
     Ok(())
 }
 
-fn handle_message(text: &str, stream: &StreamOutput) {
-    // First try to parse into our minimal Flashblock struct.
-    match serde_json::from_str::<Flashblock>(text) {
-        Ok(fb) => {
-            let block_number = fb.metadata.as_ref().map(|m| m.block_number);
-
-            match block_number {
-                Some(num) => {
-                    // Debug: log flashblock metadata
-                    if let Some(ref meta) = fb.metadata {
-                        let total_logs: usize =
-                            meta.receipts.values().map(|r| r.logs().len()).sum();
-                        debug!(
-                            payload_id = %fb.payload_id,
-                            index = fb.index,
-                            block_number = num,
-                            receipts = meta.receipts.len(),
-                            total_logs = total_logs,
-                            "Flashblock"
-                        );
-                    }
-
-                    // Process all protocols in parallel
-                    process_all_protocols(&fb, num, stream);
-                }
-                None => {
-                    debug!(
-                        payload_id = %fb.payload_id,
-                        index = fb.index,
-                        "Flashblock (no block_number)"
-                    );
-                }
-            }
+/// Process a single deduplicated flashblock through all protocol handlers.
+fn handle_flashblock(
+    handlers: &[Box<dyn ProtocolHandler>],
+    fb: &Flashblock,
+    stream: &StreamOutput,
+) {
+    match fb.metadata.as_ref() {
+        Some(meta) => {
+            let num = meta.block_number;
+
+            // Debug: log flashblock metadata
+            let total_logs: usize = meta.receipts.values().map(|r| r.logs().len()).sum();
+            debug!(
+                payload_id = %fb.payload_id,
+                index = fb.index,
+                block_number = num,
+                receipts = meta.receipts.len(),
+                total_logs = total_logs,
+                "Flashblock"
+            );
+
+            // Process all protocols in parallel
+            process_all_protocols(handlers, fb, num, stream);
         }
-        Err(e) => {
-            // If the schema changes or we get some other message type, dump the raw JSON.
-            warn!(error = %e, "Failed to parse Flashblock JSON");
-            debug!("Raw message: {text}");
+        None => {
+            debug!(
+                payload_id = %fb.payload_id,
+                index = fb.index,
+                "Flashblock (no block_number)"
+            );
         }
     }
 }
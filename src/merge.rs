@@ -0,0 +1,288 @@
+//! Merges flashblocks from several upstreams into a single ordered feed.
+//!
+//! Every upstream delivers the same flashblocks, so the first copy of each
+//! `(payload_id, index)` wins and later copies are dropped. Latency and error
+//! counts are tracked per upstream so slow or flaky feeds are visible.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+use flashblocks_indexer_streams::{DataStream, StreamOutput};
+use flashblocks_types::flashblocks::Flashblock;
+use serde::Serialize;
+use tracing::{error, info, warn};
+
+use crate::upstream::{UpstreamEvent, UpstreamId};
+
+/// Number of recent flashblock keys remembered for deduplication
+const DEDUP_CAPACITY: usize = 4096;
+
+type FlashblockKey = (String, u64);
+
+/// Per-upstream counters
+#[derive(Debug, Default)]
+struct UpstreamStats {
+    /// Whether the upstream delivered a flashblock since it last went down
+    connected: bool,
+    /// Whether the supervisor gave up on the upstream
+    gave_up: bool,
+    flashblocks: u64,
+    /// Flashblocks this upstream delivered first
+    wins: u64,
+    duplicates: u64,
+    /// Connection losses and unparseable frames
+    errors: u64,
+    /// Outages the upstream recovered from
+    resumes: u64,
+    last_error: Option<String>,
+    /// Lag behind the first copy, summed over the current report interval
+    lag_total: Duration,
+    lag_samples: u64,
+    lag_max: Duration,
+}
+
+/// Snapshot of one upstream's counters, streamed as part of `Upstream_stats`
+#[derive(Debug, Clone, Serialize)]
+pub struct UpstreamStatsReport {
+    pub url: String,
+    pub connected: bool,
+    pub gave_up: bool,
+    pub flashblocks: u64,
+    pub wins: u64,
+    pub duplicates: u64,
+    pub errors: u64,
+    pub resumes: u64,
+    pub last_error: Option<String>,
+    /// Average lag behind the fastest upstream since the previous report
+    pub avg_lag_ms: Option<f64>,
+    /// Maximum lag behind the fastest upstream since the previous report
+    pub max_lag_ms: Option<f64>,
+}
+
+/// Periodic per-upstream health report
+#[derive(Debug, Clone, Serialize)]
+pub struct UpstreamStatsSnapshot {
+    pub upstreams: Vec<UpstreamStatsReport>,
+}
+
+/// Deduplicates flashblocks across upstreams and keeps per-upstream stats
+pub struct UpstreamMerger {
+    urls: Vec<String>,
+    stats: Vec<UpstreamStats>,
+    /// First arrival time of recently seen flashblocks
+    seen: HashMap<FlashblockKey, Instant>,
+    /// Insertion order of `seen`, oldest first, for eviction
+    seen_order: VecDeque<FlashblockKey>,
+}
+
+impl UpstreamMerger {
+    pub fn new(urls: &[String]) -> Self {
+        Self {
+            urls: urls.to_vec(),
+            stats: urls.iter().map(|_| UpstreamStats::default()).collect(),
+            seen: HashMap::new(),
+            seen_order: VecDeque::new(),
+        }
+    }
+
+    /// True once every upstream's supervisor has given up
+    pub fn all_gave_up(&self) -> bool {
+        self.stats.iter().all(|s| s.gave_up)
+    }
+
+    /// Handle an upstream event.
+    /// Returns the flashblock if this is the first copy seen across all upstreams.
+    pub fn accept(&mut self, event: UpstreamEvent, stream: &StreamOutput) -> Option<Flashblock> {
+        match event {
+            UpstreamEvent::Flashblock {
+                upstream,
+                received_at,
+                flashblock,
+            } => self.on_flashblock(upstream, received_at, *flashblock),
+            UpstreamEvent::BadFrame { upstream } => {
+                self.stats[upstream].errors += 1;
+                None
+            }
+            UpstreamEvent::Lost { upstream, reason } => {
+                let stats = &mut self.stats[upstream];
+                stats.errors += 1;
+                stats.connected = false;
+                stats.last_error = Some(reason);
+                None
+            }
+            UpstreamEvent::Resumed { upstream, resumed } => {
+                self.stats[upstream].resumes += 1;
+                stream
+                    .send("Upstream_resumed", &resumed)
+                    .unwrap_or_else(|e| {
+                        error!("Failed to send upstream resume to stream: {}", e);
+                    });
+                None
+            }
+            UpstreamEvent::GaveUp { upstream, error } => {
+                error!("{error}");
+                let stats = &mut self.stats[upstream];
+                stats.gave_up = true;
+                stats.connected = false;
+                if !self.all_gave_up() {
+                    warn!("Continuing with the remaining upstreams");
+                }
+                None
+            }
+        }
+    }
+
+    fn on_flashblock(
+        &mut self,
+        upstream: UpstreamId,
+        received_at: Instant,
+        flashblock: Flashblock,
+    ) -> Option<Flashblock> {
+        let stats = &mut self.stats[upstream];
+        stats.connected = true;
+        stats.flashblocks += 1;
+
+        let key = (flashblock.payload_id.clone(), flashblock.index);
+        if let Some(first_seen) = self.seen.get(&key) {
+            let lag = received_at.saturating_duration_since(*first_seen);
+            stats.duplicates += 1;
+            stats.lag_total += lag;
+            stats.lag_samples += 1;
+            stats.lag_max = stats.lag_max.max(lag);
+            return None;
+        }
+
+        stats.wins += 1;
+        stats.lag_samples += 1;
+
+        self.seen.insert(key.clone(), received_at);
+        self.seen_order.push_back(key);
+        while self.seen_order.len() > DEDUP_CAPACITY {
+            if let Some(old) = self.seen_order.pop_front() {
+                self.seen.remove(&old);
+            }
+        }
+
+        Some(flashblock)
+    }
+
+    /// Log and stream per-upstream stats, then reset the interval lag counters
+    pub fn report(&mut self, stream: &StreamOutput) {
+        let upstreams: Vec<_> = self
+            .urls
+            .iter()
+            .zip(self.stats.iter_mut())
+            .map(|(url, stats)| {
+                let report = UpstreamStatsReport {
+                    url: url.clone(),
+                    connected: stats.connected,
+                    gave_up: stats.gave_up,
+                    flashblocks: stats.flashblocks,
+                    wins: stats.wins,
+                    duplicates: stats.duplicates,
+                    errors: stats.errors,
+                    resumes: stats.resumes,
+                    last_error: stats.last_error.clone(),
+                    avg_lag_ms: (stats.lag_samples > 0)
+                        .then(|| stats.lag_total.as_secs_f64() * 1000.0 / stats.lag_samples as f64),
+                    max_lag_ms: (stats.lag_samples > 0)
+                        .then_some(stats.lag_max.as_secs_f64() * 1000.0),
+                };
+                stats.lag_total = Duration::ZERO;
+                stats.lag_samples = 0;
+                stats.lag_max = Duration::ZERO;
+                report
+            })
+            .collect();
+
+        for report in &upstreams {
+            info!(
+                url = %report.url,
+                connected = report.connected,
+                flashblocks = report.flashblocks,
+                wins = report.wins,
+                duplicates = report.duplicates,
+                errors = report.errors,
+                resumes = report.resumes,
+                avg_lag_ms = ?report.avg_lag_ms,
+                max_lag_ms = ?report.max_lag_ms,
+                "Upstream stats"
+            );
+        }
+
+        stream
+            .send("Upstream_stats", &UpstreamStatsSnapshot { upstreams })
+            .unwrap_or_else(|e| {
+                error!("Failed to send upstream stats to stream: {}", e);
+            });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flashblock(payload_id: &str, index: u64) -> Box<Flashblock> {
+        Box::new(
+            serde_json::from_str(&format!(
+                r#"{{"payload_id":"{payload_id}","index":{index}}}"#
+            ))
+            .unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_first_copy_wins() {
+        let urls = vec!["ws://a".to_string(), "ws://b".to_string()];
+        let mut merger = UpstreamMerger::new(&urls);
+        let stream = StreamOutput::print();
+        let now = Instant::now();
+
+        let first = merger.accept(
+            UpstreamEvent::Flashblock {
+                upstream: 1,
+                received_at: now,
+                flashblock: flashblock("0x01", 0),
+            },
+            &stream,
+        );
+        assert!(first.is_some());
+
+        let dup = merger.accept(
+            UpstreamEvent::Flashblock {
+                upstream: 0,
+                received_at: now + Duration::from_millis(20),
+                flashblock: flashblock("0x01", 0),
+            },
+            &stream,
+        );
+        assert!(dup.is_none());
+
+        assert_eq!(merger.stats[1].wins, 1);
+        assert_eq!(merger.stats[0].duplicates, 1);
+        assert_eq!(merger.stats[0].lag_max, Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_all_gave_up() {
+        let urls = vec!["ws://a".to_string(), "ws://b".to_string()];
+        let mut merger = UpstreamMerger::new(&urls);
+        let stream = StreamOutput::print();
+
+        for (upstream, url) in urls.iter().enumerate() {
+            assert!(!merger.all_gave_up());
+            merger.accept(
+                UpstreamEvent::GaveUp {
+                    upstream,
+                    error: crate::upstream::UpstreamError::GaveUp {
+                        url: url.clone(),
+                        attempts: 3,
+                        last_error: "connect failed".to_string(),
+                    },
+                },
+                &stream,
+            );
+        }
+        assert!(merger.all_gave_up());
+    }
+}
@@ -1,34 +1,87 @@
+use std::sync::{Arc, Mutex};
+
 use flashblocks_indexer_streams::{DataStream, StreamOutput};
+use flashblocks_types::aave::AaveReserveUpdated;
+use flashblocks_types::aave_positions::{AaveLiquidatable, AavePositionBook};
+use flashblocks_types::chainlink::FeedRegistry;
 use flashblocks_types::flashblocks::Flashblock;
+use flashblocks_types::tokens::{Annotated, TokenRegistry};
 use tracing::{debug, error, info};
 
 use super::ProtocolHandler;
 
-/// Handler for AAVE user action events.
-pub struct AaveHandler;
+/// Handler for AAVE pool events.
+///
+/// Amounts of reserves in the token registry are streamed with their symbol
+/// and decimal-adjusted value. Reserve rate updates are cached per reserve and
+/// streamed with their APYs.
+///
+/// User events and Chainlink answers also update the position book, and users
+/// whose estimated health factor drops below 1 are streamed as
+/// `Aave_liquidatable`.
+pub struct AaveHandler {
+    tokens: Arc<TokenRegistry>,
+    feeds: Arc<Mutex<FeedRegistry>>,
+    book: Mutex<AavePositionBook>,
+}
+
+impl AaveHandler {
+    pub fn new(
+        tokens: Arc<TokenRegistry>,
+        feeds: Arc<Mutex<FeedRegistry>>,
+        book: AavePositionBook,
+    ) -> Self {
+        Self {
+            tokens,
+            feeds,
+            book: Mutex::new(book),
+        }
+    }
+}
 
 impl ProtocolHandler for AaveHandler {
     fn process(&self, fb: &Flashblock, block_number: u64, stream: &StreamOutput) {
         let updates = fb.extract_aave_updates();
+        let mut book = self.book.lock().unwrap();
+        book.start_payload(&fb.payload_id, block_number);
+        // Prices only matter to the book if it has reserves to value
+        let (answers, confirmations) = if book.reserve_count() > 0 {
+            (
+                fb.extract_answer_updates(),
+                fb.extract_aggregator_confirmations(),
+            )
+        } else {
+            (Vec::new(), Vec::new())
+        };
 
-        if updates.is_empty() {
+        if updates.is_empty() && answers.is_empty() {
             return;
         }
 
-        info!(
-            block_number = block_number,
-            supplies = updates.supplies.len(),
-            withdraws = updates.withdraws.len(),
-            borrows = updates.borrows.len(),
-            repays = updates.repays.len(),
-            liquidations = updates.liquidations.len(),
-            total = updates.total_count(),
-            "AAVE user events detected"
-        );
+        if !updates.is_empty() {
+            info!(
+                block_number = block_number,
+                supplies = updates.supplies.len(),
+                withdraws = updates.withdraws.len(),
+                borrows = updates.borrows.len(),
+                repays = updates.repays.len(),
+                liquidations = updates.liquidations.len(),
+                reserve_data_updates = updates.reserve_data_updates.len(),
+                flash_loans = updates.flash_loans.len(),
+                emode_sets = updates.emode_sets.len(),
+                collateral_toggles = updates.collateral_toggles.len(),
+                isolation_mode_debt_updates = updates.isolation_mode_debt_updates.len(),
+                treasury_mints = updates.treasury_mints.len(),
+                back_unbacked = updates.back_unbacked.len(),
+                total = updates.total_count(),
+                "AAVE events detected"
+            );
+        }
 
         // Stream supply events
         for supply in &updates.supplies {
             debug!(
+                tx_hash = %supply.context.tx_hash,
                 pool = %supply.pool,
                 reserve = %supply.reserve,
                 user = %supply.user,
@@ -36,14 +89,24 @@ impl ProtocolHandler for AaveHandler {
                 amount = %supply.amount,
                 "AAVE Supply"
             );
-            stream.send("Aave_supply", supply).unwrap_or_else(|e| {
-                error!("Failed to send AAVE supply to stream: {}", e);
-            });
+            stream
+                .send(
+                    "Aave_supply",
+                    &Annotated::new(
+                        supply,
+                        self.tokens
+                            .amounts([("amount", supply.reserve, supply.amount.into())]),
+                    ),
+                )
+                .unwrap_or_else(|e| {
+                    error!("Failed to send AAVE supply to stream: {}", e);
+                });
         }
 
         // Stream withdraw events
         for withdraw in &updates.withdraws {
             debug!(
+                tx_hash = %withdraw.context.tx_hash,
                 pool = %withdraw.pool,
                 reserve = %withdraw.reserve,
                 user = %withdraw.user,
@@ -51,14 +114,24 @@ impl ProtocolHandler for AaveHandler {
                 amount = %withdraw.amount,
                 "AAVE Withdraw"
             );
-            stream.send("Aave_withdraw", withdraw).unwrap_or_else(|e| {
-                error!("Failed to send AAVE withdraw to stream: {}", e);
-            });
+            stream
+                .send(
+                    "Aave_withdraw",
+                    &Annotated::new(
+                        withdraw,
+                        self.tokens
+                            .amounts([("amount", withdraw.reserve, withdraw.amount.into())]),
+                    ),
+                )
+                .unwrap_or_else(|e| {
+                    error!("Failed to send AAVE withdraw to stream: {}", e);
+                });
         }
 
         // Stream borrow events
         for borrow in &updates.borrows {
             debug!(
+                tx_hash = %borrow.context.tx_hash,
                 pool = %borrow.pool,
                 reserve = %borrow.reserve,
                 user = %borrow.user,
@@ -67,14 +140,24 @@ impl ProtocolHandler for AaveHandler {
                 interest_rate_mode = borrow.interest_rate_mode,
                 "AAVE Borrow"
             );
-            stream.send("Aave_borrow", borrow).unwrap_or_else(|e| {
-                error!("Failed to send AAVE borrow to stream: {}", e);
-            });
+            stream
+                .send(
+                    "Aave_borrow",
+                    &Annotated::new(
+                        borrow,
+                        self.tokens
+                            .amounts([("amount", borrow.reserve, borrow.amount.into())]),
+                    ),
+                )
+                .unwrap_or_else(|e| {
+                    error!("Failed to send AAVE borrow to stream: {}", e);
+                });
         }
 
         // Stream repay events
         for repay in &updates.repays {
             debug!(
+                tx_hash = %repay.context.tx_hash,
                 pool = %repay.pool,
                 reserve = %repay.reserve,
                 user = %repay.user,
@@ -82,14 +165,24 @@ impl ProtocolHandler for AaveHandler {
                 amount = %repay.amount,
                 "AAVE Repay"
             );
-            stream.send("Aave_repay", repay).unwrap_or_else(|e| {
-                error!("Failed to send AAVE repay to stream: {}", e);
-            });
+            stream
+                .send(
+                    "Aave_repay",
+                    &Annotated::new(
+                        repay,
+                        self.tokens
+                            .amounts([("amount", repay.reserve, repay.amount.into())]),
+                    ),
+                )
+                .unwrap_or_else(|e| {
+                    error!("Failed to send AAVE repay to stream: {}", e);
+                });
         }
 
         // Stream liquidation events
         for liquidation in &updates.liquidations {
             debug!(
+                tx_hash = %liquidation.context.tx_hash,
                 pool = %liquidation.pool,
                 collateral_asset = %liquidation.collateral_asset,
                 debt_asset = %liquidation.debt_asset,
@@ -100,10 +193,207 @@ impl ProtocolHandler for AaveHandler {
                 "AAVE Liquidation"
             );
             stream
-                .send("Aave_liquidation", liquidation)
+                .send(
+                    "Aave_liquidation",
+                    &Annotated::new(
+                        liquidation,
+                        self.tokens.amounts([
+                            (
+                                "debt_to_cover",
+                                liquidation.debt_asset,
+                                liquidation.debt_to_cover.into(),
+                            ),
+                            (
+                                "liquidated_collateral_amount",
+                                liquidation.collateral_asset,
+                                liquidation.liquidated_collateral_amount.into(),
+                            ),
+                        ]),
+                    ),
+                )
                 .unwrap_or_else(|e| {
                     error!("Failed to send AAVE liquidation to stream: {}", e);
                 });
         }
+
+        // Stream flash loan events
+        for flash_loan in &updates.flash_loans {
+            debug!(
+                tx_hash = %flash_loan.context.tx_hash,
+                pool = %flash_loan.pool,
+                target = %flash_loan.target,
+                initiator = %flash_loan.initiator,
+                asset = %flash_loan.asset,
+                amount = %flash_loan.amount,
+                premium = %flash_loan.premium,
+                "AAVE FlashLoan"
+            );
+            stream
+                .send(
+                    "Aave_flash_loan",
+                    &Annotated::new(
+                        flash_loan,
+                        self.tokens.amounts([
+                            ("amount", flash_loan.asset, flash_loan.amount.into()),
+                            ("premium", flash_loan.asset, flash_loan.premium.into()),
+                        ]),
+                    ),
+                )
+                .unwrap_or_else(|e| {
+                    error!("Failed to send AAVE flash loan to stream: {}", e);
+                });
+        }
+
+        // Stream efficiency mode changes
+        for emode in &updates.emode_sets {
+            debug!(
+                tx_hash = %emode.context.tx_hash,
+                pool = %emode.pool,
+                user = %emode.user,
+                category_id = emode.category_id,
+                "AAVE UserEModeSet"
+            );
+            stream
+                .send("Aave_user_emode_set", emode)
+                .unwrap_or_else(|e| {
+                    error!("Failed to send AAVE eMode change to stream: {}", e);
+                });
+        }
+
+        // Stream collateral enable/disable events
+        for toggle in &updates.collateral_toggles {
+            debug!(
+                tx_hash = %toggle.context.tx_hash,
+                pool = %toggle.pool,
+                reserve = %toggle.reserve,
+                user = %toggle.user,
+                enabled = toggle.enabled,
+                "AAVE ReserveUsedAsCollateral"
+            );
+            stream
+                .send("Aave_collateral_toggled", toggle)
+                .unwrap_or_else(|e| {
+                    error!("Failed to send AAVE collateral toggle to stream: {}", e);
+                });
+        }
+
+        // Stream isolation mode debt updates
+        for isolation in &updates.isolation_mode_debt_updates {
+            debug!(
+                tx_hash = %isolation.context.tx_hash,
+                pool = %isolation.pool,
+                asset = %isolation.asset,
+                total_debt = %isolation.total_debt,
+                "AAVE IsolationModeTotalDebtUpdated"
+            );
+            stream
+                .send("Aave_isolation_mode_debt_updated", isolation)
+                .unwrap_or_else(|e| {
+                    error!("Failed to send AAVE isolation mode debt to stream: {}", e);
+                });
+        }
+
+        // Stream treasury mints
+        for mint in &updates.treasury_mints {
+            debug!(
+                tx_hash = %mint.context.tx_hash,
+                pool = %mint.pool,
+                reserve = %mint.reserve,
+                amount_minted = %mint.amount_minted,
+                "AAVE MintedToTreasury"
+            );
+            stream
+                .send(
+                    "Aave_minted_to_treasury",
+                    &Annotated::new(
+                        mint,
+                        self.tokens.amounts([(
+                            "amount_minted",
+                            mint.reserve,
+                            mint.amount_minted.into(),
+                        )]),
+                    ),
+                )
+                .unwrap_or_else(|e| {
+                    error!("Failed to send AAVE treasury mint to stream: {}", e);
+                });
+        }
+
+        // Stream backing of unbacked aTokens
+        for back in &updates.back_unbacked {
+            debug!(
+                tx_hash = %back.context.tx_hash,
+                pool = %back.pool,
+                reserve = %back.reserve,
+                backer = %back.backer,
+                amount = %back.amount,
+                fee = %back.fee,
+                "AAVE BackUnbacked"
+            );
+            stream
+                .send(
+                    "Aave_back_unbacked",
+                    &Annotated::new(
+                        back,
+                        self.tokens.amounts([
+                            ("amount", back.reserve, back.amount.into()),
+                            ("fee", back.reserve, back.fee.into()),
+                        ]),
+                    ),
+                )
+                .unwrap_or_else(|e| {
+                    error!("Failed to send AAVE back unbacked to stream: {}", e);
+                });
+        }
+
+        // Stream reserve rate updates
+        for update in &updates.reserve_data_updates {
+            let update = AaveReserveUpdated::from(update.clone());
+            debug!(
+                tx_hash = %update.update.context.tx_hash,
+                pool = %update.update.pool,
+                reserve = %update.update.reserve,
+                supply_apy = update.supply_apy,
+                variable_borrow_apy = update.variable_borrow_apy,
+                "AAVE ReserveDataUpdated"
+            );
+            stream
+                .send("Aave_reserve_updated", &update)
+                .unwrap_or_else(|e| {
+                    error!("Failed to send AAVE reserve update to stream: {}", e);
+                });
+        }
+
+        // Walk indexes, positions and prices in execution order, then stream
+        // users that became liquidatable along the way. The book resolves the
+        // flashblock's rotations itself and leaves the shared registry to the
+        // Chainlink handler, so answers are priced whichever handler runs first.
+        let dropped = book.apply_flashblock(
+            &self.feeds.lock().unwrap(),
+            &updates,
+            &answers,
+            &confirmations,
+        );
+        for health in dropped {
+            info!(
+                pool = %health.pool,
+                user = %health.user,
+                health_factor = health.health_factor,
+                total_collateral_base = health.total_collateral_base,
+                total_debt_base = health.total_debt_base,
+                "AAVE position liquidatable"
+            );
+            let alert = AaveLiquidatable {
+                health,
+                block_number,
+                flashblock_index: fb.index,
+                payload_id: fb.payload_id.clone(),
+            };
+            stream
+                .send("Aave_liquidatable", &alert)
+                .unwrap_or_else(|e| {
+                    error!("Failed to send AAVE liquidatable position to stream: {}", e);
+                });
+        }
     }
 }
@@ -1,30 +1,92 @@
+use std::sync::{Arc, Mutex};
+
 use flashblocks_indexer_streams::{DataStream, StreamOutput};
+use flashblocks_types::chainlink::FeedRegistry;
 use flashblocks_types::flashblocks::Flashblock;
 use tracing::{debug, error, info};
 
 use super::ProtocolHandler;
 
 /// Handler for Chainlink oracle price updates.
-pub struct ChainlinkHandler;
+///
+/// Events from aggregators in the feed registry are annotated with their
+/// proxy, pair and decimals, and answers with the normalized price. A proxy's
+/// `AggregatorConfirmed` rotates its feed to the new aggregator in the
+/// registry shared with the other handlers.
+pub struct ChainlinkHandler {
+    feeds: Arc<Mutex<FeedRegistry>>,
+}
+
+impl ChainlinkHandler {
+    pub fn new(feeds: Arc<Mutex<FeedRegistry>>) -> Self {
+        Self { feeds }
+    }
+}
 
 impl ProtocolHandler for ChainlinkHandler {
     fn process(&self, fb: &Flashblock, block_number: u64, stream: &StreamOutput) {
-        let oracle_updates = fb.extract_answer_updates();
+        let mut oracle_updates = fb.extract_answer_updates();
+        let mut new_rounds = fb.extract_new_rounds();
+        let mut transmissions = fb.extract_new_transmissions();
+        let mut confirmations = fb.extract_aggregator_confirmations();
 
-        if oracle_updates.is_empty() {
+        if oracle_updates.is_empty()
+            && new_rounds.is_empty()
+            && transmissions.is_empty()
+            && confirmations.is_empty()
+        {
             return;
         }
 
         info!(
             block_number = block_number,
-            count = oracle_updates.len(),
-            "Chainlink AnswerUpdated events detected"
+            answer_updates = oracle_updates.len(),
+            new_rounds = new_rounds.len(),
+            transmissions = transmissions.len(),
+            aggregator_confirmations = confirmations.len(),
+            "Chainlink events detected"
         );
 
+        // Rotate and annotate in execution order, so answers are flagged
+        // against the aggregator that was current when they were emitted
+        self.feeds.lock().unwrap().apply(
+            &mut confirmations,
+            &mut oracle_updates,
+            &mut new_rounds,
+            &mut transmissions,
+        );
+        for confirmed in confirmations.iter().filter(|c| c.feed_info.is_some()) {
+            info!(
+                proxy = %confirmed.proxy,
+                previous = %confirmed.previous,
+                latest = %confirmed.latest,
+                "Chainlink feed rotated to a new aggregator"
+            );
+        }
+
+        for confirmed in &confirmations {
+            debug!(
+                tx_hash = %confirmed.context.tx_hash,
+                proxy = %confirmed.proxy,
+                previous = %confirmed.previous,
+                latest = %confirmed.latest,
+                "AggregatorConfirmed"
+            );
+
+            stream
+                .send("Chainlink_aggregator_confirmed", confirmed)
+                .unwrap_or_else(|e| {
+                    error!("Failed to send aggregator confirmation to stream: {}", e);
+                });
+        }
+
         for update in &oracle_updates {
             debug!(
+                tx_hash = %update.context.tx_hash,
                 feed = %update.feed,
+                pair = ?update.feed_info.as_ref().map(|f| &f.pair),
                 answer = %update.answer,
+                price = ?update.price,
                 round_id = %update.round_id,
                 updated_at = %update.updated_at,
                 "AnswerUpdated"
@@ -36,5 +98,42 @@ impl ProtocolHandler for ChainlinkHandler {
                     error!("Failed to send oracle update to stream: {}", e);
                 });
         }
+
+        for round in &new_rounds {
+            debug!(
+                tx_hash = %round.context.tx_hash,
+                feed = %round.feed,
+                round_id = %round.round_id,
+                started_by = %round.started_by,
+                started_at = %round.started_at,
+                "NewRound"
+            );
+
+            stream
+                .send("Chainlink_new_round", round)
+                .unwrap_or_else(|e| {
+                    error!("Failed to send new round to stream: {}", e);
+                });
+        }
+
+        for transmission in &transmissions {
+            debug!(
+                tx_hash = %transmission.context.tx_hash,
+                feed = %transmission.feed,
+                pair = ?transmission.feed_info.as_ref().map(|f| &f.pair),
+                aggregator_round_id = transmission.aggregator_round_id,
+                answer = %transmission.answer,
+                transmitter = %transmission.transmitter,
+                observations = transmission.observations.len(),
+                juels_per_fee_coin = %transmission.juels_per_fee_coin,
+                "NewTransmission"
+            );
+
+            stream
+                .send("Chainlink_new_transmission", transmission)
+                .unwrap_or_else(|e| {
+                    error!("Failed to send OCR2 transmission to stream: {}", e);
+                });
+        }
     }
 }
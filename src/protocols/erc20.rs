@@ -0,0 +1,325 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use alloy_primitives::utils::parse_units;
+use alloy_primitives::{Address, U256};
+use flashblocks_indexer_streams::{DataStream, StreamOutput};
+use flashblocks_types::erc20::{LargeTransfer, ParsedApproval, ParsedTransfer};
+use flashblocks_types::flashblocks::Flashblock;
+use flashblocks_types::tokens::{Annotated, TokenRegistry};
+use tracing::{debug, error, info};
+
+use super::ProtocolHandler;
+
+/// Amount of a `--large-transfer` threshold
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThresholdAmount {
+    /// In the token's smallest unit
+    Raw(U256),
+    /// In whole tokens, scaled by the token's decimals from the registry
+    Decimal(String),
+}
+
+/// A `Large_transfer` threshold as given on the command line:
+/// `<token>=<amount>` in whole tokens, or `<token>=raw:<amount>` in the
+/// token's smallest unit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThresholdSpec {
+    pub token: Address,
+    pub amount: ThresholdAmount,
+}
+
+impl FromStr for ThresholdSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (token, amount) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected <token>=<amount>, got {s:?}"))?;
+        let token = token
+            .trim()
+            .parse()
+            .map_err(|e| format!("invalid token address {token:?}: {e}"))?;
+        let amount = match amount.trim().strip_prefix("raw:") {
+            Some(raw) => ThresholdAmount::Raw(
+                raw.parse()
+                    .map_err(|e| format!("invalid raw amount {raw:?}: {e}"))?,
+            ),
+            None => ThresholdAmount::Decimal(amount.trim().to_string()),
+        };
+        Ok(Self { token, amount })
+    }
+}
+
+impl ThresholdSpec {
+    /// Resolve the threshold to the token's smallest unit. Decimal amounts
+    /// need the token to be in the registry.
+    pub fn resolve(&self, tokens: &TokenRegistry) -> Result<(Address, U256), String> {
+        let raw = match &self.amount {
+            ThresholdAmount::Raw(raw) => *raw,
+            ThresholdAmount::Decimal(amount) => {
+                let info = tokens.token(&self.token).ok_or_else(|| {
+                    format!(
+                        "token {} is not in the token list; give its threshold as raw:<amount>",
+                        self.token
+                    )
+                })?;
+                let units = parse_units(amount, info.decimals)
+                    .map_err(|e| format!("invalid amount {amount:?} for {}: {e}", info.symbol))?;
+                if units.is_negative() {
+                    return Err(format!("negative threshold {amount:?} for {}", info.symbol));
+                }
+                units.get_absolute()
+            }
+        };
+        Ok((self.token, raw))
+    }
+}
+
+/// Which ERC-20 events to stream
+#[derive(Debug, Clone, Default)]
+pub struct Erc20Config {
+    /// Stream transfers and approvals whose token, sender, recipient, owner
+    /// or spender is in this list
+    pub watchlist: HashSet<Address>,
+    /// Stream every transfer and approval, ignoring the watchlist
+    pub all: bool,
+    /// Per-token minimum value, in the token's smallest unit, for a
+    /// `Large_transfer` alert
+    pub thresholds: HashMap<Address, U256>,
+}
+
+impl Erc20Config {
+    fn watches_transfer(&self, transfer: &ParsedTransfer) -> bool {
+        self.all
+            || [transfer.token, transfer.from, transfer.to]
+                .iter()
+                .any(|a| self.watchlist.contains(a))
+    }
+
+    fn watches_approval(&self, approval: &ParsedApproval) -> bool {
+        self.all
+            || [approval.token, approval.owner, approval.spender]
+                .iter()
+                .any(|a| self.watchlist.contains(a))
+    }
+
+    /// The threshold `transfer` meets, if its token has one
+    fn large_threshold(&self, transfer: &ParsedTransfer) -> Option<U256> {
+        self.thresholds
+            .get(&transfer.token)
+            .copied()
+            .filter(|threshold| transfer.value >= *threshold)
+    }
+}
+
+/// Handler for ERC-20 Transfer and Approval events.
+///
+/// Nearly every flashblock moves tokens, so only events touching the
+/// watchlist are streamed unless `all` is set. Transfers at or above their
+/// token's threshold are streamed as `Large_transfer` alerts regardless of
+/// the watchlist.
+pub struct Erc20Handler {
+    tokens: Arc<TokenRegistry>,
+    config: Erc20Config,
+}
+
+impl Erc20Handler {
+    pub fn new(tokens: Arc<TokenRegistry>, config: Erc20Config) -> Self {
+        Self { tokens, config }
+    }
+}
+
+impl ProtocolHandler for Erc20Handler {
+    fn process(&self, fb: &Flashblock, block_number: u64, stream: &StreamOutput) {
+        if !self.config.all && self.config.watchlist.is_empty() && self.config.thresholds.is_empty()
+        {
+            return;
+        }
+
+        let updates = fb.extract_erc20_updates();
+
+        let transfers: Vec<_> = updates
+            .transfers
+            .iter()
+            .filter(|t| self.config.watches_transfer(t))
+            .collect();
+        let approvals: Vec<_> = updates
+            .approvals
+            .iter()
+            .filter(|a| self.config.watches_approval(a))
+            .collect();
+        let large: Vec<_> = updates
+            .transfers
+            .iter()
+            .filter_map(|t| {
+                self.config
+                    .large_threshold(t)
+                    .map(|threshold| LargeTransfer {
+                        transfer: t.clone(),
+                        threshold,
+                    })
+            })
+            .collect();
+
+        if transfers.is_empty() && approvals.is_empty() && large.is_empty() {
+            return;
+        }
+
+        info!(
+            block_number = block_number,
+            transfers = transfers.len(),
+            approvals = approvals.len(),
+            large_transfers = large.len(),
+            total = updates.total_count(),
+            "ERC-20 events detected"
+        );
+
+        // Stream watched transfers
+        for transfer in transfers {
+            debug!(
+                tx_hash = %transfer.context.tx_hash,
+                token = %transfer.token,
+                from = %transfer.from,
+                to = %transfer.to,
+                value = %transfer.value,
+                "ERC-20 Transfer"
+            );
+
+            let tokens = self
+                .tokens
+                .amounts([("value", transfer.token, transfer.value.into())]);
+            stream
+                .send("Erc20_transfer", &Annotated::new(transfer, tokens))
+                .unwrap_or_else(|e| {
+                    error!("Failed to send ERC-20 transfer to stream: {}", e);
+                });
+        }
+
+        // Stream watched approvals
+        for approval in approvals {
+            debug!(
+                tx_hash = %approval.context.tx_hash,
+                token = %approval.token,
+                owner = %approval.owner,
+                spender = %approval.spender,
+                value = %approval.value,
+                "ERC-20 Approval"
+            );
+
+            let tokens = self
+                .tokens
+                .amounts([("value", approval.token, approval.value.into())]);
+            stream
+                .send("Erc20_approval", &Annotated::new(approval, tokens))
+                .unwrap_or_else(|e| {
+                    error!("Failed to send ERC-20 approval to stream: {}", e);
+                });
+        }
+
+        // Stream large transfer alerts
+        for alert in &large {
+            let transfer = &alert.transfer;
+            info!(
+                tx_hash = %transfer.context.tx_hash,
+                token = %transfer.token,
+                from = %transfer.from,
+                to = %transfer.to,
+                value = %transfer.value,
+                threshold = %alert.threshold,
+                "Large ERC-20 transfer"
+            );
+
+            let tokens = self.tokens.amounts([
+                ("value", transfer.token, transfer.value.into()),
+                ("threshold", transfer.token, alert.threshold.into()),
+            ]);
+            stream
+                .send("Large_transfer", &Annotated::new(alert, tokens))
+                .unwrap_or_else(|e| {
+                    error!("Failed to send large transfer to stream: {}", e);
+                });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy_primitives::address;
+    use flashblocks_types::flashblocks::EventContext;
+
+    const USDC: Address = address!("833589fCD6eDb6E08f4c7C32D4f71b54bdA02913");
+
+    fn transfer(token: Address, from: Address, to: Address, value: u64) -> ParsedTransfer {
+        ParsedTransfer {
+            context: EventContext::default(),
+            token,
+            from,
+            to,
+            value: U256::from(value),
+        }
+    }
+
+    #[test]
+    fn test_threshold_spec() {
+        let tokens = TokenRegistry::bundled();
+
+        let spec: ThresholdSpec = format!("{USDC}=250000.5").parse().unwrap();
+        assert_eq!(
+            spec.amount,
+            ThresholdAmount::Decimal("250000.5".to_string())
+        );
+        assert_eq!(
+            spec.resolve(&tokens).unwrap(),
+            (USDC, U256::from(250_000_500_000u64))
+        );
+
+        let unknown = Address::repeat_byte(0x77);
+        let spec: ThresholdSpec = format!("{unknown}=raw:1000").parse().unwrap();
+        assert_eq!(spec.resolve(&tokens).unwrap(), (unknown, U256::from(1000)));
+
+        let spec: ThresholdSpec = format!("{unknown}=1000").parse().unwrap();
+        assert!(spec.resolve(&tokens).is_err());
+        let spec: ThresholdSpec = format!("{USDC}=-5").parse().unwrap();
+        assert!(spec.resolve(&tokens).is_err());
+        assert!("1000".parse::<ThresholdSpec>().is_err());
+        assert!(format!("{USDC}=raw:1.5").parse::<ThresholdSpec>().is_err());
+    }
+
+    #[test]
+    fn test_watchlist_and_thresholds() {
+        let watched = Address::repeat_byte(0x01);
+        let other = Address::repeat_byte(0x02);
+        let config = Erc20Config {
+            watchlist: HashSet::from([watched, USDC]),
+            thresholds: HashMap::from([(USDC, U256::from(1_000))]),
+            ..Default::default()
+        };
+
+        let token = Address::repeat_byte(0x70);
+        assert!(config.watches_transfer(&transfer(token, other, watched, 1)));
+        assert!(config.watches_transfer(&transfer(USDC, other, other, 1)));
+        assert!(!config.watches_transfer(&transfer(token, other, other, 1)));
+
+        assert_eq!(
+            config.large_threshold(&transfer(USDC, other, other, 1_000)),
+            Some(U256::from(1_000))
+        );
+        assert_eq!(
+            config.large_threshold(&transfer(USDC, other, other, 999)),
+            None
+        );
+        assert_eq!(
+            config.large_threshold(&transfer(token, other, other, u64::MAX)),
+            None
+        );
+
+        let all = Erc20Config {
+            all: true,
+            ..Default::default()
+        };
+        assert!(all.watches_transfer(&transfer(token, other, other, 1)));
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::Arc;
+
+use alloy_primitives::Address;
+use flashblocks_indexer_streams::{DataStream, StreamOutput};
+use flashblocks_types::flashblocks::Flashblock;
+use flashblocks_types::tokens::{Annotated, TokenRegistry};
+use tracing::{debug, error, info};
+
+use super::ProtocolHandler;
+
+/// Handler for ERC-4337 EntryPoint v0.6 and v0.7 events.
+///
+/// Each user operation's outcome is streamed with its revert reason, if it
+/// failed. Events from other handlers carry `context.user_op` when they were
+/// emitted by a user operation.
+pub struct Erc4337Handler {
+    tokens: Arc<TokenRegistry>,
+}
+
+impl Erc4337Handler {
+    pub fn new(tokens: Arc<TokenRegistry>) -> Self {
+        Self { tokens }
+    }
+}
+
+impl ProtocolHandler for Erc4337Handler {
+    fn process(&self, fb: &Flashblock, block_number: u64, stream: &StreamOutput) {
+        let updates = fb.extract_erc4337_updates();
+
+        if updates.is_empty() {
+            return;
+        }
+
+        info!(
+            block_number = block_number,
+            user_operations = updates.user_operations.len(),
+            accounts_deployed = updates.accounts_deployed.len(),
+            reverted = updates.revert_reasons.len(),
+            total = updates.total_count(),
+            "ERC-4337 events detected"
+        );
+
+        // Stream user operation outcomes
+        for op in &updates.user_operations {
+            debug!(
+                tx_hash = %op.context.tx_hash,
+                user_op_hash = %op.user_op_hash,
+                sender = %op.sender,
+                paymaster = ?op.paymaster,
+                success = op.success,
+                actual_gas_cost = %op.actual_gas_cost,
+                "ERC-4337 UserOperation"
+            );
+
+            // Gas is paid in native ETH, listed at the zero address
+            let tokens = self.tokens.amounts([(
+                "actual_gas_cost",
+                Address::ZERO,
+                op.actual_gas_cost.into(),
+            )]);
+            stream
+                .send("Erc4337_user_op", &Annotated::new(op, tokens))
+                .unwrap_or_else(|e| {
+                    error!("Failed to send ERC-4337 user operation to stream: {}", e);
+                });
+        }
+
+        // Stream account deployments
+        for deployed in &updates.accounts_deployed {
+            debug!(
+                tx_hash = %deployed.context.tx_hash,
+                user_op_hash = %deployed.user_op_hash,
+                sender = %deployed.sender,
+                factory = %deployed.factory,
+                paymaster = ?deployed.paymaster,
+                "ERC-4337 AccountDeployed"
+            );
+
+            stream
+                .send("Erc4337_account_deployed", deployed)
+                .unwrap_or_else(|e| {
+                    error!(
+                        "Failed to send ERC-4337 account deployment to stream: {}",
+                        e
+                    );
+                });
+        }
+    }
+}
@@ -3,20 +3,37 @@
 //! To add a new protocol:
 //! 1. Create a new module (e.g., `my_protocol.rs`)
 //! 2. Implement the `ProtocolHandler` trait
-//! 3. Add the handler to the `ALL_HANDLERS` array in this file
+//! 3. Add the handler to `build_handlers` in this file
 
 mod aave;
 mod chainlink;
+mod erc20;
+mod erc4337;
 mod morpho;
+mod univ2;
 mod univ3;
+mod univ4;
+mod weth;
+
+use std::sync::{Arc, Mutex};
 
 use flashblocks_indexer_streams::StreamOutput;
+use flashblocks_types::aave_positions::AavePositionBook;
+use flashblocks_types::chainlink::FeedRegistry;
+use flashblocks_types::dex::DexRegistry;
 use flashblocks_types::flashblocks::Flashblock;
+use flashblocks_types::morpho::{MorphoMarketRegistry, MorphoTotalsCache};
+use flashblocks_types::tokens::TokenRegistry;
 
 pub use aave::AaveHandler;
 pub use chainlink::ChainlinkHandler;
+pub use erc20::{Erc20Config, Erc20Handler, ThresholdSpec};
+pub use erc4337::Erc4337Handler;
 pub use morpho::MorphoHandler;
+pub use univ2::UniV2Handler;
 pub use univ3::UniV3Handler;
+pub use univ4::UniV4Handler;
+pub use weth::WethHandler;
 
 /// Trait for protocol-specific event extraction and streaming.
 ///
@@ -34,19 +51,61 @@ pub trait ProtocolHandler: Send + Sync {
     fn process(&self, fb: &Flashblock, block_number: u64, stream: &StreamOutput);
 }
 
-/// All registered protocol handlers.
+/// Runtime configuration for the protocol handlers, loaded at startup
+#[derive(Debug, Default)]
+pub struct ProtocolConfig {
+    /// Chainlink aggregator -> feed mapping
+    pub feeds: FeedRegistry,
+    /// Token symbols and decimals, and pool token pairs
+    pub tokens: TokenRegistry,
+    /// V3-style pool factories and pools by DEX
+    pub dexes: DexRegistry,
+    /// ERC-20 watchlist and large-transfer thresholds
+    pub erc20: Erc20Config,
+    /// AAVE positions and reserve parameters for health factor estimates
+    pub aave_positions: AavePositionBook,
+    /// Morpho Blue markets created before startup
+    pub morpho_markets: MorphoMarketRegistry,
+    /// Supply and borrow totals of the snapshot's Morpho Blue markets
+    pub morpho_totals: MorphoTotalsCache,
+}
+
+/// Build all registered protocol handlers.
 /// Add new handlers here to include them in parallel processing.
-pub static ALL_HANDLERS: &[&dyn ProtocolHandler] = &[
-    &UniV3Handler,
-    &ChainlinkHandler,
-    &AaveHandler,
-    &MorphoHandler,
-];
+pub fn build_handlers(config: ProtocolConfig) -> Vec<Box<dyn ProtocolHandler>> {
+    let tokens = Arc::new(config.tokens);
+    // Shared so the Chainlink handler's rotations reach the AAVE handler
+    let feeds = Arc::new(Mutex::new(config.feeds));
+    vec![
+        Box::new(UniV3Handler::new(tokens.clone(), config.dexes)),
+        Box::new(UniV2Handler::new(tokens.clone())),
+        Box::new(UniV4Handler::new(tokens.clone())),
+        Box::new(Erc20Handler::new(tokens.clone(), config.erc20)),
+        Box::new(WethHandler::new(tokens.clone())),
+        Box::new(Erc4337Handler::new(tokens.clone())),
+        Box::new(ChainlinkHandler::new(feeds.clone())),
+        Box::new(AaveHandler::new(
+            tokens.clone(),
+            feeds,
+            config.aave_positions,
+        )),
+        Box::new(MorphoHandler::new(
+            tokens,
+            config.morpho_markets,
+            config.morpho_totals,
+        )),
+    ]
+}
 
 /// Process a flashblock through all protocol handlers in parallel.
-pub fn process_all_protocols(fb: &Flashblock, block_number: u64, stream: &StreamOutput) {
+pub fn process_all_protocols(
+    handlers: &[Box<dyn ProtocolHandler>],
+    fb: &Flashblock,
+    block_number: u64,
+    stream: &StreamOutput,
+) {
     rayon::scope(|s| {
-        for handler in ALL_HANDLERS {
+        for handler in handlers {
             s.spawn(|_| {
                 handler.process(fb, block_number, stream);
             });
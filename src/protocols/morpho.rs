@@ -1,20 +1,80 @@
+use std::sync::{Arc, Mutex};
+
+use alloy_primitives::U256;
 use flashblocks_indexer_streams::{DataStream, StreamOutput};
 use flashblocks_types::flashblocks::Flashblock;
+use flashblocks_types::morpho::{MorphoMarketParams, MorphoMarketRegistry, MorphoTotalsCache};
+use flashblocks_types::tokens::{Annotated, TokenAmount, TokenRegistry};
 use tracing::{debug, error, info};
 
 use super::ProtocolHandler;
 
 /// Handler for Morpho Blue lending protocol events.
-pub struct MorphoHandler;
+///
+/// Market params are learned from `CreateMarket` events on top of the
+/// snapshot the handler starts with, and attached to every other event of a
+/// known market. Markets' supply and borrow totals are tracked to value each
+/// event's shares in assets. Amounts are resolved through the market's loan
+/// and collateral tokens.
+pub struct MorphoHandler {
+    tokens: Arc<TokenRegistry>,
+    markets: Mutex<MorphoMarketRegistry>,
+    totals: Mutex<MorphoTotalsCache>,
+}
+
+impl MorphoHandler {
+    pub fn new(
+        tokens: Arc<TokenRegistry>,
+        markets: MorphoMarketRegistry,
+        totals: MorphoTotalsCache,
+    ) -> Self {
+        Self {
+            tokens,
+            markets: Mutex::new(markets),
+            totals: Mutex::new(totals),
+        }
+    }
+
+    /// Resolve amounts in a known market's loan and collateral tokens
+    fn market_amounts(
+        &self,
+        market: Option<&MorphoMarketParams>,
+        loan: impl IntoIterator<Item = (&'static str, Option<U256>)>,
+        collateral: impl IntoIterator<Item = (&'static str, U256)>,
+    ) -> Vec<TokenAmount> {
+        let Some(market) = market else {
+            return Vec::new();
+        };
+        let loan = loan
+            .into_iter()
+            .filter_map(|(field, raw)| Some((field, market.loan_token, raw?.into())));
+        let collateral = collateral
+            .into_iter()
+            .map(|(field, raw)| (field, market.collateral_token, raw.into()));
+        self.tokens.amounts(loan.chain(collateral))
+    }
+}
 
 impl ProtocolHandler for MorphoHandler {
     fn process(&self, fb: &Flashblock, block_number: u64, stream: &StreamOutput) {
-        let updates = fb.extract_morpho_updates();
+        let mut updates = fb.extract_morpho_updates();
+        // Every flashblock moves the payload, so a replaced one is rolled
+        // back even if its replacement has no Morpho events
+        let mut totals = self.totals.lock().unwrap();
+        totals.start_payload(&fb.payload_id, block_number);
 
         if updates.is_empty() {
             return;
         }
 
+        let known_markets = {
+            let mut markets = self.markets.lock().unwrap();
+            markets.annotate(&mut updates);
+            markets.len()
+        };
+        totals.apply(&mut updates);
+        drop(totals);
+
         info!(
             block_number = block_number,
             supplies = updates.supplies.len(),
@@ -25,13 +85,20 @@ impl ProtocolHandler for MorphoHandler {
             withdraw_collaterals = updates.withdraw_collaterals.len(),
             liquidations = updates.liquidations.len(),
             create_markets = updates.create_markets.len(),
+            accrue_interests = updates.accrue_interests.len(),
+            flash_loans = updates.flash_loans.len(),
+            set_fees = updates.set_fees.len(),
+            set_authorizations = updates.set_authorizations.len(),
+            increment_nonces = updates.increment_nonces.len(),
             total = updates.total_count(),
+            known_markets = known_markets,
             "Morpho events detected"
         );
 
         // Stream supply events
         for supply in &updates.supplies {
             debug!(
+                tx_hash = %supply.context.tx_hash,
                 morpho = %supply.morpho,
                 market_id = %supply.market_id,
                 caller = %supply.caller,
@@ -40,14 +107,30 @@ impl ProtocolHandler for MorphoHandler {
                 shares = %supply.shares,
                 "Morpho Supply"
             );
-            stream.send("Morpho_supply", supply).unwrap_or_else(|e| {
-                error!("Failed to send Morpho supply to stream: {}", e);
-            });
+            stream
+                .send(
+                    "Morpho_supply",
+                    &Annotated::new(
+                        supply,
+                        self.market_amounts(
+                            supply.market.as_ref(),
+                            [
+                                ("assets", Some(supply.assets)),
+                                ("shares_in_assets", supply.shares_in_assets),
+                            ],
+                            [],
+                        ),
+                    ),
+                )
+                .unwrap_or_else(|e| {
+                    error!("Failed to send Morpho supply to stream: {}", e);
+                });
         }
 
         // Stream withdraw events
         for withdraw in &updates.withdraws {
             debug!(
+                tx_hash = %withdraw.context.tx_hash,
                 morpho = %withdraw.morpho,
                 market_id = %withdraw.market_id,
                 caller = %withdraw.caller,
@@ -57,14 +140,30 @@ impl ProtocolHandler for MorphoHandler {
                 shares = %withdraw.shares,
                 "Morpho Withdraw"
             );
-            stream.send("Morpho_withdraw", withdraw).unwrap_or_else(|e| {
-                error!("Failed to send Morpho withdraw to stream: {}", e);
-            });
+            stream
+                .send(
+                    "Morpho_withdraw",
+                    &Annotated::new(
+                        withdraw,
+                        self.market_amounts(
+                            withdraw.market.as_ref(),
+                            [
+                                ("assets", Some(withdraw.assets)),
+                                ("shares_in_assets", withdraw.shares_in_assets),
+                            ],
+                            [],
+                        ),
+                    ),
+                )
+                .unwrap_or_else(|e| {
+                    error!("Failed to send Morpho withdraw to stream: {}", e);
+                });
         }
 
         // Stream borrow events
         for borrow in &updates.borrows {
             debug!(
+                tx_hash = %borrow.context.tx_hash,
                 morpho = %borrow.morpho,
                 market_id = %borrow.market_id,
                 caller = %borrow.caller,
@@ -74,14 +173,30 @@ impl ProtocolHandler for MorphoHandler {
                 shares = %borrow.shares,
                 "Morpho Borrow"
             );
-            stream.send("Morpho_borrow", borrow).unwrap_or_else(|e| {
-                error!("Failed to send Morpho borrow to stream: {}", e);
-            });
+            stream
+                .send(
+                    "Morpho_borrow",
+                    &Annotated::new(
+                        borrow,
+                        self.market_amounts(
+                            borrow.market.as_ref(),
+                            [
+                                ("assets", Some(borrow.assets)),
+                                ("shares_in_assets", borrow.shares_in_assets),
+                            ],
+                            [],
+                        ),
+                    ),
+                )
+                .unwrap_or_else(|e| {
+                    error!("Failed to send Morpho borrow to stream: {}", e);
+                });
         }
 
         // Stream repay events
         for repay in &updates.repays {
             debug!(
+                tx_hash = %repay.context.tx_hash,
                 morpho = %repay.morpho,
                 market_id = %repay.market_id,
                 caller = %repay.caller,
@@ -90,14 +205,30 @@ impl ProtocolHandler for MorphoHandler {
                 shares = %repay.shares,
                 "Morpho Repay"
             );
-            stream.send("Morpho_repay", repay).unwrap_or_else(|e| {
-                error!("Failed to send Morpho repay to stream: {}", e);
-            });
+            stream
+                .send(
+                    "Morpho_repay",
+                    &Annotated::new(
+                        repay,
+                        self.market_amounts(
+                            repay.market.as_ref(),
+                            [
+                                ("assets", Some(repay.assets)),
+                                ("shares_in_assets", repay.shares_in_assets),
+                            ],
+                            [],
+                        ),
+                    ),
+                )
+                .unwrap_or_else(|e| {
+                    error!("Failed to send Morpho repay to stream: {}", e);
+                });
         }
 
         // Stream supply collateral events
         for supply_collateral in &updates.supply_collaterals {
             debug!(
+                tx_hash = %supply_collateral.context.tx_hash,
                 morpho = %supply_collateral.morpho,
                 market_id = %supply_collateral.market_id,
                 caller = %supply_collateral.caller,
@@ -106,7 +237,17 @@ impl ProtocolHandler for MorphoHandler {
                 "Morpho SupplyCollateral"
             );
             stream
-                .send("Morpho_supply_collateral", supply_collateral)
+                .send(
+                    "Morpho_supply_collateral",
+                    &Annotated::new(
+                        supply_collateral,
+                        self.market_amounts(
+                            supply_collateral.market.as_ref(),
+                            [],
+                            [("assets", supply_collateral.assets)],
+                        ),
+                    ),
+                )
                 .unwrap_or_else(|e| {
                     error!("Failed to send Morpho supply collateral to stream: {}", e);
                 });
@@ -115,6 +256,7 @@ impl ProtocolHandler for MorphoHandler {
         // Stream withdraw collateral events
         for withdraw_collateral in &updates.withdraw_collaterals {
             debug!(
+                tx_hash = %withdraw_collateral.context.tx_hash,
                 morpho = %withdraw_collateral.morpho,
                 market_id = %withdraw_collateral.market_id,
                 caller = %withdraw_collateral.caller,
@@ -124,7 +266,17 @@ impl ProtocolHandler for MorphoHandler {
                 "Morpho WithdrawCollateral"
             );
             stream
-                .send("Morpho_withdraw_collateral", withdraw_collateral)
+                .send(
+                    "Morpho_withdraw_collateral",
+                    &Annotated::new(
+                        withdraw_collateral,
+                        self.market_amounts(
+                            withdraw_collateral.market.as_ref(),
+                            [],
+                            [("assets", withdraw_collateral.assets)],
+                        ),
+                    ),
+                )
                 .unwrap_or_else(|e| {
                     error!("Failed to send Morpho withdraw collateral to stream: {}", e);
                 });
@@ -133,6 +285,7 @@ impl ProtocolHandler for MorphoHandler {
         // Stream liquidation events
         for liquidation in &updates.liquidations {
             debug!(
+                tx_hash = %liquidation.context.tx_hash,
                 morpho = %liquidation.morpho,
                 market_id = %liquidation.market_id,
                 caller = %liquidation.caller,
@@ -145,7 +298,28 @@ impl ProtocolHandler for MorphoHandler {
                 "Morpho Liquidation"
             );
             stream
-                .send("Morpho_liquidation", liquidation)
+                .send(
+                    "Morpho_liquidation",
+                    &Annotated::new(
+                        liquidation,
+                        self.market_amounts(
+                            liquidation.market.as_ref(),
+                            [
+                                ("repaid_assets", Some(liquidation.repaid_assets)),
+                                (
+                                    "repaid_shares_in_assets",
+                                    liquidation.repaid_shares_in_assets,
+                                ),
+                                ("bad_debt_assets", Some(liquidation.bad_debt_assets)),
+                                (
+                                    "bad_debt_shares_in_assets",
+                                    liquidation.bad_debt_shares_in_assets,
+                                ),
+                            ],
+                            [("seized_assets", liquidation.seized_assets)],
+                        ),
+                    ),
+                )
                 .unwrap_or_else(|e| {
                     error!("Failed to send Morpho liquidation to stream: {}", e);
                 });
@@ -154,6 +328,7 @@ impl ProtocolHandler for MorphoHandler {
         // Stream create market events
         for create_market in &updates.create_markets {
             debug!(
+                tx_hash = %create_market.context.tx_hash,
                 morpho = %create_market.morpho,
                 market_id = %create_market.market_id,
                 loan_token = %create_market.loan_token,
@@ -169,5 +344,97 @@ impl ProtocolHandler for MorphoHandler {
                     error!("Failed to send Morpho create market to stream: {}", e);
                 });
         }
+
+        // Stream interest accrual events
+        for accrual in &updates.accrue_interests {
+            debug!(
+                tx_hash = %accrual.context.tx_hash,
+                morpho = %accrual.morpho,
+                market_id = %accrual.market_id,
+                prev_borrow_rate = %accrual.prev_borrow_rate,
+                interest = %accrual.interest,
+                fee_shares = %accrual.fee_shares,
+                "Morpho AccrueInterest"
+            );
+            stream
+                .send("Morpho_accrue_interest", accrual)
+                .unwrap_or_else(|e| {
+                    error!("Failed to send Morpho accrue interest to stream: {}", e);
+                });
+        }
+
+        // Stream flash loan events
+        for flash_loan in &updates.flash_loans {
+            debug!(
+                tx_hash = %flash_loan.context.tx_hash,
+                morpho = %flash_loan.morpho,
+                caller = %flash_loan.caller,
+                token = %flash_loan.token,
+                assets = %flash_loan.assets,
+                "Morpho FlashLoan"
+            );
+            stream
+                .send(
+                    "Morpho_flash_loan",
+                    &Annotated::new(
+                        flash_loan,
+                        self.tokens.amounts([(
+                            "assets",
+                            flash_loan.token,
+                            flash_loan.assets.into(),
+                        )]),
+                    ),
+                )
+                .unwrap_or_else(|e| {
+                    error!("Failed to send Morpho flash loan to stream: {}", e);
+                });
+        }
+
+        // Stream fee changes
+        for set_fee in &updates.set_fees {
+            debug!(
+                tx_hash = %set_fee.context.tx_hash,
+                morpho = %set_fee.morpho,
+                market_id = %set_fee.market_id,
+                fee = %set_fee.fee,
+                "Morpho SetFee"
+            );
+            stream.send("Morpho_set_fee", set_fee).unwrap_or_else(|e| {
+                error!("Failed to send Morpho set fee to stream: {}", e);
+            });
+        }
+
+        // Stream authorization changes
+        for authorization in &updates.set_authorizations {
+            debug!(
+                tx_hash = %authorization.context.tx_hash,
+                morpho = %authorization.morpho,
+                authorizer = %authorization.authorizer,
+                authorized = %authorization.authorized,
+                is_authorized = authorization.is_authorized,
+                "Morpho SetAuthorization"
+            );
+            stream
+                .send("Morpho_set_authorization", authorization)
+                .unwrap_or_else(|e| {
+                    error!("Failed to send Morpho set authorization to stream: {}", e);
+                });
+        }
+
+        // Stream used authorization nonces
+        for nonce in &updates.increment_nonces {
+            debug!(
+                tx_hash = %nonce.context.tx_hash,
+                morpho = %nonce.morpho,
+                authorizer = %nonce.authorizer,
+                used_nonce = %nonce.used_nonce,
+                "Morpho IncrementNonce"
+            );
+            stream
+                .send("Morpho_increment_nonce", nonce)
+                .unwrap_or_else(|e| {
+                    error!("Failed to send Morpho increment nonce to stream: {}", e);
+                });
+        }
     }
 }
@@ -0,0 +1,110 @@
+use std::sync::Arc;
+
+use flashblocks_indexer_streams::{DataStream, StreamOutput};
+use flashblocks_types::flashblocks::Flashblock;
+use flashblocks_types::tokens::{Annotated, TokenRegistry};
+use tracing::{debug, error, info};
+
+use super::ProtocolHandler;
+
+/// Handler for Uniswap V2-style and Aerodrome V2 pool events.
+///
+/// Swaps are streamed as-is; every Sync becomes a pool-state event with the
+/// reserves and spot price.
+pub struct UniV2Handler {
+    tokens: Arc<TokenRegistry>,
+}
+
+impl UniV2Handler {
+    pub fn new(tokens: Arc<TokenRegistry>) -> Self {
+        Self { tokens }
+    }
+}
+
+impl ProtocolHandler for UniV2Handler {
+    fn process(&self, fb: &Flashblock, block_number: u64, stream: &StreamOutput) {
+        let updates = fb.extract_univ2_updates();
+
+        if updates.is_empty() {
+            return;
+        }
+
+        info!(
+            block_number = block_number,
+            swaps = updates.swaps.len(),
+            syncs = updates.syncs.len(),
+            total = updates.total_count(),
+            "UniV2 events detected"
+        );
+
+        // Stream swap events
+        for swap in &updates.swaps {
+            debug!(
+                tx_hash = %swap.context.tx_hash,
+                pool = %swap.pool,
+                kind = ?swap.kind,
+                sender = %swap.sender,
+                to = %swap.to,
+                amount0_in = %swap.amount0_in,
+                amount1_in = %swap.amount1_in,
+                amount0_out = %swap.amount0_out,
+                amount1_out = %swap.amount1_out,
+                "UniV2 Swap"
+            );
+
+            let tokens = self
+                .tokens
+                .pool(&swap.pool)
+                .map(|pair| {
+                    self.tokens.amounts([
+                        ("amount0_in", pair.token0, swap.amount0_in.into()),
+                        ("amount1_in", pair.token1, swap.amount1_in.into()),
+                        ("amount0_out", pair.token0, swap.amount0_out.into()),
+                        ("amount1_out", pair.token1, swap.amount1_out.into()),
+                    ])
+                })
+                .unwrap_or_default();
+            stream
+                .send("UniV2_swap", &Annotated::new(swap, tokens))
+                .unwrap_or_else(|e| {
+                    error!("Failed to send UniV2 swap to stream: {}", e);
+                });
+        }
+
+        // Stream pool state from sync events
+        for sync in &updates.syncs {
+            let state = sync.pool_state();
+            debug!(
+                tx_hash = %state.context.tx_hash,
+                pool = %state.pool,
+                kind = ?state.kind,
+                reserve0 = %state.reserve0,
+                reserve1 = %state.reserve1,
+                price_0_in_1 = ?state.price_0_in_1,
+                "UniV2 pool state"
+            );
+
+            let tokens = self
+                .tokens
+                .pool(&state.pool)
+                .map(|pair| {
+                    self.tokens.amounts([
+                        ("reserve0", pair.token0, state.reserve0.into()),
+                        ("reserve1", pair.token1, state.reserve1.into()),
+                    ])
+                })
+                .unwrap_or_default();
+            let price = sync
+                .spot_price()
+                .and_then(|raw| self.tokens.pool_price(&state.pool, raw));
+            stream
+                .send(
+                    "UniV2_pool_state",
+                    &Annotated::new(&state, tokens).with_price(price),
+                )
+                .unwrap_or_else(|e| {
+                    error!("Failed to send UniV2 pool state to stream: {}", e);
+                });
+        }
+    }
+}
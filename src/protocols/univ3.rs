@@ -1,28 +1,82 @@
+use std::sync::{Arc, Mutex};
+
+use alloy_primitives::Address;
+use alloy_primitives::utils::ParseUnits;
 use flashblocks_indexer_streams::{DataStream, StreamOutput};
+use flashblocks_types::dex::DexRegistry;
 use flashblocks_types::flashblocks::Flashblock;
+use flashblocks_types::tokens::{Annotated, TokenAmount, TokenRegistry};
 use tracing::{debug, error, info};
 
 use super::ProtocolHandler;
 
-/// Handler for Uniswap V3 swap events.
-pub struct UniV3Handler;
+/// Handler for Uniswap V3 and V3-fork pool events.
+///
+/// Swaps are attributed to a DEX through the pool's factory: pools seen being
+/// created, listed in the DEX registry, or derived from a factory's
+/// `init_code_hash` and the token list. Swaps on other pools, such as most
+/// pools created before startup, stream with no `dex`. Events from pools
+/// with a known token pair are streamed with their token amounts, and swaps
+/// with the decimal-adjusted price.
+pub struct UniV3Handler {
+    tokens: Arc<TokenRegistry>,
+    dexes: Mutex<DexRegistry>,
+}
+
+impl UniV3Handler {
+    pub fn new(tokens: Arc<TokenRegistry>, dexes: DexRegistry) -> Self {
+        Self {
+            tokens,
+            dexes: Mutex::new(dexes),
+        }
+    }
+
+    /// A pool's token pair, from the token registry or its PoolCreated event
+    fn pair(&self, dexes: &DexRegistry, pool: &Address) -> Option<(Address, Address)> {
+        self.tokens
+            .pool(pool)
+            .map(|p| (p.token0, p.token1))
+            .or_else(|| dexes.pool(pool).and_then(|p| p.tokens))
+    }
+
+    fn amounts(
+        &self,
+        pair: Option<(Address, Address)>,
+        amount0: impl Into<ParseUnits>,
+        amount1: impl Into<ParseUnits>,
+    ) -> Vec<TokenAmount> {
+        pair.map(|(token0, token1)| self.tokens.pair_amounts(&token0, &token1, amount0, amount1))
+            .unwrap_or_default()
+    }
+}
 
 impl ProtocolHandler for UniV3Handler {
     fn process(&self, fb: &Flashblock, block_number: u64, stream: &StreamOutput) {
-        let swaps = fb.extract_swaps();
+        let mut updates = fb.extract_univ3_updates();
 
-        if swaps.is_empty() {
+        if updates.is_empty() {
             return;
         }
 
+        let mut dexes = self.dexes.lock().unwrap();
+        dexes.annotate(&mut updates);
+
         info!(
             block_number = block_number,
-            count = swaps.len(),
-            "UniV3 Swaps detected"
+            swaps = updates.swaps.len(),
+            mints = updates.mints.len(),
+            burns = updates.burns.len(),
+            collects = updates.collects.len(),
+            flashes = updates.flashes.len(),
+            pools_created = updates.pools_created.len(),
+            total = updates.total_count(),
+            "UniV3 events detected"
         );
 
-        for swap in &swaps {
+        // Stream swap events
+        for swap in &updates.swaps {
             debug!(
+                tx_hash = %swap.context.tx_hash,
                 pool = %swap.pool,
                 sender = %swap.sender,
                 recipient = %swap.recipient,
@@ -31,6 +85,7 @@ impl ProtocolHandler for UniV3Handler {
                 sqrt_price_x96 = %swap.sqrt_price_x96,
                 liquidity = swap.liquidity,
                 tick = swap.tick,
+                dex = ?swap.dex,
                 "Swap"
             );
 
@@ -38,14 +93,131 @@ impl ProtocolHandler for UniV3Handler {
             info!(
                 pool = %swap.pool,
                 tick = state.tick,
-                price_0_in_1 = %format!("{:.32}", state.price_0_in_1()),
+                price_0_in_1 = %state.price().to_decimal_string(32),
                 liquidity = state.liquidity,
                 "Pool state after swap"
             );
 
-            stream.send("UniV3_swap", swap).unwrap_or_else(|e| {
+            let pair = self.pair(&dexes, &swap.pool);
+            let price = pair.and_then(|(token0, token1)| {
+                self.tokens.pair_price(&token0, &token1, state.price())
+            });
+            let annotated = Annotated::new(swap, self.amounts(pair, swap.amount0, swap.amount1))
+                .with_price(price);
+            stream.send("UniV3_swap", &annotated).unwrap_or_else(|e| {
                 error!("Failed to send swap to stream: {}", e);
             });
         }
+
+        // Stream mint events
+        for mint in &updates.mints {
+            debug!(
+                tx_hash = %mint.context.tx_hash,
+                pool = %mint.pool,
+                owner = %mint.owner,
+                tick_lower = mint.tick_lower,
+                tick_upper = mint.tick_upper,
+                amount = mint.amount,
+                amount0 = %mint.amount0,
+                amount1 = %mint.amount1,
+                "UniV3 Mint"
+            );
+            let pair = self.pair(&dexes, &mint.pool);
+            let annotated = Annotated::new(mint, self.amounts(pair, mint.amount0, mint.amount1));
+            stream.send("UniV3_mint", &annotated).unwrap_or_else(|e| {
+                error!("Failed to send UniV3 mint to stream: {}", e);
+            });
+        }
+
+        // Stream burn events
+        for burn in &updates.burns {
+            debug!(
+                tx_hash = %burn.context.tx_hash,
+                pool = %burn.pool,
+                owner = %burn.owner,
+                tick_lower = burn.tick_lower,
+                tick_upper = burn.tick_upper,
+                amount = burn.amount,
+                amount0 = %burn.amount0,
+                amount1 = %burn.amount1,
+                "UniV3 Burn"
+            );
+            let pair = self.pair(&dexes, &burn.pool);
+            let annotated = Annotated::new(burn, self.amounts(pair, burn.amount0, burn.amount1));
+            stream.send("UniV3_burn", &annotated).unwrap_or_else(|e| {
+                error!("Failed to send UniV3 burn to stream: {}", e);
+            });
+        }
+
+        // Stream collect events
+        for collect in &updates.collects {
+            debug!(
+                tx_hash = %collect.context.tx_hash,
+                pool = %collect.pool,
+                owner = %collect.owner,
+                recipient = %collect.recipient,
+                amount0 = collect.amount0,
+                amount1 = collect.amount1,
+                "UniV3 Collect"
+            );
+            let pair = self.pair(&dexes, &collect.pool);
+            let annotated = Annotated::new(
+                collect,
+                self.amounts(pair, collect.amount0, collect.amount1),
+            );
+            stream
+                .send("UniV3_collect", &annotated)
+                .unwrap_or_else(|e| {
+                    error!("Failed to send UniV3 collect to stream: {}", e);
+                });
+        }
+
+        // Stream flash loan events
+        for flash in &updates.flashes {
+            debug!(
+                tx_hash = %flash.context.tx_hash,
+                pool = %flash.pool,
+                sender = %flash.sender,
+                recipient = %flash.recipient,
+                amount0 = %flash.amount0,
+                amount1 = %flash.amount1,
+                paid0 = %flash.paid0,
+                paid1 = %flash.paid1,
+                "UniV3 Flash"
+            );
+            let pair = self.pair(&dexes, &flash.pool);
+            let mut tokens = self.amounts(pair, flash.amount0, flash.amount1);
+            if let Some((token0, token1)) = pair {
+                tokens.extend(self.tokens.amounts([
+                    ("paid0", token0, flash.paid0.into()),
+                    ("paid1", token1, flash.paid1.into()),
+                ]));
+            }
+            stream
+                .send("UniV3_flash", &Annotated::new(flash, tokens))
+                .unwrap_or_else(|e| {
+                    error!("Failed to send UniV3 flash to stream: {}", e);
+                });
+        }
+
+        // Stream pool creation events
+        for created in &updates.pools_created {
+            debug!(
+                tx_hash = %created.context.tx_hash,
+                factory = %created.factory,
+                pool = %created.pool,
+                token0 = %created.token0,
+                token1 = %created.token1,
+                fee = ?created.fee,
+                tick_spacing = created.tick_spacing,
+                dex = ?created.dex,
+                "PoolCreated"
+            );
+            stream
+                .send("UniV3_pool_created", created)
+                .unwrap_or_else(|e| {
+                    error!("Failed to send UniV3 pool creation to stream: {}", e);
+                });
+        }
     }
 }
@@ -0,0 +1,175 @@
+use std::sync::{Arc, Mutex};
+
+use flashblocks_indexer_streams::{DataStream, StreamOutput};
+use flashblocks_types::flashblocks::Flashblock;
+use flashblocks_types::price::Price;
+use flashblocks_types::tokens::{Annotated, TokenRegistry};
+use flashblocks_types::univ4::V4PoolRegistry;
+use tracing::{debug, error, info};
+
+use super::ProtocolHandler;
+
+/// Handler for Uniswap V4 PoolManager events.
+///
+/// Pool keys are learned from `Initialize` events, so swaps, liquidity
+/// changes and donations in pools initialized while running are streamed
+/// with their currencies, fee, tick spacing and hooks.
+pub struct UniV4Handler {
+    tokens: Arc<TokenRegistry>,
+    pools: Mutex<V4PoolRegistry>,
+}
+
+impl UniV4Handler {
+    pub fn new(tokens: Arc<TokenRegistry>) -> Self {
+        Self {
+            tokens,
+            pools: Mutex::new(V4PoolRegistry::new()),
+        }
+    }
+}
+
+impl ProtocolHandler for UniV4Handler {
+    fn process(&self, fb: &Flashblock, block_number: u64, stream: &StreamOutput) {
+        let mut updates = fb.extract_univ4_updates();
+
+        if updates.is_empty() {
+            return;
+        }
+
+        let known_pools = {
+            let mut pools = self.pools.lock().unwrap();
+            pools.annotate(&mut updates);
+            pools.len()
+        };
+
+        info!(
+            block_number = block_number,
+            initializes = updates.initializes.len(),
+            swaps = updates.swaps.len(),
+            modify_liquidities = updates.modify_liquidities.len(),
+            donates = updates.donates.len(),
+            total = updates.total_count(),
+            known_pools = known_pools,
+            "UniV4 events detected"
+        );
+
+        // Stream initialize events
+        for init in &updates.initializes {
+            debug!(
+                tx_hash = %init.context.tx_hash,
+                pool_id = %init.pool_id,
+                currency0 = %init.key.currency0,
+                currency1 = %init.key.currency1,
+                fee = init.key.fee,
+                tick_spacing = init.key.tick_spacing,
+                hooks = %init.key.hooks,
+                tick = init.tick,
+                "UniV4 Initialize"
+            );
+
+            let price = self.tokens.pair_price(
+                &init.key.currency0,
+                &init.key.currency1,
+                Price::from_sqrt_price_x96(init.sqrt_price_x96),
+            );
+            stream
+                .send(
+                    "UniV4_initialize",
+                    &Annotated::new(init, Vec::new()).with_price(price),
+                )
+                .unwrap_or_else(|e| {
+                    error!("Failed to send UniV4 initialize to stream: {}", e);
+                });
+        }
+
+        // Stream swap events
+        for swap in &updates.swaps {
+            debug!(
+                tx_hash = %swap.context.tx_hash,
+                pool_id = %swap.pool_id,
+                sender = %swap.sender,
+                amount0 = swap.amount0,
+                amount1 = swap.amount1,
+                sqrt_price_x96 = %swap.sqrt_price_x96,
+                liquidity = swap.liquidity,
+                tick = swap.tick,
+                fee = swap.fee,
+                "UniV4 Swap"
+            );
+
+            let state = swap.pool_state();
+            info!(
+                pool_id = %swap.pool_id,
+                tick = state.tick,
+                price_0_in_1 = %state.price().to_decimal_string(32),
+                liquidity = state.liquidity,
+                "UniV4 pool state after swap"
+            );
+
+            let (tokens, price) = match &swap.pool_key {
+                Some(key) => (
+                    self.tokens.amounts([
+                        ("amount0", key.currency0, swap.amount0.into()),
+                        ("amount1", key.currency1, swap.amount1.into()),
+                    ]),
+                    self.tokens
+                        .pair_price(&key.currency0, &key.currency1, state.price()),
+                ),
+                None => (Vec::new(), None),
+            };
+            stream
+                .send(
+                    "UniV4_swap",
+                    &Annotated::new(swap, tokens).with_price(price),
+                )
+                .unwrap_or_else(|e| {
+                    error!("Failed to send UniV4 swap to stream: {}", e);
+                });
+        }
+
+        // Stream liquidity events
+        for modify in &updates.modify_liquidities {
+            debug!(
+                tx_hash = %modify.context.tx_hash,
+                pool_id = %modify.pool_id,
+                sender = %modify.sender,
+                tick_lower = modify.tick_lower,
+                tick_upper = modify.tick_upper,
+                liquidity_delta = %modify.liquidity_delta,
+                "UniV4 ModifyLiquidity"
+            );
+            stream
+                .send("UniV4_modify_liquidity", modify)
+                .unwrap_or_else(|e| {
+                    error!("Failed to send UniV4 liquidity change to stream: {}", e);
+                });
+        }
+
+        // Stream donate events
+        for donate in &updates.donates {
+            debug!(
+                tx_hash = %donate.context.tx_hash,
+                pool_id = %donate.pool_id,
+                sender = %donate.sender,
+                amount0 = %donate.amount0,
+                amount1 = %donate.amount1,
+                "UniV4 Donate"
+            );
+
+            let tokens = donate
+                .pool_key
+                .map(|key| {
+                    self.tokens.amounts([
+                        ("amount0", key.currency0, donate.amount0.into()),
+                        ("amount1", key.currency1, donate.amount1.into()),
+                    ])
+                })
+                .unwrap_or_default();
+            stream
+                .send("UniV4_donate", &Annotated::new(donate, tokens))
+                .unwrap_or_else(|e| {
+                    error!("Failed to send UniV4 donate to stream: {}", e);
+                });
+        }
+    }
+}
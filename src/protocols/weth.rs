@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use flashblocks_indexer_streams::{DataStream, StreamOutput};
+use flashblocks_types::flashblocks::Flashblock;
+use flashblocks_types::tokens::{Annotated, TokenRegistry};
+use flashblocks_types::weth::WETH;
+use tracing::{debug, error, info};
+
+use super::ProtocolHandler;
+
+/// Handler for WETH Deposit and Withdrawal events on the WETH predeploy
+pub struct WethHandler {
+    tokens: Arc<TokenRegistry>,
+}
+
+impl WethHandler {
+    pub fn new(tokens: Arc<TokenRegistry>) -> Self {
+        Self { tokens }
+    }
+}
+
+impl ProtocolHandler for WethHandler {
+    fn process(&self, fb: &Flashblock, block_number: u64, stream: &StreamOutput) {
+        let updates = fb.extract_weth_updates();
+
+        if updates.is_empty() {
+            return;
+        }
+
+        info!(
+            block_number = block_number,
+            deposits = updates.deposits.len(),
+            withdrawals = updates.withdrawals.len(),
+            total = updates.total_count(),
+            "WETH events detected"
+        );
+
+        // Stream deposit events
+        for deposit in &updates.deposits {
+            debug!(
+                tx_hash = %deposit.context.tx_hash,
+                account = %deposit.account,
+                amount = %deposit.amount,
+                "WETH Deposit"
+            );
+
+            let tokens = self
+                .tokens
+                .amounts([("amount", WETH, deposit.amount.into())]);
+            stream
+                .send("Weth_deposit", &Annotated::new(deposit, tokens))
+                .unwrap_or_else(|e| {
+                    error!("Failed to send WETH deposit to stream: {}", e);
+                });
+        }
+
+        // Stream withdrawal events
+        for withdrawal in &updates.withdrawals {
+            debug!(
+                tx_hash = %withdrawal.context.tx_hash,
+                account = %withdrawal.account,
+                amount = %withdrawal.amount,
+                "WETH Withdrawal"
+            );
+
+            let tokens = self
+                .tokens
+                .amounts([("amount", WETH, withdrawal.amount.into())]);
+            stream
+                .send("Weth_withdrawal", &Annotated::new(withdrawal, tokens))
+                .unwrap_or_else(|e| {
+                    error!("Failed to send WETH withdrawal to stream: {}", e);
+                });
+        }
+    }
+}
@@ -0,0 +1,504 @@
+//! Recording raw upstream frames to disk and replaying them.
+//!
+//! Recordings are append-only files of length-prefixed frame records:
+//!
+//! ```text
+//! u64 LE  arrival time, microseconds since the UNIX epoch
+//! u16 LE  upstream id (position in the `--url` list)
+//! u8      frame kind (0 = text, 1 = binary)
+//! u32 LE  payload length
+//! [u8]    payload, exactly as received (binary frames stay Brotli-compressed)
+//! ```
+//!
+//! A record truncated by a crash mid-write ends the replay with a warning.
+//! Frames are written on a dedicated thread and flushed at least every
+//! [`FLUSH_INTERVAL`], so a crash loses at most that much of the recording.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+use std::sync::mpsc::{self as std_mpsc, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use tokio::sync::{mpsc, watch};
+use tracing::{error, info, warn};
+
+use crate::upstream::{UpstreamEvent, UpstreamId, decode_frame};
+
+/// Size of the fixed record header in bytes
+const HEADER_LEN: usize = 8 + 2 + 1 + 4;
+
+/// Longest a written frame stays buffered before being flushed to disk
+pub const FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Kind of WebSocket data frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    /// UTF-8 JSON text frame
+    Text,
+    /// Brotli-compressed JSON binary frame
+    Binary,
+}
+
+impl FrameKind {
+    fn to_byte(self) -> u8 {
+        match self {
+            Self::Text => 0,
+            Self::Binary => 1,
+        }
+    }
+
+    fn from_byte(byte: u8) -> io::Result<Self> {
+        match byte {
+            0 => Ok(Self::Text),
+            1 => Ok(Self::Binary),
+            other => Err(io::Error::new(
+                ErrorKind::InvalidData,
+                format!("unknown frame kind {other}"),
+            )),
+        }
+    }
+}
+
+/// A single recorded upstream frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FrameRecord {
+    /// Arrival time, microseconds since the UNIX epoch
+    pub timestamp_us: u64,
+    pub upstream: u16,
+    pub kind: FrameKind,
+    pub payload: Vec<u8>,
+}
+
+impl FrameRecord {
+    /// Read the next record from `r`.
+    /// Returns `Ok(None)` at a clean end of file.
+    pub fn read_from<R: Read>(r: &mut R) -> io::Result<Option<Self>> {
+        let Some((mut record, len)) = Self::read_header(r)? else {
+            return Ok(None);
+        };
+        record.payload = vec![0u8; len];
+        r.read_exact(&mut record.payload)?;
+        Ok(Some(record))
+    }
+
+    /// Read the next record's header, returning it with an empty payload
+    /// and the payload's length. Returns `Ok(None)` at a clean end of file.
+    fn read_header<R: Read>(r: &mut R) -> io::Result<Option<(Self, usize)>> {
+        let mut header = [0u8; HEADER_LEN];
+        match r.read_exact(&mut header[..1]) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        r.read_exact(&mut header[1..])?;
+
+        let record = Self {
+            timestamp_us: u64::from_le_bytes(header[0..8].try_into().unwrap()),
+            upstream: u16::from_le_bytes(header[8..10].try_into().unwrap()),
+            kind: FrameKind::from_byte(header[10])?,
+            payload: Vec::new(),
+        };
+        let len = u32::from_le_bytes(header[11..15].try_into().unwrap()) as usize;
+        Ok(Some((record, len)))
+    }
+}
+
+/// Number of upstreams in a recording: one more than the highest recorded
+/// upstream id, and at least 1. A truncated last record is ignored.
+pub fn recorded_upstreams(path: &Path) -> io::Result<usize> {
+    count_upstreams(&mut BufReader::new(File::open(path)?))
+}
+
+fn count_upstreams<R: Read>(r: &mut R) -> io::Result<usize> {
+    let mut upstreams = 1;
+    loop {
+        let (record, len) = match FrameRecord::read_header(r) {
+            Ok(Some(header)) => header,
+            Ok(None) => break,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e),
+        };
+        // Skip the payload, stopping at a record cut short by a crash
+        if io::copy(&mut r.take(len as u64), &mut io::sink())? < len as u64 {
+            break;
+        }
+        upstreams = upstreams.max(usize::from(record.upstream) + 1);
+    }
+    Ok(upstreams)
+}
+
+/// Write a single frame record to `w`
+fn write_frame<W: Write>(
+    w: &mut W,
+    timestamp_us: u64,
+    upstream: u16,
+    kind: FrameKind,
+    payload: &[u8],
+) -> io::Result<()> {
+    let len = u32::try_from(payload.len())
+        .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "frame too large"))?;
+
+    let mut header = [0u8; HEADER_LEN];
+    header[0..8].copy_from_slice(&timestamp_us.to_le_bytes());
+    header[8..10].copy_from_slice(&upstream.to_le_bytes());
+    header[10] = kind.to_byte();
+    header[11..15].copy_from_slice(&len.to_le_bytes());
+
+    w.write_all(&header)?;
+    w.write_all(payload)
+}
+
+/// Appends upstream frames to a recording file.
+///
+/// Cheap to clone; all clones hand their frames to the same writer thread,
+/// so recording never blocks the async upstream tasks on file IO.
+#[derive(Clone)]
+pub struct Recorder {
+    frames: std_mpsc::Sender<FrameRecord>,
+}
+
+/// The thread writing a recording. It stops once every [`Recorder`] clone is
+/// dropped, after flushing what they sent.
+pub struct RecordingWriter {
+    thread: JoinHandle<()>,
+}
+
+impl Recorder {
+    /// Open `path` for appending, creating it if needed, and start its writer thread
+    pub fn create(path: &Path) -> io::Result<(Self, RecordingWriter)> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        info!("Recording upstream frames to {}", path.display());
+
+        let (frames, received) = std_mpsc::channel();
+        let path = path.to_path_buf();
+        let thread = std::thread::Builder::new()
+            .name("recorder".to_string())
+            .spawn(move || write_frames(&path, BufWriter::new(file), received))?;
+        Ok((Self { frames }, RecordingWriter { thread }))
+    }
+
+    /// Queue a frame for writing, stamped with the current time.
+    /// Failures are logged; recording never interrupts ingestion.
+    pub fn record(&self, upstream: UpstreamId, kind: FrameKind, payload: &[u8]) {
+        let timestamp_us = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_micros() as u64)
+            .unwrap_or_default();
+
+        let record = FrameRecord {
+            timestamp_us,
+            upstream: upstream as u16,
+            kind,
+            payload: payload.to_vec(),
+        };
+        if self.frames.send(record).is_err() {
+            error!("Recording writer stopped; dropping frame");
+        }
+    }
+}
+
+impl RecordingWriter {
+    /// Wait for the writer to flush and stop. Blocks until every
+    /// [`Recorder`] clone has been dropped.
+    pub fn finish(self) {
+        if self.thread.join().is_err() {
+            error!("Recording writer panicked");
+        }
+    }
+}
+
+/// Write queued frames until every sender is gone, flushing at least every
+/// `FLUSH_INTERVAL` while frames keep arriving and whenever the queue goes quiet
+fn write_frames<W: Write>(path: &Path, mut writer: W, frames: std_mpsc::Receiver<FrameRecord>) {
+    let flush = |writer: &mut W| {
+        if let Err(e) = writer.flush() {
+            error!("Failed to flush recording {}: {}", path.display(), e);
+        }
+    };
+
+    let mut last_flush = Instant::now();
+    let mut dirty = false;
+    loop {
+        match frames.recv_timeout(FLUSH_INTERVAL) {
+            Ok(r) => {
+                if let Err(e) =
+                    write_frame(&mut writer, r.timestamp_us, r.upstream, r.kind, &r.payload)
+                {
+                    error!("Failed to record frame to {}: {}", path.display(), e);
+                }
+                dirty = true;
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if dirty && last_flush.elapsed() >= FLUSH_INTERVAL {
+            flush(&mut writer);
+            last_flush = Instant::now();
+            dirty = false;
+        }
+    }
+    flush(&mut writer);
+    info!("Recording to {} closed", path.display());
+}
+
+/// Slowest `--replay-speed` accepted. Slower factors stretch recorded gaps
+/// far enough to overflow a `Duration`.
+pub const MIN_REPLAY_SPEED: f64 = 0.001;
+
+/// Longest the replay sleeps before checking for shutdown again
+const SHUTDOWN_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Replay pacing
+#[derive(Debug, Clone, Copy)]
+pub enum ReplaySpeed {
+    /// Sleep for the recorded gap divided by this factor (1.0 = original speed)
+    Scaled(f64),
+    /// Don't sleep between frames
+    Unthrottled,
+}
+
+impl ReplaySpeed {
+    /// Build from the `--replay-speed` CLI value, where 0 means as fast as possible.
+    /// Positive factors below [`MIN_REPLAY_SPEED`] are raised to it.
+    pub fn from_factor(factor: f64) -> Self {
+        if factor > 0.0 {
+            Self::Scaled(factor.max(MIN_REPLAY_SPEED))
+        } else {
+            Self::Unthrottled
+        }
+    }
+
+    /// Time to wait for a recorded gap, saturating at `Duration::MAX`
+    fn delay(self, gap_us: u64) -> Option<Duration> {
+        match self {
+            Self::Scaled(factor) => Some(
+                Duration::try_from_secs_f64(Duration::from_micros(gap_us).as_secs_f64() / factor)
+                    .unwrap_or(Duration::MAX),
+            ),
+            Self::Unthrottled => None,
+        }
+    }
+}
+
+/// Sleep for `delay` in slices of at most [`SHUTDOWN_POLL_INTERVAL`].
+/// Returns `false` as soon as shutdown is signalled.
+fn sleep_unless_shutdown(delay: Duration, shutdown: &watch::Receiver<bool>) -> bool {
+    let deadline = Instant::now().checked_add(delay);
+    loop {
+        if *shutdown.borrow() {
+            return false;
+        }
+        let remaining = match deadline {
+            Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+            None => Duration::MAX,
+        };
+        if remaining.is_zero() {
+            return true;
+        }
+        std::thread::sleep(remaining.min(SHUTDOWN_POLL_INTERVAL));
+    }
+}
+
+/// Feed a recording into the ingestion loop, each frame tagged with the
+/// upstream it was recorded from.
+///
+/// Runs on a blocking thread; returns at the end of the file, on shutdown,
+/// or when the ingestion loop goes away. Frames from every recorded upstream
+/// are replayed in file order, so the merger picks the same winners, drops
+/// the same duplicates and keeps the same per-upstream stats as it did live.
+/// Size the merger with [`recorded_upstreams`].
+pub fn replay(
+    path: &Path,
+    speed: ReplaySpeed,
+    shutdown: watch::Receiver<bool>,
+    events: mpsc::Sender<UpstreamEvent>,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(File::open(path)?);
+    info!("Replaying {} ({:?})", path.display(), speed);
+
+    let started = Instant::now();
+    let mut frames = 0u64;
+    let mut prev_timestamp: Option<u64> = None;
+
+    loop {
+        if *shutdown.borrow() {
+            info!("Shutting down replay.");
+            break;
+        }
+
+        let record = match FrameRecord::read_from(&mut reader) {
+            Ok(Some(record)) => record,
+            Ok(None) => break,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => {
+                warn!("Recording ends with a truncated frame; stopping replay");
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+
+        if let Some(prev) = prev_timestamp
+            && let Some(delay) = speed.delay(record.timestamp_us.saturating_sub(prev))
+            && !sleep_unless_shutdown(delay, &shutdown)
+        {
+            info!("Shutting down replay.");
+            break;
+        }
+        prev_timestamp = Some(record.timestamp_us);
+        frames += 1;
+
+        let upstream = UpstreamId::from(record.upstream);
+        let event = match decode_frame(record.kind, &record.payload) {
+            Some(flashblock) => UpstreamEvent::Flashblock {
+                upstream,
+                received_at: Instant::now(),
+                flashblock: Box::new(flashblock),
+            },
+            None => UpstreamEvent::BadFrame { upstream },
+        };
+        if events.blocking_send(event).is_err() {
+            break;
+        }
+    }
+
+    let elapsed = started.elapsed();
+    info!(
+        frames = frames,
+        elapsed_ms = elapsed.as_millis() as u64,
+        frames_per_sec = frames as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+        "Replay finished"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_round_trip() {
+        let records = vec![
+            FrameRecord {
+                timestamp_us: 1_700_000_000_000_000,
+                upstream: 0,
+                kind: FrameKind::Text,
+                payload: br#"{"payload_id":"0x01","index":0}"#.to_vec(),
+            },
+            FrameRecord {
+                timestamp_us: 1_700_000_000_200_000,
+                upstream: 3,
+                kind: FrameKind::Binary,
+                payload: vec![0x1b, 0x00, 0xff],
+            },
+        ];
+
+        let mut buf = Vec::new();
+        for r in &records {
+            write_frame(&mut buf, r.timestamp_us, r.upstream, r.kind, &r.payload).unwrap();
+        }
+
+        let mut reader = buf.as_slice();
+        let mut decoded = Vec::new();
+        while let Some(record) = FrameRecord::read_from(&mut reader).unwrap() {
+            decoded.push(record);
+        }
+        assert_eq!(decoded, records);
+    }
+
+    #[test]
+    fn test_recorder_writes_on_its_own_thread() {
+        let path = std::env::temp_dir().join(format!("recorder-{}.fbrec", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        let (recorder, writer) = Recorder::create(&path).unwrap();
+        let clone = recorder.clone();
+        recorder.record(0, FrameKind::Text, b"{}");
+        clone.record(2, FrameKind::Binary, &[0x1b, 0x00]);
+        drop((recorder, clone));
+        writer.finish();
+
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let mut reader = bytes.as_slice();
+        let mut decoded = Vec::new();
+        while let Some(record) = FrameRecord::read_from(&mut reader).unwrap() {
+            decoded.push((record.upstream, record.kind, record.payload));
+        }
+        assert_eq!(
+            decoded,
+            vec![
+                (0, FrameKind::Text, b"{}".to_vec()),
+                (2, FrameKind::Binary, vec![0x1b, 0x00]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_count_upstreams() {
+        let mut buf = Vec::new();
+        for upstream in [0, 2, 1] {
+            write_frame(&mut buf, 1, upstream, FrameKind::Text, b"{}").unwrap();
+        }
+        let count = |mut bytes: &[u8]| count_upstreams(&mut bytes);
+        assert_eq!(count(&buf).unwrap(), 3);
+
+        // A truncated record is ignored, and an empty recording still has one upstream
+        write_frame(&mut buf, 1, 7, FrameKind::Text, b"{}").unwrap();
+        buf.pop();
+        assert_eq!(count(&buf).unwrap(), 3);
+        assert_eq!(count(&[]).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_truncated_record() {
+        let mut buf = Vec::new();
+        write_frame(&mut buf, 1, 0, FrameKind::Text, b"{}").unwrap();
+        buf.pop();
+
+        let err = FrameRecord::read_from(&mut buf.as_slice()).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_replay_sleep_stops_on_shutdown() {
+        let (shutdown_tx, shutdown_rx) = watch::channel(false);
+        assert!(sleep_unless_shutdown(
+            Duration::from_millis(10),
+            &shutdown_rx
+        ));
+
+        let signal = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            shutdown_tx.send(true).unwrap();
+        });
+        let started = Instant::now();
+        assert!(!sleep_unless_shutdown(Duration::MAX, &shutdown_rx));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        signal.join().unwrap();
+    }
+
+    #[test]
+    fn test_replay_speed() {
+        let gap = 200_000;
+        assert_eq!(
+            ReplaySpeed::from_factor(1.0).delay(gap),
+            Some(Duration::from_millis(200))
+        );
+        assert_eq!(
+            ReplaySpeed::from_factor(4.0).delay(gap),
+            Some(Duration::from_millis(50))
+        );
+        assert_eq!(ReplaySpeed::from_factor(0.0).delay(gap), None);
+
+        // Tiny factors are clamped instead of overflowing the delay
+        assert_eq!(
+            ReplaySpeed::from_factor(1e-300).delay(gap),
+            Some(Duration::from_secs(200))
+        );
+        assert_eq!(
+            ReplaySpeed::Scaled(1e-300).delay(u64::MAX),
+            Some(Duration::MAX)
+        );
+    }
+}
@@ -0,0 +1,398 @@
+//! Flashblock sequencing checks.
+//!
+//! A builder publishes a payload as flashblocks with consecutive indices,
+//! where index 0 carries the `base`. The sequencer follows the current
+//! payload and flags anything that leaves a consumer's view incomplete:
+//! missing, duplicated or out-of-order indices, and payloads abandoned for a
+//! new one at the same block number.
+
+use std::collections::BTreeSet;
+
+use flashblocks_indexer_streams::{DataStream, StreamOutput};
+use flashblocks_types::flashblocks::{ExecutionPayloadBase, Flashblock};
+use serde::Serialize;
+use tracing::{error, info, warn};
+
+/// What went wrong with a flashblock's position in its payload
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GapKind {
+    /// One or more indices were skipped
+    Missing,
+    /// An index that was already processed arrived again
+    Duplicate,
+    /// A skipped index arrived after later ones
+    OutOfOrder,
+    /// Index 0 of a payload arrived without a `base`
+    MissingBase,
+    /// The first flashblock seen for a payload had an index above 0, as when
+    /// the digestor starts or reconnects while the payload is being built.
+    /// Its earlier indices were never available to us rather than lost.
+    JoinedMidPayload,
+    /// A flashblock arrived for a payload the sequencer already moved past
+    Stale,
+}
+
+/// A hole or irregularity in the flashblock sequence, streamed as `Flashblock_gap`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FlashblockGap {
+    pub kind: GapKind,
+    pub payload_id: String,
+    pub block_number: Option<u64>,
+    /// Index the sequencer expected next
+    pub expected_index: u64,
+    /// Index that actually arrived
+    pub received_index: u64,
+    /// Indices that were skipped (only for `Missing` and `JoinedMidPayload`)
+    pub missing_indices: Vec<u64>,
+}
+
+/// A payload abandoned for a new one at the same block number,
+/// streamed as `Payload_replaced`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PayloadReplaced {
+    pub block_number: u64,
+    pub previous_payload_id: String,
+    /// Highest index seen for the abandoned payload
+    pub previous_last_index: u64,
+    pub new_payload_id: String,
+}
+
+/// Sequencing events produced for a single flashblock
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SequenceEvent {
+    Gap(FlashblockGap),
+    PayloadReplaced(PayloadReplaced),
+}
+
+/// Result of sequencing a single flashblock
+#[derive(Debug, Default)]
+pub struct Observation {
+    pub events: Vec<SequenceEvent>,
+    /// The flashblock is a duplicate or belongs to an abandoned payload,
+    /// and should not be processed
+    pub skip: bool,
+}
+
+/// Progress through the payload currently being built
+#[derive(Debug)]
+struct PayloadProgress {
+    payload_id: String,
+    block_number: Option<u64>,
+    /// One past the highest index seen
+    next_index: u64,
+    seen: BTreeSet<u64>,
+    /// Base received with index 0
+    base: Option<ExecutionPayloadBase>,
+}
+
+/// Tracks the current payload and checks each flashblock's index against it
+#[derive(Debug, Default)]
+pub struct FlashblockSequencer {
+    current: Option<PayloadProgress>,
+    /// Payload the sequencer moved away from, to recognise late stragglers
+    previous_payload_id: Option<String>,
+}
+
+impl FlashblockSequencer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sequence a flashblock, then log and stream any events.
+    /// Returns false if the flashblock should not be processed.
+    pub fn observe(&mut self, fb: &Flashblock, stream: &StreamOutput) -> bool {
+        let observation = self.sequence(fb);
+
+        for event in &observation.events {
+            match event {
+                SequenceEvent::Gap(gap) if gap.kind == GapKind::JoinedMidPayload => {
+                    info!(
+                        payload_id = %gap.payload_id,
+                        block_number = ?gap.block_number,
+                        received_index = gap.received_index,
+                        "Joined payload mid-build"
+                    );
+                    stream.send("Flashblock_gap", gap).unwrap_or_else(|e| {
+                        error!("Failed to send flashblock gap to stream: {}", e);
+                    });
+                }
+                SequenceEvent::Gap(gap) => {
+                    warn!(
+                        kind = ?gap.kind,
+                        payload_id = %gap.payload_id,
+                        block_number = ?gap.block_number,
+                        expected_index = gap.expected_index,
+                        received_index = gap.received_index,
+                        missing = gap.missing_indices.len(),
+                        "Flashblock gap"
+                    );
+                    stream.send("Flashblock_gap", gap).unwrap_or_else(|e| {
+                        error!("Failed to send flashblock gap to stream: {}", e);
+                    });
+                }
+                SequenceEvent::PayloadReplaced(replaced) => {
+                    warn!(
+                        block_number = replaced.block_number,
+                        previous_payload_id = %replaced.previous_payload_id,
+                        previous_last_index = replaced.previous_last_index,
+                        new_payload_id = %replaced.new_payload_id,
+                        "Payload replaced"
+                    );
+                    stream
+                        .send("Payload_replaced", replaced)
+                        .unwrap_or_else(|e| {
+                            error!("Failed to send payload replacement to stream: {}", e);
+                        });
+                }
+            }
+        }
+
+        !observation.skip
+    }
+
+    /// Base of the payload currently being built, if its index 0 was seen
+    pub fn base_for(&self, payload_id: &str) -> Option<&ExecutionPayloadBase> {
+        self.current
+            .as_ref()
+            .filter(|p| p.payload_id == payload_id)
+            .and_then(|p| p.base.as_ref())
+    }
+
+    /// Check a flashblock's position and update the tracked payload
+    pub fn sequence(&mut self, fb: &Flashblock) -> Observation {
+        let block_number = fb.metadata.as_ref().map(|m| m.block_number);
+        let mut observation = Observation::default();
+
+        let is_current = self
+            .current
+            .as_ref()
+            .is_some_and(|p| p.payload_id == fb.payload_id);
+        let joined = !is_current && fb.index > 0;
+
+        if !is_current {
+            if self.previous_payload_id.as_deref() == Some(fb.payload_id.as_str()) {
+                observation.events.push(SequenceEvent::Gap(FlashblockGap {
+                    kind: GapKind::Stale,
+                    payload_id: fb.payload_id.clone(),
+                    block_number,
+                    expected_index: 0,
+                    received_index: fb.index,
+                    missing_indices: Vec::new(),
+                }));
+                observation.skip = true;
+                return observation;
+            }
+
+            if let Some(previous) = self.current.take() {
+                if previous.block_number.is_some() && previous.block_number == block_number {
+                    observation
+                        .events
+                        .push(SequenceEvent::PayloadReplaced(PayloadReplaced {
+                            block_number: block_number.unwrap_or_default(),
+                            previous_payload_id: previous.payload_id.clone(),
+                            previous_last_index: previous.next_index.saturating_sub(1),
+                            new_payload_id: fb.payload_id.clone(),
+                        }));
+                }
+                self.previous_payload_id = Some(previous.payload_id);
+            }
+
+            self.current = Some(PayloadProgress {
+                payload_id: fb.payload_id.clone(),
+                block_number,
+                next_index: 0,
+                seen: BTreeSet::new(),
+                base: None,
+            });
+        }
+
+        let progress = self.current.as_mut().expect("current payload is set");
+        if progress.block_number.is_none() {
+            progress.block_number = block_number;
+        }
+
+        let gap = |kind, missing_indices| {
+            SequenceEvent::Gap(FlashblockGap {
+                kind,
+                payload_id: fb.payload_id.clone(),
+                block_number,
+                expected_index: progress.next_index,
+                received_index: fb.index,
+                missing_indices,
+            })
+        };
+
+        if fb.index == 0 && fb.base.is_none() {
+            observation
+                .events
+                .push(gap(GapKind::MissingBase, Vec::new()));
+        }
+
+        if fb.index > progress.next_index {
+            let missing = (progress.next_index..fb.index)
+                .filter(|i| !progress.seen.contains(i))
+                .collect();
+            let kind = if joined {
+                GapKind::JoinedMidPayload
+            } else {
+                GapKind::Missing
+            };
+            observation.events.push(gap(kind, missing));
+        } else if fb.index < progress.next_index {
+            if progress.seen.contains(&fb.index) {
+                observation.events.push(gap(GapKind::Duplicate, Vec::new()));
+                observation.skip = true;
+            } else {
+                observation
+                    .events
+                    .push(gap(GapKind::OutOfOrder, Vec::new()));
+            }
+        }
+
+        if progress.base.is_none() {
+            progress.base = fb.base.clone();
+        }
+        progress.seen.insert(fb.index);
+        progress.next_index = progress.next_index.max(fb.index + 1);
+
+        observation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flashblock(payload_id: &str, index: u64, block_number: u64) -> Flashblock {
+        let mut fb: Flashblock = serde_json::from_str(&format!(
+            r#"{{"payload_id":"{payload_id}","index":{index},"metadata":{{"receipts":{{}},"new_account_balances":{{}},"block_number":{block_number}}}}}"#
+        ))
+        .unwrap();
+        if index == 0 {
+            fb.base = Some(ExecutionPayloadBase {
+                parent_beacon_block_root: Default::default(),
+                parent_hash: Default::default(),
+                fee_recipient: Default::default(),
+                prev_randao: Default::default(),
+                block_number,
+                gas_limit: 60_000_000,
+                timestamp: 1_700_000_000 + block_number * 2,
+                extra_data: Default::default(),
+                base_fee_per_gas: Default::default(),
+            });
+        }
+        fb
+    }
+
+    fn gap_kinds(observation: &Observation) -> Vec<GapKind> {
+        observation
+            .events
+            .iter()
+            .filter_map(|e| match e {
+                SequenceEvent::Gap(gap) => Some(gap.kind),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_in_order_sequence() {
+        let mut sequencer = FlashblockSequencer::new();
+        for index in 0..5 {
+            let observation = sequencer.sequence(&flashblock("0x01", index, 100));
+            assert!(observation.events.is_empty());
+        }
+        // A new payload for the next block is a normal transition
+        let observation = sequencer.sequence(&flashblock("0x02", 0, 101));
+        assert!(observation.events.is_empty());
+    }
+
+    #[test]
+    fn test_missing_then_out_of_order() {
+        let mut sequencer = FlashblockSequencer::new();
+        sequencer.sequence(&flashblock("0x01", 0, 100));
+
+        let observation = sequencer.sequence(&flashblock("0x01", 3, 100));
+        assert_eq!(gap_kinds(&observation), vec![GapKind::Missing]);
+        let SequenceEvent::Gap(gap) = &observation.events[0] else {
+            panic!("expected a gap");
+        };
+        assert_eq!(gap.missing_indices, vec![1, 2]);
+
+        let observation = sequencer.sequence(&flashblock("0x01", 1, 100));
+        assert_eq!(gap_kinds(&observation), vec![GapKind::OutOfOrder]);
+        assert!(!observation.skip);
+    }
+
+    #[test]
+    fn test_base_carried_forward() {
+        let mut sequencer = FlashblockSequencer::new();
+        assert!(sequencer.base_for("0x01").is_none());
+
+        sequencer.sequence(&flashblock("0x01", 0, 100));
+        sequencer.sequence(&flashblock("0x01", 1, 100));
+        let base = sequencer.base_for("0x01").unwrap();
+        assert_eq!(base.block_number, 100);
+        assert_eq!(base.timestamp, 1_700_000_200);
+
+        sequencer.sequence(&flashblock("0x02", 1, 101));
+        assert!(sequencer.base_for("0x01").is_none());
+        assert!(sequencer.base_for("0x02").is_none());
+    }
+
+    #[test]
+    fn test_duplicate() {
+        let mut sequencer = FlashblockSequencer::new();
+        sequencer.sequence(&flashblock("0x01", 0, 100));
+        sequencer.sequence(&flashblock("0x01", 1, 100));
+
+        let observation = sequencer.sequence(&flashblock("0x01", 1, 100));
+        assert_eq!(gap_kinds(&observation), vec![GapKind::Duplicate]);
+        assert!(observation.skip);
+    }
+
+    #[test]
+    fn test_new_payload_without_base() {
+        let mut sequencer = FlashblockSequencer::new();
+        let observation = sequencer.sequence(&flashblock("0x01", 2, 100));
+        assert_eq!(gap_kinds(&observation), vec![GapKind::JoinedMidPayload]);
+        let SequenceEvent::Gap(gap) = &observation.events[0] else {
+            panic!("expected a gap");
+        };
+        assert_eq!(gap.missing_indices, vec![0, 1]);
+
+        // Once joined, skipped indices are real gaps
+        let observation = sequencer.sequence(&flashblock("0x01", 4, 100));
+        assert_eq!(gap_kinds(&observation), vec![GapKind::Missing]);
+
+        let fb: Flashblock = serde_json::from_str(
+            r#"{"payload_id":"0x02","index":0,"metadata":{"receipts":{},"new_account_balances":{},"block_number":101}}"#,
+        )
+        .unwrap();
+        let observation = sequencer.sequence(&fb);
+        assert_eq!(gap_kinds(&observation), vec![GapKind::MissingBase]);
+    }
+
+    #[test]
+    fn test_payload_replaced_and_stale() {
+        let mut sequencer = FlashblockSequencer::new();
+        sequencer.sequence(&flashblock("0x01", 0, 100));
+        sequencer.sequence(&flashblock("0x01", 1, 100));
+
+        let observation = sequencer.sequence(&flashblock("0x02", 0, 100));
+        assert_eq!(
+            observation.events,
+            vec![SequenceEvent::PayloadReplaced(PayloadReplaced {
+                block_number: 100,
+                previous_payload_id: "0x01".to_string(),
+                previous_last_index: 1,
+                new_payload_id: "0x02".to_string(),
+            })]
+        );
+
+        let observation = sequencer.sequence(&flashblock("0x01", 2, 100));
+        assert_eq!(gap_kinds(&observation), vec![GapKind::Stale]);
+        assert!(observation.skip);
+    }
+}
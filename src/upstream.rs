@@ -0,0 +1,514 @@
+//! Supervised connection to the upstream Flashblocks WebSocket.
+//!
+//! The supervisor reconnects with jittered exponential backoff whenever the
+//! connection errors, closes, or stays idle for longer than the read timeout.
+//! Each upstream runs in its own task and forwards parsed flashblocks over a
+//! channel, so the downstream `StreamOutput` and its connected clients keep
+//! running across reconnects and while other upstreams are down.
+
+use std::time::{Duration, Instant};
+
+use flashblocks_types::flashblocks::Flashblock;
+use futures_util::StreamExt;
+use rand::Rng;
+use serde::Serialize;
+use thiserror::Error;
+use tokio::sync::{mpsc, watch};
+use tokio_tungstenite::connect_async;
+use tokio_tungstenite::tungstenite::protocol::Message;
+use tracing::{debug, error, info, warn};
+
+use crate::record::{FrameKind, Recorder};
+use crate::utils::decompress_brotli;
+
+#[derive(Error, Debug)]
+pub enum UpstreamError {
+    #[error("Gave up on {url} after {attempts} reconnect attempts: {last_error}")]
+    GaveUp {
+        url: String,
+        attempts: u32,
+        last_error: String,
+    },
+}
+
+/// Reconnect policy for the upstream connection
+#[derive(Debug, Clone)]
+pub struct ReconnectConfig {
+    /// Delay before the first reconnect attempt
+    pub initial_backoff: Duration,
+    /// Upper bound for the delay between reconnect attempts
+    pub max_backoff: Duration,
+    /// Reconnect when no frame has been received for this long
+    pub idle_timeout: Duration,
+    /// Give up after this many consecutive failed attempts (`None` retries forever)
+    pub max_attempts: Option<u32>,
+}
+
+/// Exponential backoff with equal jitter.
+///
+/// Each delay is drawn uniformly from `[base / 2, base]`, where `base` doubles
+/// on every attempt until it reaches `max`.
+#[derive(Debug)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    attempt: u32,
+}
+
+impl Backoff {
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Self {
+            initial,
+            max,
+            attempt: 0,
+        }
+    }
+
+    /// Number of delays handed out since the last reset
+    pub fn attempts(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Upper bound of the next delay, before jitter is applied
+    fn base(&self) -> Duration {
+        let factor = 2u32.saturating_pow(self.attempt.min(31));
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+
+    /// Get the delay before the next attempt and advance the backoff
+    pub fn next_delay(&mut self) -> Duration {
+        let half = self.base() / 2;
+        self.attempt = self.attempt.saturating_add(1);
+        half + half.mul_f64(rand::thread_rng().r#gen::<f64>())
+    }
+
+    /// Reset after a healthy connection
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+/// Position of a flashblock in the chain: block number and flashblock index
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct FlashblockCursor {
+    pub block_number: u64,
+    pub index: u64,
+}
+
+/// Emitted on the first flashblock received after a reconnect.
+///
+/// Describes what the digestor may have missed while the upstream was down.
+#[derive(Debug, Clone, Serialize)]
+pub struct UpstreamResumed {
+    /// Upstream URL that was reconnected
+    pub url: String,
+    /// Last flashblock processed before the connection dropped
+    pub last_seen: FlashblockCursor,
+    /// First flashblock processed after the reconnect
+    pub resumed_at: FlashblockCursor,
+    /// Blocks skipped entirely between `last_seen` and `resumed_at`
+    pub missed_blocks: u64,
+    /// Flashblocks skipped, known exactly only when resuming within the same block
+    pub missed_flashblocks: Option<u64>,
+    /// Time between losing the connection and the first flashblock after it
+    pub downtime_ms: u64,
+    /// Reconnect attempts it took to resume
+    pub reconnect_attempts: u32,
+}
+
+impl UpstreamResumed {
+    pub fn new(
+        url: &str,
+        last_seen: FlashblockCursor,
+        resumed_at: FlashblockCursor,
+        downtime: Duration,
+        reconnect_attempts: u32,
+    ) -> Self {
+        let (missed_blocks, missed_flashblocks) =
+            if resumed_at.block_number == last_seen.block_number {
+                (
+                    0,
+                    Some(resumed_at.index.saturating_sub(last_seen.index + 1)),
+                )
+            } else {
+                (
+                    resumed_at
+                        .block_number
+                        .saturating_sub(last_seen.block_number + 1),
+                    None,
+                )
+            };
+
+        Self {
+            url: url.to_string(),
+            last_seen,
+            resumed_at,
+            missed_blocks,
+            missed_flashblocks,
+            downtime_ms: downtime.as_millis() as u64,
+            reconnect_attempts,
+        }
+    }
+}
+
+/// Index of an upstream in the `--url` list
+pub type UpstreamId = usize;
+
+/// Events sent from an upstream supervisor to the ingestion loop
+#[derive(Debug)]
+pub enum UpstreamEvent {
+    /// A flashblock parsed from an upstream frame
+    Flashblock {
+        upstream: UpstreamId,
+        received_at: Instant,
+        flashblock: Box<Flashblock>,
+    },
+    /// A frame that could not be decoded or parsed
+    BadFrame { upstream: UpstreamId },
+    /// The connection was lost and will be retried
+    Lost {
+        upstream: UpstreamId,
+        reason: String,
+    },
+    /// The first flashblock after a reconnect arrived
+    Resumed {
+        upstream: UpstreamId,
+        resumed: UpstreamResumed,
+    },
+    /// The supervisor ran out of reconnect attempts and stopped
+    GaveUp {
+        upstream: UpstreamId,
+        error: UpstreamError,
+    },
+}
+
+/// Why a single connection session ended
+enum SessionEnd {
+    /// Shutdown was requested, or the ingestion loop went away
+    Shutdown,
+    /// The upstream went away; the reason is logged and kept for `GaveUp`
+    Lost(String),
+}
+
+/// Outage bookkeeping carried across sessions
+struct Outage {
+    since: Instant,
+    attempts: u32,
+}
+
+/// Reconnect state for a single upstream URL
+struct Supervisor {
+    id: UpstreamId,
+    url: String,
+    config: ReconnectConfig,
+    backoff: Backoff,
+    events: mpsc::Sender<UpstreamEvent>,
+    /// Appends every raw data frame to disk when `--record` is set
+    recorder: Option<Recorder>,
+    /// Last flashblock received from this upstream
+    last_seen: Option<FlashblockCursor>,
+    /// Set while the upstream is down, cleared by the first flashblock after it
+    outage: Option<Outage>,
+}
+
+/// Connect to `url` and forward every parsed flashblock to `events` until shutdown.
+///
+/// Reconnects on errors, closes and idle timeouts. Intended to be spawned
+/// once per upstream; the task ends on shutdown, when the receiver is
+/// dropped, or after sending `GaveUp`.
+pub async fn supervise(
+    id: UpstreamId,
+    url: String,
+    config: ReconnectConfig,
+    mut shutdown: watch::Receiver<bool>,
+    events: mpsc::Sender<UpstreamEvent>,
+    recorder: Option<Recorder>,
+) {
+    let mut supervisor = Supervisor {
+        id,
+        backoff: Backoff::new(config.initial_backoff, config.max_backoff),
+        url,
+        config,
+        events,
+        recorder,
+        last_seen: None,
+        outage: None,
+    };
+
+    loop {
+        let reason = match supervisor.run_session(&mut shutdown).await {
+            SessionEnd::Shutdown => return,
+            SessionEnd::Lost(reason) => reason,
+        };
+
+        let delay = match supervisor.on_lost(reason).await {
+            Ok(delay) => delay,
+            Err(error) => {
+                let _ = supervisor
+                    .events
+                    .send(UpstreamEvent::GaveUp {
+                        upstream: supervisor.id,
+                        error,
+                    })
+                    .await;
+                return;
+            }
+        };
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = shutdown.changed() => return,
+        }
+    }
+}
+
+impl Supervisor {
+    /// Send an event to the ingestion loop; false if it has gone away
+    async fn emit(&self, event: UpstreamEvent) -> bool {
+        self.events.send(event).await.is_ok()
+    }
+
+    /// Record a lost connection and get the delay before reconnecting
+    async fn on_lost(&mut self, reason: String) -> Result<Duration, UpstreamError> {
+        if self
+            .config
+            .max_attempts
+            .is_some_and(|max| self.backoff.attempts() >= max)
+        {
+            return Err(UpstreamError::GaveUp {
+                url: self.url.clone(),
+                attempts: self.backoff.attempts(),
+                last_error: reason,
+            });
+        }
+
+        let delay = self.backoff.next_delay();
+        let outage = self.outage.get_or_insert_with(|| Outage {
+            since: Instant::now(),
+            attempts: 0,
+        });
+        outage.attempts = self.backoff.attempts();
+
+        warn!(
+            url = %self.url,
+            attempt = self.backoff.attempts(),
+            delay_ms = delay.as_millis() as u64,
+            "Upstream lost ({reason}), reconnecting"
+        );
+        self.emit(UpstreamEvent::Lost {
+            upstream: self.id,
+            reason,
+        })
+        .await;
+        Ok(delay)
+    }
+
+    /// Forward a parsed flashblock, reporting the gap first if it ends an outage
+    async fn on_flashblock(&mut self, received_at: Instant, flashblock: Flashblock) -> bool {
+        // The connection is healthy once it delivers a flashblock
+        self.backoff.reset();
+
+        let cursor = flashblock.metadata.as_ref().map(|meta| FlashblockCursor {
+            block_number: meta.block_number,
+            index: flashblock.index,
+        });
+
+        if let Some(cursor) = cursor {
+            if let Some(outage) = self.outage.take()
+                && let Some(last) = self.last_seen
+            {
+                let resumed = UpstreamResumed::new(
+                    &self.url,
+                    last,
+                    cursor,
+                    outage.since.elapsed(),
+                    outage.attempts,
+                );
+                warn!(
+                    url = %self.url,
+                    last_block = last.block_number,
+                    last_index = last.index,
+                    resumed_block = cursor.block_number,
+                    resumed_index = cursor.index,
+                    missed_blocks = resumed.missed_blocks,
+                    missed_flashblocks = ?resumed.missed_flashblocks,
+                    downtime_ms = resumed.downtime_ms,
+                    "Upstream resumed"
+                );
+                self.emit(UpstreamEvent::Resumed {
+                    upstream: self.id,
+                    resumed,
+                })
+                .await;
+            }
+            self.last_seen = Some(cursor);
+        }
+
+        self.emit(UpstreamEvent::Flashblock {
+            upstream: self.id,
+            received_at,
+            flashblock: Box::new(flashblock),
+        })
+        .await
+    }
+
+    async fn run_session(&mut self, shutdown: &mut watch::Receiver<bool>) -> SessionEnd {
+        if *shutdown.borrow() {
+            return SessionEnd::Shutdown;
+        }
+
+        let idle_timeout = self.config.idle_timeout;
+
+        info!("Connecting to Flashblocks WebSocket: {}", self.url);
+        let connect = tokio::time::timeout(idle_timeout, connect_async(self.url.as_str()));
+        let mut ws_stream = tokio::select! {
+            res = connect => match res {
+                Ok(Ok((ws_stream, _))) => ws_stream,
+                Ok(Err(e)) => return SessionEnd::Lost(format!("connect failed: {e}")),
+                Err(_) => return SessionEnd::Lost("connect timed out".to_string()),
+            },
+            _ = shutdown.changed() => return SessionEnd::Shutdown,
+        };
+        info!("Connected to {}. Streaming Flashblocks…", self.url);
+
+        loop {
+            let msg_result = tokio::select! {
+                res = tokio::time::timeout(idle_timeout, ws_stream.next()) => match res {
+                    Ok(Some(msg_result)) => msg_result,
+                    Ok(None) => return SessionEnd::Lost("stream ended".to_string()),
+                    Err(_) => {
+                        return SessionEnd::Lost(format!(
+                            "no frames for {}s",
+                            idle_timeout.as_secs_f64()
+                        ));
+                    }
+                },
+                _ = shutdown.changed() => {
+                    info!("Shutting down stream reader for {}.", self.url);
+                    return SessionEnd::Shutdown;
+                }
+            };
+            let received_at = Instant::now();
+
+            let (kind, payload) = match msg_result {
+                Ok(Message::Text(text)) => (FrameKind::Text, text.into_bytes()),
+                Ok(Message::Binary(bin)) => (FrameKind::Binary, bin),
+                Ok(Message::Ping(_)) | Ok(Message::Pong(_)) | Ok(Message::Frame(_)) => {
+                    // Ignore; tungstenite handles pings/pongs at the protocol level.
+                    info!("Received control frame");
+                    continue;
+                }
+                Ok(Message::Close(frame)) => {
+                    info!("WebSocket closed: {frame:?}");
+                    return SessionEnd::Lost(format!("closed by upstream: {frame:?}"));
+                }
+                Err(e) => {
+                    error!("WebSocket read error: {e}");
+                    return SessionEnd::Lost(format!("read error: {e}"));
+                }
+            };
+
+            if let Some(recorder) = &self.recorder {
+                recorder.record(self.id, kind, &payload);
+            }
+            let flashblock = decode_frame(kind, &payload);
+
+            let delivered = match flashblock {
+                Some(flashblock) => self.on_flashblock(received_at, flashblock).await,
+                None => {
+                    self.emit(UpstreamEvent::BadFrame { upstream: self.id })
+                        .await
+                }
+            };
+            if !delivered {
+                return SessionEnd::Shutdown;
+            }
+        }
+    }
+}
+
+/// Decode a raw data frame into a `Flashblock`
+pub fn decode_frame(kind: FrameKind, payload: &[u8]) -> Option<Flashblock> {
+    match kind {
+        FrameKind::Text => match std::str::from_utf8(payload) {
+            Ok(text) => parse_flashblock(text),
+            Err(e) => {
+                warn!("Text frame is not valid UTF-8: {e}");
+                None
+            }
+        },
+        // Binary frames are Brotli-compressed JSON
+        FrameKind::Binary => match decompress_brotli(payload) {
+            Ok(text) => parse_flashblock(&text),
+            Err(e) => {
+                warn!("Failed to decompress binary frame: {e}");
+                info!("Binary frame ({} bytes)", payload.len());
+                None
+            }
+        },
+    }
+}
+
+/// Parse a decoded frame into a `Flashblock`, logging frames that don't match the schema
+fn parse_flashblock(text: &str) -> Option<Flashblock> {
+    match serde_json::from_str::<Flashblock>(text) {
+        Ok(fb) => Some(fb),
+        Err(e) => {
+            // If the schema changes or we get some other message type, dump the raw JSON.
+            warn!(error = %e, "Failed to parse Flashblock JSON");
+            debug!("Raw message: {text}");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_grows_and_caps() {
+        let mut backoff = Backoff::new(Duration::from_millis(100), Duration::from_secs(1));
+        let delays: Vec<_> = (0..8).map(|_| backoff.next_delay()).collect();
+
+        assert!(delays[0] >= Duration::from_millis(50) && delays[0] <= Duration::from_millis(100));
+        assert!(delays[3] >= Duration::from_millis(400) && delays[3] <= Duration::from_millis(800));
+        for delay in &delays[4..] {
+            assert!(*delay >= Duration::from_millis(500) && *delay <= Duration::from_secs(1));
+        }
+
+        backoff.reset();
+        assert_eq!(backoff.attempts(), 0);
+        assert!(backoff.next_delay() <= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_resume_gap_same_block() {
+        let last = FlashblockCursor {
+            block_number: 100,
+            index: 2,
+        };
+        let resumed_at = FlashblockCursor {
+            block_number: 100,
+            index: 6,
+        };
+        let resumed = UpstreamResumed::new("ws://x", last, resumed_at, Duration::ZERO, 1);
+        assert_eq!(resumed.missed_blocks, 0);
+        assert_eq!(resumed.missed_flashblocks, Some(3));
+    }
+
+    #[test]
+    fn test_resume_gap_across_blocks() {
+        let last = FlashblockCursor {
+            block_number: 100,
+            index: 9,
+        };
+        let resumed_at = FlashblockCursor {
+            block_number: 103,
+            index: 1,
+        };
+        let resumed = UpstreamResumed::new("ws://x", last, resumed_at, Duration::ZERO, 2);
+        assert_eq!(resumed.missed_blocks, 2);
+        assert_eq!(resumed.missed_flashblocks, None);
+    }
+}